@@ -0,0 +1,777 @@
+use super::FractalApp;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the bifurcation diagram of 1D maps like the logistic map x -> r x (1 - x).
+Every column of the diagram is one value of the parameter: the map is iterated until the orbit
+has settled down, and the points it visits after that are plotted above the parameter. As the
+parameter grows, the orbit goes from one point to a cycle of 2, 4, 8... points, and after this
+period doubling cascade it becomes chaotic, with windows of order (like the period 3 window)
+inside the chaos. The curve below the diagram is the Lyapunov exponent, which is negative where
+the orbit is a cycle and positive where it is chaotic.
+The parameters where the cycles double get closer by the same ratio every time, Feigenbaum's
+delta = 4.669..., and the forks shrink by his alpha = -2.502...; this is the same for every map
+with one smooth hump. They are measured at the superstable parameters, where the hump's top is
+part of the cycle, which are found by bisection.
+Fractal Explanation: https://en.wikipedia.org/wiki/Bifurcation_diagram
+Source: https://en.wikipedia.org/wiki/Feigenbaum_constants
+*/
+
+pub struct BifurcationDiagram {
+    map: UnimodalMap,                   // The map that is iterated
+    view: [f64; 4],                     // The parameter and x ranges (r0, x0, r1, x1) shown
+    transient: usize,                   // The iterations skipped while the orbit settles
+    points: usize,                      // The iterations plotted in every column
+    brightness: f32,                    // Brightens rarely hit pixels, 1 is a plain log scale
+    color: Color32,                     // The color of the diagram
+    show_feigenbaum: bool,              // If true, the period doublings are marked
+    show_lyapunov: bool,                // If true, the Lyapunov exponent is drawn below
+    analysis: Option<Analysis>,         // The cascade and the windows of the map
+    selection: Option<Pos2>,            // Where the box that is being dragged started
+    exponents: Vec<f32>,                // The Lyapunov exponent of every column
+    texture: Option<(TextureId, Vec2)>, // The rendered diagram and its size
+    last_input: Option<ImageInput>,     // The input of the rendered diagram
+}
+
+// The maps with one hump that can be iterated, all with a single parameter
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum UnimodalMap {
+    Logistic,
+    Sine,
+    Tent,
+    Gauss,
+}
+
+// The width of the Gauss map's hump
+const GAUSS_ALPHA: f64 = 6.2;
+
+impl UnimodalMap {
+    const ALL: [UnimodalMap; 4] = [
+        UnimodalMap::Logistic,
+        UnimodalMap::Sine,
+        UnimodalMap::Tent,
+        UnimodalMap::Gauss,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            UnimodalMap::Logistic => "Logistic: r x (1 - x)",
+            UnimodalMap::Sine => "Sine: r sin(pi x)",
+            UnimodalMap::Tent => "Tent: r min(x, 1 - x)",
+            UnimodalMap::Gauss => "Gauss: exp(-6.2 x^2) + r",
+        }
+    }
+
+    pub(super) fn iterate(self, x: f64, r: f64) -> f64 {
+        match self {
+            UnimodalMap::Logistic => r * x * (1.0 - x),
+            UnimodalMap::Sine => r * (std::f64::consts::PI * x).sin(),
+            UnimodalMap::Tent => r * x.min(1.0 - x),
+            UnimodalMap::Gauss => (-GAUSS_ALPHA * x * x).exp() + r,
+        }
+    }
+
+    // The slope of the map, the Lyapunov exponent is the average of its log
+    pub(super) fn derivative(self, x: f64, r: f64) -> f64 {
+        match self {
+            UnimodalMap::Logistic => r * (1.0 - 2.0 * x),
+            UnimodalMap::Sine => r * std::f64::consts::PI * (std::f64::consts::PI * x).cos(),
+            UnimodalMap::Tent => r,
+            UnimodalMap::Gauss => -2.0 * GAUSS_ALPHA * x * (-GAUSS_ALPHA * x * x).exp(),
+        }
+    }
+
+    // The range of the parameter, and the range of x that the orbits stay in
+    fn ranges(self) -> [f64; 4] {
+        match self {
+            UnimodalMap::Logistic => [0.0, 0.0, 4.0, 1.0],
+            UnimodalMap::Sine => [0.0, 0.0, 1.0, 1.0],
+            UnimodalMap::Tent => [0.0, 0.0, 2.0, 1.0],
+            UnimodalMap::Gauss => [-1.0, -1.0, 1.0, 1.5],
+        }
+    }
+
+    // The part of the ranges where the map does something interesting
+    fn default_view(self) -> [f64; 4] {
+        match self {
+            UnimodalMap::Logistic => [2.8, 0.0, 4.0, 1.0],
+            UnimodalMap::Sine => [0.7, 0.0, 1.0, 1.0],
+            UnimodalMap::Tent => [0.9, 0.0, 2.0, 1.0],
+            UnimodalMap::Gauss => [-1.0, -1.0, 1.0, 1.5],
+        }
+    }
+
+    // The top of the hump, whose orbit finds the attracting cycle if there is one
+    fn critical_point(self) -> f64 {
+        match self {
+            UnimodalMap::Gauss => 0.0,
+            _ => 0.5,
+        }
+    }
+
+    // The parameter where the top of the hump is a fixed point, the start of the cascade. The
+    // tent map has a corner instead of a smooth hump, and doesn't double its cycles.
+    fn first_superstable(self) -> Option<f64> {
+        match self {
+            UnimodalMap::Logistic => Some(2.0),
+            UnimodalMap::Sine => Some(0.5),
+            UnimodalMap::Tent => None,
+            UnimodalMap::Gauss => Some(-1.0),
+        }
+    }
+}
+
+// What is known about a map before drawing it: the superstable parameters of the period doubling
+// cascade and the windows of order
+#[derive(Clone, PartialEq)]
+struct Analysis {
+    map: UnimodalMap,
+    superstable: Vec<f64>, // The parameters where the cycle of 2^n is superstable
+    windows: Vec<Window>,  // The widest windows of order, from left to right
+}
+
+// A range of parameters inside the chaos where the orbit settles on a cycle again
+#[derive(Clone, Copy, PartialEq)]
+struct Window {
+    period: usize, // The period of the window's own cycle, before it doubles
+    start: f64,
+    end: f64,
+}
+
+// Feigenbaum's constants
+const FEIGENBAUM_DELTA: f64 = 4.669_201_609_102_99;
+const FEIGENBAUM_ALPHA: f64 = -2.502_907_875_095_89;
+
+// The most period doublings that are found, beyond this the parameters are too close together
+// for 64 bit floats
+const MAX_DOUBLINGS: usize = 11;
+
+// The number of parameters checked for windows, and the longest cycle that is looked for
+const WINDOW_SCAN: usize = 8_000;
+const MAX_PERIOD: usize = 32;
+const MAX_WINDOWS: usize = 8;
+
+// Everything the rendered diagram is made from: the map, the view, the transient and plotted
+// iterations, the brightness, the color and the size of the image
+type ImageInput = (UnimodalMap, [f64; 4], usize, usize, f32, Color32, Vec2);
+
+impl Default for BifurcationDiagram {
+    fn default() -> Self {
+        Self {
+            map: UnimodalMap::Logistic,
+            view: UnimodalMap::Logistic.default_view(),
+            transient: 1_000,
+            points: 500,
+            brightness: 1.5,
+            color: Color32::from_rgb(120, 200, 255),
+            show_feigenbaum: true,
+            show_lyapunov: true,
+            analysis: None,
+            selection: None,
+            exponents: Vec::new(),
+            texture: None,
+            last_input: None,
+        }
+    }
+}
+
+impl epi::App for BifurcationDiagram {
+    fn name(&self) -> &str {
+        "Bifurcation Diagram"
+    }
+
+    // Called every frame
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui, frame));
+    }
+}
+
+impl BifurcationDiagram {
+    // Paints the fractal
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter, frame);
+
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        let settings = Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+                if self.show_feigenbaum {
+                    CollapsingHeader::new("Feigenbaum Ratios").show(ui, |ui| self.ratios_ui(ui));
+                }
+            });
+
+        // After the settings, so that their sliders get dragged instead of the diagram
+        self.navigate(ui, &painter, settings.response.rect);
+    }
+
+    // An options window for the map, the view and the iterations
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let map = self.map;
+        ComboBox::from_label("Map")
+            .selected_text(map.name())
+            .show_ui(ui, |ui| {
+                for choice in UnimodalMap::ALL {
+                    if ui.selectable_label(map == choice, choice.name()).clicked() {
+                        self.map = choice;
+                        self.view = choice.default_view();
+                    }
+                }
+            });
+
+        let [r0, x0, r1, x1] = self.view;
+        ui.label(format!("r: {:.8} to {:.8}", r0, r1));
+        ui.label(format!("x: {:.6} to {:.6}", x0, x1));
+        ui.label("Drag a box to zoom in, scroll to zoom out");
+        if ui.button("Reset View").clicked() {
+            self.view = self.map.default_view();
+        }
+
+        ui.label("Windows of order:");
+        let windows = self.analysis().windows.clone();
+        if windows.is_empty() {
+            ui.label("None, the map has no windows of order");
+        }
+        ui.horizontal_wrapped(|ui| {
+            for window in windows {
+                let text = format!("Period {} at {:.4}", window.period, window.start);
+                if ui.button(text).clicked() {
+                    self.zoom_to(window);
+                }
+            }
+        });
+
+        ui.separator();
+        ui.add(
+            Slider::new(&mut self.transient, 10..=20_000)
+                .logarithmic(true)
+                .text("Transient"),
+        );
+        ui.add(
+            Slider::new(&mut self.points, 10..=5_000)
+                .logarithmic(true)
+                .text("Points"),
+        );
+        ui.add(Slider::new(&mut self.brightness, 0.2..=4.0).text("Brightness"));
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            ui.color_edit_button_srgba(&mut self.color);
+        });
+        ui.checkbox(&mut self.show_feigenbaum, "Show the period doublings");
+        ui.checkbox(&mut self.show_lyapunov, "Show the Lyapunov exponent");
+        // The texture is kept out of the reset, so the next paint frees it when it draws the default
+        // diagram
+        let texture = self.texture.take();
+        egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    // A table of the superstable parameters and the ratios between them, next to the constants
+    // they converge to
+    fn ratios_ui(&mut self, ui: &mut Ui) {
+        let map = self.map;
+        let superstable = self.analysis().superstable.clone();
+        if superstable.len() < 3 {
+            ui.label("The map has no period doubling cascade");
+            return;
+        }
+
+        Grid::new("feigenbaum_ratios").striped(true).show(ui, |ui| {
+            ui.label("Period");
+            ui.label("r");
+            ui.label("delta");
+            ui.label("alpha");
+            ui.end_row();
+            for (n, &r) in superstable.iter().enumerate() {
+                ui.label(format!("{}", 1 << n));
+                ui.label(format!("{:.10}", r));
+                if n >= 2 {
+                    let delta =
+                        (superstable[n - 1] - superstable[n - 2]) / (r - superstable[n - 1]);
+                    ui.label(format!("{:.5}", delta));
+                } else {
+                    ui.label("");
+                }
+                if n >= 2 {
+                    let alpha = fork_width(map, superstable[n - 1], n - 1) / fork_width(map, r, n);
+                    ui.label(format!("{:.5}", alpha));
+                } else {
+                    ui.label("");
+                }
+                ui.end_row();
+            }
+        });
+        ui.label(format!(
+            "Feigenbaum's constants: delta = {:.5}, alpha = {:.5}",
+            FEIGENBAUM_DELTA, FEIGENBAUM_ALPHA
+        ));
+        if let Some(onset) = onset_of_chaos(&superstable) {
+            ui.label(format!("Onset of chaos: r = {:.8}", onset));
+        }
+    }
+
+    // The cascade and the windows of the map, found again when the map changes
+    fn analysis(&mut self) -> &Analysis {
+        let map = self.map;
+        if self
+            .analysis
+            .as_ref()
+            .is_none_or(|analysis| analysis.map != map)
+        {
+            self.analysis = Some(Analysis {
+                map,
+                superstable: superstable_parameters(map),
+                windows: windows_of_order(map),
+            });
+        }
+        self.analysis.as_ref().unwrap()
+    }
+
+    // Shows a window of order with a bit of the chaos around it
+    fn zoom_to(&mut self, window: Window) {
+        let [_, x0, _, x1] = self.map.ranges();
+        let margin = 0.15 * (window.end - window.start);
+        self.view = [window.start - margin, x0, window.end + margin, x1];
+    }
+
+    // Splits the screen into the diagram and the strip along the bottom where the Lyapunov
+    // exponent is drawn, which is empty when the exponent is hidden
+    fn layout(&self, rect: Rect) -> (Rect, Rect) {
+        let strip = if self.show_lyapunov {
+            (0.2 * rect.height()).round()
+        } else {
+            0.0
+        };
+        let split = rect.bottom() - strip;
+        (
+            Rect::from_min_max(rect.min, pos2(rect.right(), split)),
+            Rect::from_min_max(pos2(rect.left(), split), rect.max),
+        )
+    }
+
+    // Where the diagram is drawn, with the parameter going right and x going up. Unlike the
+    // other apps the view is stretched to fill the screen.
+    fn to_screen(&self, rect: Rect, r: f64, x: f64) -> Pos2 {
+        let [r0, x0, r1, x1] = self.view;
+        pos2(
+            rect.left() + ((r - r0) / (r1 - r0)) as f32 * rect.width(),
+            rect.bottom() - ((x - x0) / (x1 - x0)) as f32 * rect.height(),
+        )
+    }
+
+    fn to_plane(&self, rect: Rect, p: Pos2) -> [f64; 2] {
+        let [r0, x0, r1, x1] = self.view;
+        [
+            r0 + ((p.x - rect.left()) / rect.width()) as f64 * (r1 - r0),
+            x0 + ((rect.bottom() - p.y) / rect.height()) as f64 * (x1 - x0),
+        ]
+    }
+
+    // Dragging a box zooms into it, and scrolling zooms in and out around the mouse
+    fn navigate(&mut self, ui: &mut Ui, painter: &Painter, settings: Rect) {
+        let (rect, _) = self.layout(painter.clip_rect());
+        let response = ui.interact(rect, ui.id().with("bifurcation_view"), Sense::drag());
+        let pointer = ui.input().pointer.interact_pos();
+
+        if response.drag_started() {
+            self.selection = pointer;
+        }
+        if let (Some(start), Some(end)) = (self.selection, pointer) {
+            let selected = Rect::from_two_pos(start, end);
+            if response.dragged() {
+                painter.rect_stroke(selected, 0.0, (1.0, Color32::WHITE));
+            } else {
+                // small boxes are clicks by mistake
+                if selected.width() > 4.0 && selected.height() > 4.0 {
+                    let [r0, x0] = self.to_plane(rect, selected.left_bottom());
+                    let [r1, x1] = self.to_plane(rect, selected.right_top());
+                    self.view = [r0, x0, r1, x1];
+                }
+                self.selection = None;
+            }
+        }
+
+        let scroll = ui.input().scroll_delta.y;
+        let hover = ui.input().pointer.hover_pos();
+        if response.hovered() && scroll != 0.0 && !hover.is_some_and(|p| settings.contains(p)) {
+            let factor = (-scroll as f64 / 200.0).exp();
+            let [r, x] = hover.map_or(self.to_plane(rect, rect.center()), |p| {
+                self.to_plane(rect, p)
+            });
+            let [r0, x0, r1, x1] = self.view;
+            self.view = [
+                r - (r - r0) * factor,
+                x - (x - x0) * factor,
+                r + (r1 - r) * factor,
+                x + (x1 - x) * factor,
+            ];
+        }
+    }
+
+    // Renders the diagram by counting how many points of every column land on each pixel, and
+    // measures the Lyapunov exponent of every column on the way
+    fn diagram_image(&mut self, rect: Rect, background: Color32) -> Image {
+        let (width, height) = (rect.width() as usize, rect.height() as usize);
+        let [r0, x0, r1, x1] = self.view;
+        let mut counts = vec![0_u32; width * height];
+        self.exponents = vec![0.0; width];
+
+        for column in 0..width {
+            let r = r0 + (column as f64 + 0.5) / width as f64 * (r1 - r0);
+            let mut x = self.map.critical_point();
+            for _ in 0..self.transient {
+                x = self.map.iterate(x, r);
+            }
+            let mut sum = 0.0;
+            for _ in 0..self.points {
+                sum += self.map.derivative(x, r).abs().max(1e-300).ln();
+                x = self.map.iterate(x, r);
+                let row = (x1 - x) / (x1 - x0) * height as f64;
+                if row >= 0.0 && (row as usize) < height {
+                    counts[row as usize * width + column] += 1;
+                }
+            }
+            self.exponents[column] = (sum / self.points as f64) as f32;
+        }
+
+        let mut image = Image::new(width, height, background);
+        let max = counts.iter().copied().max().unwrap_or(0) as f32;
+        if max == 0.0 {
+            return image;
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let level = ((count as f32).ln_1p() / max.ln_1p()).powf(1.0 / self.brightness);
+            let shade = |channel: u8| (channel as f32 * level).round() as u8;
+            let color = Color32::from_rgba_premultiplied(
+                shade(self.color.r()),
+                shade(self.color.g()),
+                shade(self.color.b()),
+                shade(255),
+            );
+            image.blend((i % width) as i64, (i / width) as i64, color, 1.0);
+        }
+        image
+    }
+
+    // The lines drawn over the diagram, which mark the superstable parameters of the cascade and
+    // the onset of chaos, and the Lyapunov exponent in the strip below it
+    fn overlay_shapes(&mut self, rect: Rect, strip: Rect) -> Vec<Shape> {
+        let mut shapes = Vec::new();
+        if self.show_feigenbaum {
+            let superstable = self.analysis().superstable.clone();
+            let onset = onset_of_chaos(&superstable);
+            let doublings = superstable
+                .iter()
+                .map(|&r| (r, Color32::from_rgba_unmultiplied(255, 210, 80, 160)));
+            let chaos = onset.map(|r| (r, Color32::from_rgb(255, 120, 120)));
+            for (r, color) in doublings.chain(chaos) {
+                let x = self.to_screen(rect, r, 0.0).x;
+                if rect.x_range().contains(&x) {
+                    shapes.push(Shape::line_segment(
+                        [pos2(x, rect.top()), pos2(x, rect.bottom())],
+                        (1.0, color),
+                    ));
+                }
+            }
+        }
+
+        if self.show_lyapunov && !self.exponents.is_empty() {
+            // exponents from -2 to 1 fill the strip, with the chaos above zero
+            let to_y = |exponent: f32| {
+                strip.top() + (1.0 - exponent.clamp(-2.0, 1.0)) / 3.0 * strip.height()
+            };
+            shapes.push(Shape::line_segment(
+                [
+                    pos2(strip.left(), strip.top()),
+                    pos2(strip.right(), strip.top()),
+                ],
+                (1.0, Color32::from_gray(60)),
+            ));
+            shapes.push(Shape::line_segment(
+                [
+                    pos2(strip.left(), to_y(0.0)),
+                    pos2(strip.right(), to_y(0.0)),
+                ],
+                (1.0, Color32::from_gray(90)),
+            ));
+            let points: Vec<Pos2> = self
+                .exponents
+                .iter()
+                .enumerate()
+                .map(|(column, &exponent)| pos2(strip.left() + column as f32 + 0.5, to_y(exponent)))
+                .collect();
+            shapes.push(Shape::line(points, (1.0, Color32::from_rgb(255, 110, 160))));
+        }
+        shapes
+    }
+
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
+        let (rect, strip) = self.layout(painter.clip_rect());
+        let size = rect.size().round();
+        let input = (
+            self.map,
+            self.view,
+            self.transient,
+            self.points,
+            self.brightness,
+            self.color,
+            size,
+        );
+
+        // Need to recalculate due to a change in one of the settings
+        if self.last_input.as_ref() != Some(&input) {
+            let image =
+                self.diagram_image(Rect::from_min_size(rect.min, size), Color32::TRANSPARENT);
+            if let Some((texture, _)) = self.texture.take() {
+                frame.tex_allocator().free(texture);
+            }
+            let texture = frame
+                .tex_allocator()
+                .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+            self.texture = Some((texture, size));
+            self.last_input = Some(input);
+        }
+
+        if let Some((texture, size)) = self.texture {
+            let mut mesh = epaint::Mesh::with_texture(texture);
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+
+        let image_rect = Rect::from_min_size(rect.min, size);
+        painter.extend(self.overlay_shapes(image_rect, strip));
+        if self.show_lyapunov {
+            painter.text(
+                strip.left_top() + vec2(4.0, 4.0),
+                Align2::LEFT_TOP,
+                "Lyapunov exponent",
+                TextStyle::Small,
+                Color32::from_rgb(255, 110, 160),
+            );
+        }
+
+        // The periods of the cascade, where there is room for them
+        if self.show_feigenbaum {
+            let superstable = self.analysis().superstable.clone();
+            let mut last_label = f32::MIN;
+            for (n, &r) in superstable.iter().enumerate() {
+                let x = self.to_screen(image_rect, r, 0.0).x;
+                if image_rect.x_range().contains(&x) && x - last_label > 30.0 {
+                    painter.text(
+                        pos2(x + 3.0, image_rect.bottom() - 4.0),
+                        Align2::LEFT_BOTTOM,
+                        format!("{}", 1 << n),
+                        TextStyle::Small,
+                        Color32::from_rgb(255, 210, 80),
+                    );
+                    last_label = x;
+                }
+            }
+        }
+    }
+}
+
+// Iterates the map from the top of the hump for 2^n steps
+fn critical_orbit(map: UnimodalMap, r: f64, n: usize) -> f64 {
+    let mut x = map.critical_point();
+    for _ in 0..1_usize << n {
+        x = map.iterate(x, r);
+    }
+    x
+}
+
+// The distance from the top of the hump to the nearest point of the superstable cycle of 2^n,
+// which is on the other prong of the fork. It shrinks by Feigenbaum's alpha at every doubling.
+fn fork_width(map: UnimodalMap, r: f64, n: usize) -> f64 {
+    critical_orbit(map, r, n - 1) - map.critical_point()
+}
+
+// Finds the parameters where the cycle of 2^n goes through the top of the hump. Each one is the
+// first root of f^(2^n)(c) - c after the one before, skipping the root at the one before itself
+// (the cycles of 2^(n-1) are cycles of 2^n too).
+fn superstable_parameters(map: UnimodalMap) -> Vec<f64> {
+    let first = match map.first_superstable() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    let c = map.critical_point();
+    let [_, _, end, _] = map.ranges();
+    let mut parameters = vec![first];
+    let mut gap = end - first;
+    for n in 1..=MAX_DOUBLINGS {
+        let last = parameters[n - 1];
+        let g = |r: f64| critical_orbit(map, r, n) - c;
+        // after the first doubling, the next one can't be further than the last gap
+        let high = if n == 1 { end } else { last + 0.6 * gap };
+        match first_root(g, last + 0.02 * gap, high) {
+            Some(r) => {
+                gap = r - last;
+                parameters.push(r);
+            }
+            None => break,
+        }
+    }
+    parameters
+}
+
+// The first root of g between low and high, found by looking for a sign change and bisecting it
+fn first_root(g: impl Fn(f64) -> f64, low: f64, high: f64) -> Option<f64> {
+    let steps = 500;
+    let mut before = (low, g(low));
+    for i in 1..=steps {
+        let r = low + (high - low) * i as f64 / steps as f64;
+        let value = g(r);
+        if value.signum() != before.1.signum() {
+            let (mut low, mut high) = (before.0, r);
+            for _ in 0..60 {
+                let middle = (low + high) / 2.0;
+                if g(middle).signum() == before.1.signum() {
+                    low = middle;
+                } else {
+                    high = middle;
+                }
+            }
+            return Some((low + high) / 2.0);
+        }
+        before = (r, value);
+    }
+    None
+}
+
+// Where the cascade ends and the chaos starts, estimated from the last two doublings
+fn onset_of_chaos(superstable: &[f64]) -> Option<f64> {
+    if superstable.len() < 3 {
+        return None;
+    }
+    let (before, last) = (
+        superstable[superstable.len() - 2],
+        superstable[superstable.len() - 1],
+    );
+    Some(last + (last - before) / (FEIGENBAUM_DELTA - 1.0))
+}
+
+// The period of the cycle the orbit of the parameter settles on, None if it doesn't settle
+fn period(map: UnimodalMap, r: f64) -> Option<usize> {
+    let [_, x0, _, x1] = map.ranges();
+    let tolerance = 1e-7 * (x1 - x0);
+    let mut x = map.critical_point();
+    for _ in 0..2_000 {
+        x = map.iterate(x, r);
+    }
+    let start = x;
+    for p in 1..=MAX_PERIOD {
+        x = map.iterate(x, r);
+        if (x - start).abs() < tolerance {
+            return Some(p);
+        }
+    }
+    None
+}
+
+// Looks for ranges of parameters with a cycle whose period isn't a power of two, which only
+// happens inside the chaos. Each window keeps the period it starts with, before its own cascade.
+fn windows_of_order(map: UnimodalMap) -> Vec<Window> {
+    let [start, _, end, _] = map.ranges();
+    let mut windows: Vec<Window> = Vec::new();
+    let mut current: Option<Window> = None;
+    for i in 0..=WINDOW_SCAN {
+        let r = start + i as f64 / WINDOW_SCAN as f64 * (end - start);
+        match (period(map, r), current.as_mut()) {
+            // the window's own cascade doubles its period
+            (Some(p), Some(window)) if p % window.period == 0 => window.end = r,
+            (Some(p), _) if !p.is_power_of_two() => {
+                windows.extend(current.take());
+                current = Some(Window {
+                    period: p,
+                    start: r,
+                    end: r,
+                });
+            }
+            _ => windows.extend(current.take()),
+        }
+    }
+    windows.extend(current);
+
+    // a cycle that is slow to settle near its own doubling can split a window in two, and the
+    // Gauss map runs some cascades backwards so the doubled period comes first
+    let step = (end - start) / WINDOW_SCAN as f64;
+    let mut merged: Vec<Window> = Vec::new();
+    for window in windows {
+        match merged.last_mut() {
+            Some(last)
+                if window.start - last.end < 3.5 * step
+                    && (window.period % last.period == 0 || last.period % window.period == 0) =>
+            {
+                last.period = last.period.min(window.period);
+                last.end = window.end;
+            }
+            _ => merged.push(window),
+        }
+    }
+    let mut windows: Vec<Window> = merged.into_iter().filter(|w| w.end > w.start).collect();
+
+    windows.sort_by(|a, b| (b.end - b.start).total_cmp(&(a.end - a.start)));
+    windows.truncate(MAX_WINDOWS);
+    windows.sort_by(|a, b| a.start.total_cmp(&b.start));
+    windows
+}
+
+impl Animate for BifurcationDiagram {
+    fn parameters(&self) -> Vec<Parameter> {
+        let [start, _, end, _] = self.map.ranges();
+        let range = start as f32..=end as f32;
+        vec![
+            Parameter::new("Start", self.view[0] as f32, range.clone()),
+            Parameter::new("End", self.view[2] as f32, range),
+            Parameter::new("Transient", self.transient as f32, 10.0..=20_000.0),
+            Parameter::new("Points", self.points as f32, 10.0..=5_000.0),
+            Parameter::new("Brightness", self.brightness, 0.2..=4.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Start" => self.view[0] = value as f64,
+            "End" => self.view[2] = value as f64,
+            "Transient" => self.transient = value.round() as usize,
+            "Points" => self.points = value.round() as usize,
+            "Brightness" => self.brightness = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for BifurcationDiagram {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height, Color32::BLACK);
+        let screen = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        let (rect, strip) = self.layout(screen);
+        let diagram = self.diagram_image(rect, Color32::BLACK);
+        image.pixels[..diagram.pixels.len()].copy_from_slice(&diagram.pixels);
+        for shape in self.overlay_shapes(rect, strip) {
+            image.paint_shape(&shape);
+        }
+        image
+    }
+}
+
+impl FractalApp for BifurcationDiagram {}
@@ -1,24 +1,264 @@
+use super::FractalApp;
+use crate::dimension::{Counted, KnownDimension};
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use crate::words;
 use eframe::{egui, epi};
 use egui::{containers::*, widgets::*, *};
 
 #[derive(PartialEq)]
 
+/*
+This file contains the code for drawing the Fibonacci word fractal.
+The Fibonacci word is built like the Fibonacci numbers: S(0) = 0, S(1) = 01 and
+S(n) = S(n - 1) S(n - 2). The curve is drawn with the odd-even drawing rule: for the k-th digit
+(counting from 1) draw a segment forward, then if the digit is 0 turn left when k is even and
+right when k is odd. With a turn angle of 90 degrees this gives the classic fractal.
+The dense variant reads the word two digits at a time. The word never contains 11, so every pair
+is 00, 01 or 10, and each pair draws one segment and then goes straight, turns right or turns
+left. Other turn angles (like 60 degrees) give more variants of both curves.
+The word is never stored. Drawing a whole block S(k) always moves and turns the turtle the same
+way for the same state of the drawing rule, so these effects (and a circle around each block) are
+worked out once per block. The curve is then drawn by recursive descent, skipping the blocks that
+are off the screen and drawing the blocks that are smaller than a pixel as one line.
+Fractal Explanation: https://en.wikipedia.org/wiki/Fibonacci_word_fractal
+Source: https://hal.archives-ouvertes.fr/hal-00367972/document
+*/
+
 pub struct FibonacciWord {
-    zoom: f32,
-    start_line_width: f32,
-    n: u16,
-    dir: usize,
-    last_n: u16,
+    start_line_width: f32,                // The width of the lines
+    n: u16,                               // Which Fibonacci word is drawn
+    variant: Variant,                     // The rule that turns the word into turns
+    turn_angle: f32,                      // The angle (in degrees) of every turn
+    rotation: f32,                        // The angle (in degrees) the curve is rotated by
+    color: Color32,                       // The color of the curve
+    zoom: f32,                            // How far the view is zoomed in, 1 fits the curve
+    pan: [f64; 2],                        // How far the view is moved from the curve's center
+    detail: f32,                          // The smallest block (in points) that isn't one line
+    effects: Vec<[Effect; 3]>,            // The cached effect of every block S(k) and state
+    bounds: [f64; 4],                     // The bounding box of the curve
+    last_curve: (u16, Variant, f32, f32), // The n, variant, angle and rotation of the cache
+    drawn_count: usize,                   // The number of lines drawn in the last frame
+    analysis: Option<Analysis>,           // The cached analysis of the word, made when shown
+    min_palindrome: usize,                // The shortest palindrome listed in the analysis
+    sturmian_slope: f64,                  // The slope of the Sturmian word being compared
+    sturmian_intercept: f64,              // The intercept of the Sturmian word being compared
+}
+
+// The rule that turns the word into the turns of the curve
+#[derive(Clone, Copy, PartialEq)]
+enum Variant {
+    Standard,
+    Dense,
+}
+
+impl Variant {
+    const ALL: [Variant; 2] = [Variant::Standard, Variant::Dense];
+
+    fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::Dense => "Dense",
+        }
+    }
+
+    // Reads one letter of the word in a state of the drawing rule. Returns the next state, if a
+    // segment is drawn, and the turn made after it: 1 is left, -1 is right and 0 is straight.
+    // For the standard rule the state is whether the letter's position (counting from 1) is
+    // even (the unused state 2 acts like 0). For the dense rule it is the first letter of an
+    // unfinished pair, plus 1.
+    fn read(self, state: usize, letter: u8) -> (usize, bool, i64) {
+        match self {
+            Variant::Standard => {
+                let turn = match (letter, state == 1) {
+                    (b'0', true) => 1,
+                    (b'0', false) => -1,
+                    _ => 0,
+                };
+                ((state + 1) % 2, true, turn)
+            }
+            Variant::Dense => match (state, letter) {
+                (0, b'0') => (1, false, 0),
+                (0, _) => (2, false, 0),
+                (1, b'1') => (0, true, -1),
+                (2, b'0') => (0, true, 1),
+                _ => (0, true, 0),
+            },
+        }
+    }
+}
+
+// What drawing a whole block S(k) does to the turtle, starting in one state of the drawing rule.
+// Positions are relative to the turtle at the start of the block, heading to the right, with
+// segments of length 1.
+#[derive(Clone, Copy, PartialEq)]
+struct Effect {
+    end_state: usize, // The state of the drawing rule after the block
+    end: [f64; 2],    // Where the turtle ends up
+    turns: i64,       // How many turns to the left (negative to the right) the turtle made
+    center: [f64; 2], // The center of a circle around every segment of the block
+    radius: f64,      // The radius of that circle
+    segments: u64,    // The number of segments in the block
+}
+
+// Rotates a vector by an angle (in radians) in screen coordinates, where y points down
+fn rotate(v: [f64; 2], angle: f64) -> [f64; 2] {
+    let (sin, cos) = angle.sin_cos();
+    [v[0] * cos + v[1] * sin, -v[0] * sin + v[1] * cos]
+}
+
+impl Effect {
+    // The effect of walking a short word letter by letter
+    fn walk(variant: Variant, word: &[u8], state: usize, turn_angle: f64) -> Self {
+        let mut effect = Effect {
+            end_state: state,
+            end: [0.0, 0.0],
+            turns: 0,
+            center: [0.0, 0.0],
+            radius: 0.0,
+            segments: 0,
+        };
+        let (mut min, mut max) = ([0.0_f64, 0.0_f64], [0.0_f64, 0.0_f64]);
+        for &letter in word {
+            let (next, draws, turn) = variant.read(effect.end_state, letter);
+            if draws {
+                let step = rotate([1.0, 0.0], effect.turns as f64 * turn_angle);
+                effect.end = [effect.end[0] + step[0], effect.end[1] + step[1]];
+                effect.segments += 1;
+                for i in 0..2 {
+                    min[i] = min[i].min(effect.end[i]);
+                    max[i] = max[i].max(effect.end[i]);
+                }
+            }
+            effect.end_state = next;
+            effect.turns += turn;
+        }
+        effect.center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+        effect.radius = (max[0] - min[0]).hypot(max[1] - min[1]) / 2.0;
+        effect
+    }
+
+    // The effect of this block followed by another one
+    fn then(&self, next: &Effect, turn_angle: f64) -> Self {
+        let heading = self.turns as f64 * turn_angle;
+        let moved = |v: [f64; 2]| {
+            let r = rotate(v, heading);
+            [self.end[0] + r[0], self.end[1] + r[1]]
+        };
+        let next_center = moved(next.center);
+
+        // the smallest circle around both circles
+        let d = (next_center[0] - self.center[0]).hypot(next_center[1] - self.center[1]);
+        let (center, radius) = if d + next.radius <= self.radius {
+            (self.center, self.radius)
+        } else if d + self.radius <= next.radius {
+            (next_center, next.radius)
+        } else {
+            let radius = (d + self.radius + next.radius) / 2.0;
+            let t = (radius - self.radius) / d;
+            let center = [
+                self.center[0] + (next_center[0] - self.center[0]) * t,
+                self.center[1] + (next_center[1] - self.center[1]) * t,
+            ];
+            (center, radius)
+        };
+
+        Effect {
+            end_state: next.end_state,
+            end: moved(next.end),
+            turns: self.turns + next.turns,
+            center,
+            radius,
+            segments: self.segments + next.segments,
+        }
+    }
+}
+
+// Maps the curve to the screen
+struct View {
+    rect: Rect,       // The rectangle on the screen
+    center: [f64; 2], // The point of the curve in the middle of the rectangle
+    scale: f64,       // How many points on the screen one segment is long
+}
+
+impl View {
+    fn to_screen(&self, p: [f64; 2]) -> Pos2 {
+        self.rect.center()
+            + vec2(
+                ((p[0] - self.center[0]) * self.scale) as f32,
+                ((p[1] - self.center[1]) * self.scale) as f32,
+            )
+    }
+
+    fn to_curve(&self, p: Pos2) -> [f64; 2] {
+        let offset = p - self.rect.center();
+        [
+            self.center[0] + offset.x as f64 / self.scale,
+            self.center[1] + offset.y as f64 / self.scale,
+        ]
+    }
+}
+
+// The properties of a Fibonacci word that are shown in the analysis panel
+#[derive(PartialEq)]
+struct Analysis {
+    n: u16,                           // The Fibonacci word that was analysed
+    word: Vec<u8>,                    // The first ANALYSIS_LENGTH letters of the word
+    length: u64,                      // The length of the whole word
+    zeros: u64,                       // The number of 0s in the whole word
+    complexity: Vec<usize>,           // The number of factors of every length
+    palindromic_prefixes: Vec<usize>, // The lengths of the prefixes that are palindromes
+    palindromes: Vec<(usize, usize)>, // The maximal palindromes as (start, length)
+    squares: Vec<(usize, usize)>,     // The different squares ww as (start, length of w)
+}
+
+// Palindromes and squares are only searched for in the start of the word
+const ANALYSIS_LENGTH: usize = 1000;
+// The factors are counted in the start of the word, which already has all the short ones
+const COMPLEXITY_LENGTH: usize = 100_000;
+// The longest factors counted for the factor complexity
+const MAX_FACTOR_LENGTH: usize = 12;
+
+impl Analysis {
+    fn new(n: u16) -> Self {
+        let prefix: Vec<u8> = words::fibonacci_letters(n).take(ANALYSIS_LENGTH).collect();
+        let start: Vec<u8> = words::fibonacci_letters(n)
+            .take(COMPLEXITY_LENGTH)
+            .collect();
+        let (zeros, ones) = words::fibonacci_letter_counts(n);
+        Self {
+            n,
+            length: zeros + ones,
+            zeros,
+            complexity: words::factor_complexity(&start, MAX_FACTOR_LENGTH),
+            palindromic_prefixes: words::palindromic_prefixes(&prefix),
+            palindromes: words::maximal_palindromes(&prefix),
+            squares: words::squares(&prefix),
+            word: prefix,
+        }
+    }
 }
 
 impl Default for FibonacciWord {
     fn default() -> Self {
         Self {
-            zoom: 1.0,
             start_line_width: 0.5,
-            n: 1,
-            dir: 1,
-            last_n: 1,
+            n: 16,
+            variant: Variant::Standard,
+            turn_angle: 90.0,
+            rotation: 0.0,
+            color: Color32::GREEN,
+            zoom: 1.0,
+            pan: [0.0, 0.0],
+            detail: 1.0,
+            effects: Vec::new(),
+            bounds: [0.0; 4],
+            last_curve: (0, Variant::Standard, 0.0, 0.0),
+            drawn_count: 0,
+            analysis: None,
+            min_palindrome: 5,
+            sturmian_slope: 2.0 - words::GOLDEN_RATIO,
+            sturmian_intercept: 2.0 - words::GOLDEN_RATIO,
         }
     }
 }
@@ -37,11 +277,6 @@ impl epi::App for FibonacciWord {
 
 impl FibonacciWord {
     pub fn ui(&mut self, ui: &mut Ui) {
-        //Don't recalculate if we have the same n
-        if self.last_n == self.n {
-            ui.ctx().request_repaint();
-        }
-
         let painter = Painter::new(
             ui.ctx().clone(),
             ui.layer_id(),
@@ -51,85 +286,452 @@ impl FibonacciWord {
         // Make sure we allocate what we used (everything)
         ui.expand_to_include_rect(painter.clip_rect());
 
-        Frame::popup(ui.style())
+        let settings = Frame::popup(ui.style())
             .stroke(Stroke::none())
             .show(ui, |ui| {
                 ui.set_max_width(270.0);
                 CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+                CollapsingHeader::new("Word Analysis").show(ui, |ui| self.analysis_ui(ui));
             });
 
-        self.last_n = self.n;
+        // After the settings, so that their sliders get dragged instead of the curve.
+        // The new view is painted in the next frame.
+        self.navigate(ui, painter.clip_rect(), settings.response.rect);
+    }
+
+    // Dragging pans the view and scrolling zooms in and out around the mouse
+    fn navigate(&mut self, ui: &mut Ui, rect: Rect, settings: Rect) {
+        let response = ui.interact(rect, ui.id().with("fibonacci_view"), Sense::drag());
+        let view = self.view(rect);
+
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.pan[0] -= delta.x as f64 / view.scale;
+            self.pan[1] -= delta.y as f64 / view.scale;
+            ui.ctx().request_repaint();
+        }
+
+        let scroll = ui.input().scroll_delta.y;
+        if response.hovered() && scroll != 0.0 {
+            let factor = (scroll / 200.0).exp();
+            let pointer = ui.input().pointer.hover_pos();
+            if pointer.is_some_and(|p| settings.contains(p)) {
+                return;
+            }
+            if let Some(pointer) = pointer {
+                // keep the point under the mouse in the same place
+                let anchor = view.to_curve(pointer);
+                let shift = 1.0 - 1.0 / factor as f64;
+                self.pan[0] += (anchor[0] - view.center[0]) * shift;
+                self.pan[1] += (anchor[1] - view.center[1]) * shift;
+            }
+            self.zoom = (self.zoom * factor).clamp(0.1, 1.0e8);
+            ui.ctx().request_repaint();
+        }
     }
 
     fn options_ui(&mut self, ui: &mut Ui) {
-        ui.add(Slider::new(&mut self.n, 1..=30).text("N"));
-        // ui.add(Slider::new(&mut self.zoom, 0.005..=1.0).text("zoom"));
-        ui.add(Slider::new(&mut self.dir, 1..=4).text("direction"));
+        ui.add(Slider::new(&mut self.n, 1..=45).text("N"));
+        ComboBox::from_label("Variant")
+            .selected_text(self.variant.name())
+            .show_ui(ui, |ui| {
+                for variant in Variant::ALL {
+                    ui.selectable_value(&mut self.variant, variant, variant.name());
+                }
+            });
+        ui.add(Slider::new(&mut self.turn_angle, 0.0..=180.0).text("Turn angle"));
+        ui.add(Slider::new(&mut self.rotation, -180.0..=180.0).text("Rotation"));
+        ui.add(Slider::new(&mut self.start_line_width, 0.5..=5.0).text("Line width"));
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            ui.color_edit_button_srgba(&mut self.color);
+        });
+
+        ui.separator();
+
+        //view settings, the mouse can also drag and scroll the curve
+        ui.add(
+            Slider::new(&mut self.zoom, 0.1..=1.0e8)
+                .logarithmic(true)
+                .text("Zoom"),
+        );
+        ui.add(Slider::new(&mut self.detail, 0.25..=4.0).text("Level of detail (px)"));
+        if ui.button("Fit to Bounds").clicked() {
+            self.zoom = 1.0;
+            self.pan = [0.0, 0.0];
+        }
+        let segments = self.effects.last().map_or(0, |e| e[0].segments);
+        ui.label(format!(
+            "Segments: {} ({} drawn)",
+            segments, self.drawn_count
+        ));
         egui::reset_button(ui, self);
     }
 
-    fn paint(&mut self, painter: &Painter) {
-        self.zoom = 1.0_f32 / (4.0_f32 * f32::from(self.n));
-        let _golden_ratio: f64 = (1.0_f64 + 5.0_f64.sqrt()) / 2.0_f64;
-
-        //rendering with respect to screen's parameter
-        let mut shapes: Vec<Shape> = Vec::new();
-        let rect = painter.clip_rect();
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_center_size(Pos2::ZERO, rect.square_proportions() / self.zoom),
-            rect,
+    // Shows how the Fibonacci word looks as a word, and compares it with other Sturmian words
+    fn analysis_ui(&mut self, ui: &mut Ui) {
+        if self.analysis.as_ref().map(|a| a.n) != Some(self.n) {
+            self.analysis = Some(Analysis::new(self.n));
+        }
+        let analysis = self.analysis.as_ref().unwrap();
+        let prefix = |word: &[u8], len: usize| {
+            let shown = String::from_utf8_lossy(&word[..word.len().min(len)]).into_owned();
+            if word.len() > len {
+                shown + "…"
+            } else {
+                shown
+            }
+        };
+
+        // the word and its length
+        ui.add(
+            Label::new(prefix(&analysis.word, 120))
+                .monospace()
+                .wrap(true),
         );
+        ui.label(format!(
+            "Length: {}, the Fibonacci number F({})",
+            analysis.length,
+            self.n + 2
+        ));
+
+        // the ratio of 0s to 1s
+        let ones = analysis.length - analysis.zeros;
+        let ratio = analysis.zeros as f64 / ones as f64;
+        ui.label(format!(
+            "0s: {}, 1s: {}, ratio {:.6} (φ = {:.6}, off by {:.1e})",
+            analysis.zeros,
+            ones,
+            ratio,
+            words::GOLDEN_RATIO,
+            (ratio - words::GOLDEN_RATIO).abs()
+        ));
+
+        ui.separator();
+        ui.label("Factor complexity p(n), the number of different factors of length n:");
+        complexity_grid(ui, "fibonacci_complexity", &analysis.complexity);
+
+        ui.separator();
+        ui.label(format!("In the first {} letters:", analysis.word.len()));
+        let lengths: Vec<String> = analysis
+            .palindromic_prefixes
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        ui.label(format!(
+            "Palindromic prefixes of length {}",
+            lengths.join(", ")
+        ));
+
+        ui.add(Slider::new(&mut self.min_palindrome, 2..=60).text("Shortest palindrome"));
+        let min_palindrome = self.min_palindrome;
+        let analysis = self.analysis.as_ref().unwrap();
+        let palindromes: Vec<String> = analysis
+            .palindromes
+            .iter()
+            .filter(|&&(_, len)| len >= min_palindrome)
+            .map(|(start, len)| format!("{}..{}", start, start + len))
+            .collect();
+        ui.label(format!("{} maximal palindromes:", palindromes.len()));
+        ui.add(Label::new(listed(&palindromes, " ")).small().wrap(true));
+
+        let squares: Vec<String> = analysis
+            .squares
+            .iter()
+            .map(|&(start, len)| {
+                format!(
+                    "({})² at {}",
+                    prefix(&analysis.word[start..start + len], 8),
+                    start
+                )
+            })
+            .collect();
+        ui.label(format!("{} different squares:", squares.len()));
+        ui.add(Label::new(listed(&squares, ", ")).small().wrap(true));
+
+        ui.separator();
+        ui.label("Sturmian word with the letters floor((k+1)·slope + c) - floor(k·slope + c):");
+        ui.add(Slider::new(&mut self.sturmian_slope, 0.0..=1.0).text("Slope"));
+        ui.add(Slider::new(&mut self.sturmian_intercept, 0.0..=1.0).text("Intercept c"));
+        if ui.button("Fibonacci slope (2 - φ)").clicked() {
+            self.sturmian_slope = 2.0 - words::GOLDEN_RATIO;
+            self.sturmian_intercept = self.sturmian_slope;
+        }
+
+        let analysis = self.analysis.as_ref().unwrap();
+        let sturmian = words::sturmian_word(
+            self.sturmian_slope,
+            self.sturmian_intercept,
+            analysis.word.len(),
+        );
+        ui.add(Label::new(prefix(&sturmian, 120)).monospace().wrap(true));
+        match sturmian
+            .iter()
+            .zip(&analysis.word)
+            .position(|(a, b)| a != b)
+        {
+            Some(i) => ui.label(format!("Differs from the Fibonacci word at letter {}", i)),
+            None => ui.label("Matches the Fibonacci word"),
+        };
+        let ones = sturmian.iter().filter(|&&c| c == b'1').count();
+        ui.label(format!(
+            "Density of 1s: {:.6} (the slope)",
+            ones as f64 / sturmian.len().max(1) as f64
+        ));
+        complexity_grid(
+            ui,
+            "sturmian_complexity",
+            &words::factor_complexity(&sturmian, MAX_FACTOR_LENGTH),
+        );
+    }
 
-        //paint lines
-        let mut paint_line = |points: [Pos2; 2], color: Color32, width: f32| {
-            let line = [to_screen * points[0], to_screen * points[1]];
+    fn paint(&mut self, painter: &Painter) {
+        let shapes = self.curve_shapes(painter.clip_rect());
+        painter.extend(shapes);
+    }
+
+    // The heading (in radians) of the turtle after some number of turns
+    fn heading(&self, turns: i64) -> f64 {
+        (self.rotation as f64).to_radians() + turns as f64 * (self.turn_angle as f64).to_radians()
+    }
+
+    // Works out the effects of the blocks and the bounds again if the curve changed
+    fn update_effects(&mut self) {
+        let curve = (self.n, self.variant, self.turn_angle, self.rotation);
+        if curve == self.last_curve {
+            return;
+        }
+        self.last_curve = curve;
+
+        let turn_angle = (self.turn_angle as f64).to_radians();
+        let variant = self.variant;
+        let walk = |word: &[u8]| [0, 1, 2].map(|s| Effect::walk(variant, word, s, turn_angle));
+        self.effects = vec![walk(b"0"), walk(b"01")];
+        for k in 2..=self.n as usize {
+            let (longer, shorter) = (self.effects[k - 1], self.effects[k - 2]);
+            let effect =
+                [0, 1, 2].map(|s| longer[s].then(&shorter[longer[s].end_state], turn_angle));
+            self.effects.push(effect);
+        }
 
-            // Culling - doesn't render any shapes that are outside of the screen
-            if rect.intersects(Rect::from_two_pos(line[0], line[1])) {
-                shapes.push(Shape::line_segment(line, (width, color)));
+        // blocks much smaller than the whole curve are one line, which is close enough for the fit
+        let limit = self.effects[self.n as usize][0].radius / 256.0;
+        let mut bounds = [0.0_f64; 4];
+        let decide = |_: [f64; 2], radius: f64| {
+            if radius < limit {
+                Step::Line
+            } else {
+                Step::Split
             }
         };
+        self.traverse(self.n as usize, 0, [0.0, 0.0], 0, &decide, &mut |_, end| {
+            bounds[0] = bounds[0].min(end[0]);
+            bounds[1] = bounds[1].min(end[1]);
+            bounds[2] = bounds[2].max(end[0]);
+            bounds[3] = bounds[3].max(end[1]);
+        });
+        self.bounds = bounds;
+    }
+
+    // Walks through the block S(k) starting at a point after some number of turns. The decide
+    // function gets the circle around every block and picks if it is skipped, drawn as one line
+    // from its start to its end, or split into its two smaller blocks. Every line goes to line.
+    fn traverse(
+        &self,
+        k: usize,
+        state: usize,
+        start: [f64; 2],
+        turns: i64,
+        decide: &impl Fn([f64; 2], f64) -> Step,
+        line: &mut impl FnMut([f64; 2], [f64; 2]),
+    ) {
+        let effect = &self.effects[k][state];
+        if effect.segments == 0 {
+            return;
+        }
+        let heading = self.heading(turns);
+        let moved = |v: [f64; 2]| {
+            let r = rotate(v, heading);
+            [start[0] + r[0], start[1] + r[1]]
+        };
 
-        let mut s0 = String::from("0");
-        let mut s1 = String::from("01");
-
-        for _i in 2..self.n {
-            let tmp = String::from(s1.as_str());
-            s1.push_str(&s0);
-            s0 = tmp;
-        }
-
-        let mut curr_pts = pos2(0.0, 0.0);
-        let mut color = Color32::GREEN;
-        let mut curr_dir = Vec2 { x: 0.0, y: 0.01 };
-        if self.dir == 2 {
-            color = Color32::RED;
-            curr_dir = Vec2 { x: 0.0, y: -0.01 };
-        } else if self.dir == 3 {
-            color = Color32::YELLOW;
-            curr_dir = Vec2 { x: 0.01, y: 0.0 };
-        } else if self.dir == 4 {
-            color = Color32::BLUE;
-            curr_dir = Vec2 { x: -0.01, y: 0.0 };
-        }
-        for (i, c) in s1.chars().enumerate() {
-            let curr_end = curr_pts + curr_dir;
-            paint_line([curr_pts, curr_end], color, self.start_line_width);
-            curr_pts = curr_end;
-            if c == '0' {
-                if i % 2 == 0 {
-                    curr_dir = Vec2 {
-                        x: curr_dir.y,
-                        y: curr_dir.x,
-                    };
-                } else {
-                    curr_dir = Vec2 {
-                        x: -curr_dir.y,
-                        y: -curr_dir.x,
-                    };
+        match decide(moved(effect.center), effect.radius) {
+            Step::Skip => {}
+            Step::Line => line(start, moved(effect.end)),
+            Step::Split if k <= 1 => {
+                // walk the letters of S(0) = 0 or S(1) = 01
+                let word: &[u8] = if k == 0 { b"0" } else { b"01" };
+                let (mut state, mut position, mut turns) = (state, start, turns);
+                for &letter in word {
+                    let (next, draws, turn) = self.variant.read(state, letter);
+                    if draws {
+                        let step = rotate([1.0, 0.0], self.heading(turns));
+                        let end = [position[0] + step[0], position[1] + step[1]];
+                        line(position, end);
+                        position = end;
+                    }
+                    state = next;
+                    turns += turn;
                 }
             }
+            Step::Split => {
+                // S(k) = S(k - 1) S(k - 2)
+                let first = &self.effects[k - 1][state];
+                self.traverse(k - 1, state, start, turns, decide, line);
+                let middle = moved(first.end);
+                let turns = turns + first.turns;
+                self.traverse(k - 2, first.end_state, middle, turns, decide, line);
+            }
         }
-        painter.extend(shapes);
+    }
+
+    // Where the curve is shown in the rectangle
+    fn view(&mut self, rect: Rect) -> View {
+        self.update_effects();
+        let [min_x, min_y, max_x, max_y] = self.bounds;
+        let fit = 0.9
+            * (rect.width() as f64 / (max_x - min_x).max(1e-6))
+                .min(rect.height() as f64 / (max_y - min_y).max(1e-6));
+        View {
+            rect,
+            center: [
+                (min_x + max_x) / 2.0 + self.pan[0],
+                (min_y + max_y) / 2.0 + self.pan[1],
+            ],
+            scale: fit * self.zoom as f64,
+        }
+    }
+
+    // Generates the lines of the curve that are on the screen, joined into paths
+    fn curve_shapes(&mut self, rect: Rect) -> Vec<Shape> {
+        let view = self.view(rect);
+        let stroke = Stroke::new(self.start_line_width, self.color);
+        let visible = rect.expand(self.start_line_width);
+        let detail = self.detail as f64;
+
+        let decide = |center: [f64; 2], radius: f64| {
+            let center = view.to_screen(center);
+            let radius = (radius * view.scale) as f32;
+            let nearest = visible.clamp(center);
+            if nearest.distance(center) > radius {
+                Step::Skip
+            } else if 2.0 * radius < detail as f32 {
+                Step::Line
+            } else {
+                Step::Split
+            }
+        };
+
+        let mut shapes = Vec::new();
+        let mut path: Vec<Pos2> = Vec::new();
+        let mut drawn_count = 0;
+        self.traverse(
+            self.n as usize,
+            0,
+            [0.0, 0.0],
+            0,
+            &decide,
+            &mut |start, end| {
+                let (start, end) = (view.to_screen(start), view.to_screen(end));
+                if path.last().is_none_or(|&last| last.distance(start) > 0.01) {
+                    if path.len() > 1 {
+                        shapes.push(Shape::line(std::mem::take(&mut path), stroke));
+                    }
+                    path.clear();
+                    path.push(start);
+                }
+                path.push(end);
+                drawn_count += 1;
+            },
+        );
+        if path.len() > 1 {
+            shapes.push(Shape::line(path, stroke));
+        }
+        self.drawn_count = drawn_count;
+        shapes
+    }
+}
+
+// What is done with a block of the curve
+enum Step {
+    Skip,  // The block is off the screen
+    Line,  // The block is so small that it is drawn as one line
+    Split, // The block is drawn as its two smaller blocks
+}
+
+// Joins the first items of a long list
+fn listed(items: &[String], separator: &str) -> String {
+    const SHOWN: usize = 40;
+    let mut text = items[..items.len().min(SHOWN)].join(separator);
+    if items.len() > SHOWN {
+        text += &format!("{}… {} more", separator, items.len() - SHOWN);
+    }
+    text
+}
+
+// A table of p(n) for every length n, marking where p(n) = n + 1 like in a Sturmian word
+fn complexity_grid(ui: &mut Ui, id: &str, complexity: &[usize]) {
+    Grid::new(id).show(ui, |ui| {
+        ui.label("n");
+        for n in 1..=complexity.len() {
+            ui.label(n.to_string());
+        }
+        ui.end_row();
+
+        ui.label("p(n)");
+        for (i, &p) in complexity.iter().enumerate() {
+            let color = if p == i + 2 {
+                Color32::GREEN
+            } else {
+                Color32::RED
+            };
+            ui.colored_label(color, p.to_string());
+        }
+        ui.end_row();
+    });
+}
+
+impl Animate for FibonacciWord {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("N", self.n as f32, 1.0..=45.0),
+            Parameter::new("Turn angle", self.turn_angle, 0.0..=180.0),
+            Parameter::new("Rotation", self.rotation, -180.0..=180.0),
+            Parameter::new("Line width", self.start_line_width, 0.5..=5.0),
+            Parameter::new("Zoom", self.zoom, 0.1..=1.0e8),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "N" => self.n = value.round() as u16,
+            "Turn angle" => self.turn_angle = value,
+            "Rotation" => self.rotation = value,
+            "Line width" => self.start_line_width = value,
+            "Zoom" => self.zoom = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for FibonacciWord {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        Image::from_shapes(width, height, Color32::BLACK, &self.curve_shapes(rect))
+    }
+}
+
+impl FractalApp for FibonacciWord {
+    // Only known for the standard curve with right angles (Monnerot-Dumaine)
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        if self.variant != Variant::Standard || self.turn_angle != 90.0 {
+            return None;
+        }
+        let golden_ratio = (1.0 + 5.0_f64.sqrt()) / 2.0;
+        Some(KnownDimension {
+            formula: String::from("3 log φ / log(1 + √2)"),
+            value: 3.0 * golden_ratio.ln() / (1.0 + 2.0_f64.sqrt()).ln(),
+            counted: Counted::Drawn,
+        })
     }
 }
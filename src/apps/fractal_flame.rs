@@ -0,0 +1,870 @@
+use super::ifs::{AffineMap, View};
+use super::FractalApp;
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::f64::consts::PI;
+
+#[derive(PartialEq)]
+
+/*
+This file contains the fractal flame renderer. A flame is an IFS (see ifs.rs) whose transforms
+bend the plane after their affine map with a mix of nonlinear variations, like swirls, spheres
+and horseshoes. Every transform also has a color index, and the point's color moves halfway to
+it whenever the transform is used, so the colors show which transforms made each part.
+The chaos game fills a histogram with how many points landed on every pixel and the sum of
+their colors. Density estimation blurs the pixels that were rarely hit with a wider kernel than
+the busy ones, which smooths the noise in the dark parts without blurring the details. The
+counts are then tone mapped on a log scale, with gamma to bring out the faint parts, and
+vibrancy choosing between applying the gamma to the brightness only (vivid colors) or to every
+channel on its own (softer colors).
+The histogram keeps growing while the flame is shown, so the image refines as you watch until
+it has the chosen number of samples per pixel.
+Fractal Explanation: https://en.wikipedia.org/wiki/Fractal_flame
+Source: https://flam3.com/flame_draves.pdf
+*/
+
+pub struct FractalFlame {
+    transforms: Vec<Transform>,          // The transforms of the flame
+    selected: usize,                     // The transform that is edited in the settings
+    seed: u64,                           // The seed of the last random flame
+    last_seed: u64,                      // Used to determine if the seed was changed
+    palette: usize,                      // The palette that the color indices are looked up in
+    rotation: f64,                       // The angle (in degrees) all the affine maps turn by
+    gamma: f32,                          // Brightens the faint parts of the flame
+    vibrancy: f32,                       // 1 keeps the colors vivid, 0 applies gamma per channel
+    brightness: f32,                     // Scales the brightness before the gamma
+    quality: f32,                        // The number of samples per pixel to stop at
+    estimator_radius: f32,               // The widest blur of the density estimation, in pixels
+    center: [f64; 2],                    // The point of the plane in the middle of the screen
+    zoom: f64,                           // How far the view is zoomed in, 1 shows -1 to 1
+    histogram: Option<Histogram>,        // The samples of the flame so far
+    last_input: Option<HistogramInput>,  // The input of the histogram
+    last_tone_map: Option<ToneMapInput>, // The input of the tone mapped texture
+    texture: Option<(TextureId, Vec2)>,  // The tone mapped histogram and its size
+}
+
+// One transform of the flame: an affine map, followed by a weighted sum of variations
+#[derive(Clone, PartialEq)]
+struct Transform {
+    affine: AffineMap, // The map, its probability is the transform's weight
+    variations: [f64; VARIATION_COUNT], // The weight of every variation
+    color: f64,        // The color index, from 0 to 1
+}
+
+// The nonlinear functions that bend the plane
+#[derive(Clone, Copy, PartialEq)]
+enum Variation {
+    Linear,
+    Sinusoidal,
+    Spherical,
+    Swirl,
+    Horseshoe,
+    Polar,
+    Handkerchief,
+    Heart,
+    Disc,
+    Spiral,
+    Hyperbolic,
+    Diamond,
+    Julia,
+    Fisheye,
+}
+
+const VARIATION_COUNT: usize = 14;
+
+impl Variation {
+    const ALL: [Variation; VARIATION_COUNT] = [
+        Variation::Linear,
+        Variation::Sinusoidal,
+        Variation::Spherical,
+        Variation::Swirl,
+        Variation::Horseshoe,
+        Variation::Polar,
+        Variation::Handkerchief,
+        Variation::Heart,
+        Variation::Disc,
+        Variation::Spiral,
+        Variation::Hyperbolic,
+        Variation::Diamond,
+        Variation::Julia,
+        Variation::Fisheye,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Variation::Linear => "Linear",
+            Variation::Sinusoidal => "Sinusoidal",
+            Variation::Spherical => "Spherical",
+            Variation::Swirl => "Swirl",
+            Variation::Horseshoe => "Horseshoe",
+            Variation::Polar => "Polar",
+            Variation::Handkerchief => "Handkerchief",
+            Variation::Heart => "Heart",
+            Variation::Disc => "Disc",
+            Variation::Spiral => "Spiral",
+            Variation::Hyperbolic => "Hyperbolic",
+            Variation::Diamond => "Diamond",
+            Variation::Julia => "Julia",
+            Variation::Fisheye => "Fisheye",
+        }
+    }
+
+    // Bends a point, as given in the flame paper. theta is measured from the y axis there.
+    fn apply(self, p: [f64; 2], rng: &mut StdRng) -> [f64; 2] {
+        let [x, y] = p;
+        let r2 = x * x + y * y + 1e-12;
+        let r = r2.sqrt();
+        let theta = x.atan2(y);
+        match self {
+            Variation::Linear => [x, y],
+            Variation::Sinusoidal => [x.sin(), y.sin()],
+            Variation::Spherical => [x / r2, y / r2],
+            Variation::Swirl => {
+                let (sin, cos) = r2.sin_cos();
+                [x * sin - y * cos, x * cos + y * sin]
+            }
+            Variation::Horseshoe => [(x - y) * (x + y) / r, 2.0 * x * y / r],
+            Variation::Polar => [theta / PI, r - 1.0],
+            Variation::Handkerchief => [r * (theta + r).sin(), r * (theta - r).cos()],
+            Variation::Heart => [r * (theta * r).sin(), -r * (theta * r).cos()],
+            Variation::Disc => [theta / PI * (PI * r).sin(), theta / PI * (PI * r).cos()],
+            Variation::Spiral => [(theta.cos() + r.sin()) / r, (theta.sin() - r.cos()) / r],
+            Variation::Hyperbolic => [theta.sin() / r, r * theta.cos()],
+            Variation::Diamond => [theta.sin() * r.cos(), theta.cos() * r.sin()],
+            Variation::Julia => {
+                // one of the two square roots, picked at random
+                let omega = if rng.gen::<bool>() { PI } else { 0.0 };
+                let (sin, cos) = (theta / 2.0 + omega).sin_cos();
+                [r.sqrt() * cos, r.sqrt() * sin]
+            }
+            Variation::Fisheye => [2.0 * y / (r + 1.0), 2.0 * x / (r + 1.0)],
+        }
+    }
+}
+
+impl Transform {
+    fn new(affine: [f64; 7], color: f64, variations: &[(Variation, f64)]) -> Self {
+        let mut weights = [0.0; VARIATION_COUNT];
+        for &(variation, weight) in variations {
+            weights[variation as usize] = weight;
+        }
+        Self {
+            affine: AffineMap::new(affine),
+            variations: weights,
+            color,
+        }
+    }
+
+    // Moves a point by the affine map, turned by the rotation, and then the variations
+    fn apply(&self, p: [f64; 2], rotation: (f64, f64), rng: &mut StdRng) -> [f64; 2] {
+        let (sin, cos) = rotation;
+        let m = &self.affine;
+        let q = [m.a * p[0] + m.b * p[1], m.c * p[0] + m.d * p[1]];
+        let q = [cos * q[0] - sin * q[1] + m.e, sin * q[0] + cos * q[1] + m.f];
+
+        let mut result = [0.0, 0.0];
+        for (variation, &weight) in Variation::ALL.iter().zip(&self.variations) {
+            if weight != 0.0 {
+                let v = variation.apply(q, rng);
+                result = [result[0] + weight * v[0], result[1] + weight * v[1]];
+            }
+        }
+        result
+    }
+}
+
+// The named palettes that color indices are looked up in
+const PALETTES: [(&str, &[Color32]); 4] = [
+    (
+        "Fire",
+        &[
+            Color32::from_rgb(90, 0, 30),
+            Color32::from_rgb(220, 40, 10),
+            Color32::from_rgb(255, 150, 0),
+            Color32::from_rgb(255, 240, 150),
+        ],
+    ),
+    (
+        "Ocean",
+        &[
+            Color32::from_rgb(10, 20, 120),
+            Color32::from_rgb(0, 150, 199),
+            Color32::from_rgb(120, 240, 220),
+            Color32::from_rgb(240, 255, 255),
+        ],
+    ),
+    (
+        "Orchid",
+        &[
+            Color32::from_rgb(60, 10, 120),
+            Color32::from_rgb(200, 40, 180),
+            Color32::from_rgb(255, 140, 120),
+            Color32::from_rgb(255, 230, 160),
+        ],
+    ),
+    (
+        "Rainbow",
+        &[
+            Color32::from_rgb(255, 40, 40),
+            Color32::from_rgb(255, 200, 0),
+            Color32::from_rgb(40, 220, 80),
+            Color32::from_rgb(0, 150, 255),
+            Color32::from_rgb(180, 60, 255),
+        ],
+    ),
+];
+
+// The named flames, with the affine map and weight, color index and variations of every
+// transform
+type Preset = (
+    &'static str,
+    &'static [([f64; 7], f64, &'static [(Variation, f64)])],
+);
+
+const PRESETS: [Preset; 4] = [
+    (
+        "Sierpinski Swirl",
+        &[
+            (
+                [0.5, 0.0, 0.0, 0.5, -0.5, -0.5, 1.0],
+                0.0,
+                &[(Variation::Linear, 0.8), (Variation::Swirl, 0.2)],
+            ),
+            (
+                [0.5, 0.0, 0.0, 0.5, 0.5, -0.5, 1.0],
+                0.5,
+                &[(Variation::Linear, 0.8), (Variation::Swirl, 0.2)],
+            ),
+            (
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.5, 1.0],
+                1.0,
+                &[(Variation::Linear, 0.8), (Variation::Swirl, 0.2)],
+            ),
+        ],
+    ),
+    (
+        "Spherical Bloom",
+        &[
+            (
+                [0.56, -0.42, 0.42, 0.56, 0.3, 0.1, 1.0],
+                0.1,
+                &[(Variation::Spherical, 1.0)],
+            ),
+            (
+                [-0.38, 0.56, -0.56, -0.38, -0.2, 0.4, 1.0],
+                0.6,
+                &[(Variation::Spherical, 0.6), (Variation::Linear, 0.4)],
+            ),
+            (
+                [0.4, 0.0, 0.0, 0.4, 0.0, -0.6, 0.5],
+                0.95,
+                &[(Variation::Julia, 1.0)],
+            ),
+        ],
+    ),
+    (
+        "Julia Rings",
+        &[
+            (
+                [-0.8, 0.2, -0.2, -0.8, 0.2, 0.1, 1.0],
+                0.0,
+                &[(Variation::Julia, 1.0)],
+            ),
+            (
+                [0.4, 0.0, 0.0, 0.4, 0.5, 0.5, 0.5],
+                1.0,
+                &[(Variation::Spherical, 1.0)],
+            ),
+        ],
+    ),
+    (
+        "Horseshoe Bloom",
+        &[
+            (
+                [0.8, 0.0, 0.0, 0.8, 0.0, 0.0, 3.0],
+                0.2,
+                &[(Variation::Horseshoe, 1.0)],
+            ),
+            (
+                [0.3, 0.2, -0.2, 0.3, 0.7, -0.2, 1.0],
+                0.9,
+                &[(Variation::Linear, 1.0)],
+            ),
+            (
+                [0.3, -0.2, 0.2, 0.3, -0.7, -0.2, 1.0],
+                0.6,
+                &[(Variation::Linear, 1.0)],
+            ),
+        ],
+    ),
+];
+
+// The most transforms a flame can have
+const MAX_TRANSFORMS: usize = 8;
+
+// The number of samples added to the histogram every frame while it refines, which takes about
+// 80 ms for most flames so the app stays responsive
+const SAMPLES_PER_FRAME: usize = 100_000;
+
+// The density estimation rounds the radius of every pixel to a quarter of a pixel
+const KERNEL_STEPS: f32 = 4.0;
+
+// Everything the histogram is made from: the transforms, the rotation, the palette, the view
+// (center and zoom) and the size of the image
+type HistogramInput = (Vec<Transform>, f64, usize, [f64; 2], f64, Vec2);
+
+// Everything the tone mapped texture is made from: the number of samples in the histogram, the
+// gamma, the vibrancy, the brightness and the estimator radius
+type ToneMapInput = (usize, f32, f32, f32, f32);
+
+// The samples of the flame, with the point of the chaos game so it can go on where it stopped
+#[derive(Clone, PartialEq)]
+struct Histogram {
+    width: usize,
+    height: usize,
+    bins: Vec<[f32; 4]>, // The sum of the colors and the number of samples on every pixel
+    samples: usize,      // The number of samples so far
+    point: [f64; 2],
+    color: f64,
+    rng: StdRng,
+}
+
+impl Default for FractalFlame {
+    fn default() -> Self {
+        let mut flame = Self {
+            transforms: Vec::new(),
+            selected: 0,
+            seed: 0,
+            last_seed: 0,
+            palette: 0,
+            rotation: 0.0,
+            gamma: 2.5,
+            vibrancy: 0.8,
+            brightness: 1.0,
+            quality: 30.0,
+            estimator_radius: 6.0,
+            center: [0.0, 0.0],
+            zoom: 0.8,
+            histogram: None,
+            last_input: None,
+            last_tone_map: None,
+            texture: None,
+        };
+        flame.load_preset(&PRESETS[0]);
+        flame
+    }
+}
+
+impl epi::App for FractalFlame {
+    fn name(&self) -> &str {
+        "Fractal Flame"
+    }
+
+    // Called every frame
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui, frame));
+    }
+}
+
+impl FractalFlame {
+    // Paints the fractal
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter, frame);
+
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        let settings = Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+
+        // After the settings, so that their sliders get dragged instead of the flame
+        self.navigate(ui, painter.clip_rect(), settings.response.rect);
+    }
+
+    // An options window for the transforms and the tone mapping
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let preset = PRESETS
+            .iter()
+            .find(|preset| Self::preset_transforms(preset) == self.transforms)
+            .map_or("Custom", |(name, _)| name);
+        ComboBox::from_label("Preset")
+            .selected_text(preset)
+            .show_ui(ui, |ui| {
+                for preset_flame in PRESETS.iter() {
+                    let name = preset_flame.0;
+                    if ui.selectable_label(preset == name, name).clicked() {
+                        self.load_preset(preset_flame);
+                    }
+                }
+            });
+        ui.label("Random flame:");
+        super::seed_ui(ui, &mut self.seed);
+
+        ComboBox::from_label("Palette")
+            .selected_text(PALETTES[self.palette].0)
+            .show_ui(ui, |ui| {
+                for (i, (name, _)) in PALETTES.iter().enumerate() {
+                    ui.selectable_value(&mut self.palette, i, *name);
+                }
+            });
+        ui.add(Slider::new(&mut self.gamma, 1.0..=5.0).text("Gamma"));
+        ui.add(Slider::new(&mut self.vibrancy, 0.0..=1.0).text("Vibrancy"));
+        ui.add(Slider::new(&mut self.brightness, 0.2..=4.0).text("Brightness"));
+        ui.add(Slider::new(&mut self.estimator_radius, 0.0..=12.0).text("Density estimation"));
+        ui.add(
+            Slider::new(&mut self.quality, 1.0..=200.0)
+                .logarithmic(true)
+                .text("Quality"),
+        );
+        ui.add(Slider::new(&mut self.rotation, -180.0..=180.0).text("Rotation"));
+        if let Some(histogram) = &self.histogram {
+            let per_pixel = histogram.samples as f32 / (histogram.width * histogram.height) as f32;
+            ui.label(format!("Samples per pixel: {:.1}", per_pixel));
+        }
+        if ui.button("Reset View").clicked() {
+            self.center = [0.0, 0.0];
+            self.zoom = 0.8;
+        }
+
+        ui.separator();
+        self.transforms_ui(ui);
+        // The texture is kept out of the reset, so the next paint frees it when it starts over on
+        // the default flame
+        let texture = self.texture.take();
+        egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    // A list of the transforms, with the settings of the selected one
+    fn transforms_ui(&mut self, ui: &mut Ui) {
+        let mut removed = None;
+        for i in 0..self.transforms.len() {
+            ui.horizontal(|ui| {
+                let color =
+                    palette::gradient(PALETTES[self.palette].1, self.transforms[i].color as f32);
+                ui.colored_label(color, "■");
+                ui.radio_value(&mut self.selected, i, format!("Transform {}", i + 1));
+                ui.add(
+                    DragValue::new(&mut self.transforms[i].affine.probability)
+                        .clamp_range(0.0..=10.0)
+                        .speed(0.01)
+                        .prefix("weight: "),
+                );
+                if self.transforms.len() > 1 && ui.small_button("✖").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            self.transforms.remove(i);
+        }
+        self.selected = self.selected.min(self.transforms.len() - 1);
+
+        let transform = &mut self.transforms[self.selected];
+        ui.add(Slider::new(&mut transform.color, 0.0..=1.0).text("Color index"));
+        transform.affine.coefficients_ui(ui);
+        ui.label("Variations:");
+        Grid::new("flame_variations").show(ui, |ui| {
+            for (i, variation) in Variation::ALL.iter().enumerate() {
+                ui.add(
+                    DragValue::new(&mut transform.variations[i])
+                        .clamp_range(-2.0..=2.0)
+                        .speed(0.01)
+                        .prefix(format!("{}: ", variation.name())),
+                );
+                if i % 2 == 1 {
+                    ui.end_row();
+                }
+            }
+        });
+
+        if self.transforms.len() < MAX_TRANSFORMS && ui.button("Add Transform").clicked() {
+            self.transforms.push(Transform::new(
+                [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0],
+                0.5,
+                &[(Variation::Linear, 1.0)],
+            ));
+            self.selected = self.transforms.len() - 1;
+        }
+    }
+
+    fn preset_transforms(preset: &Preset) -> Vec<Transform> {
+        preset
+            .1
+            .iter()
+            .map(|&(affine, color, variations)| Transform::new(affine, color, variations))
+            .collect()
+    }
+
+    fn load_preset(&mut self, preset: &Preset) {
+        self.transforms = Self::preset_transforms(preset);
+        self.selected = 0;
+    }
+
+    // A flame with 2 to 4 transforms, each with random maps and one or two variations
+    fn random_flame(seed: u64) -> Vec<Transform> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let count = rng.gen_range(2..=4);
+        (0..count)
+            .map(|i| {
+                let mut affine = [0.0; 7];
+                for coefficient in affine.iter_mut().take(6) {
+                    *coefficient = rng.gen_range(-1.0..=1.0);
+                }
+                affine[6] = rng.gen_range(0.2..=1.0);
+                let first = Variation::ALL[rng.gen_range(0..VARIATION_COUNT)];
+                let second = Variation::ALL[rng.gen_range(0..VARIATION_COUNT)];
+                let mix = rng.gen_range(0.0..=1.0);
+                let color = i as f64 / (count - 1) as f64;
+                Transform::new(affine, color, &[(first, mix), (second, 1.0 - mix)])
+            })
+            .collect()
+    }
+
+    // Dragging moves the flame and scrolling zooms in and out around the mouse
+    fn navigate(&mut self, ui: &mut Ui, rect: Rect, settings: Rect) {
+        let response = ui.interact(rect, ui.id().with("flame_view"), Sense::drag());
+        let view = self.view(rect);
+
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.center[0] -= delta.x as f64 / view.scale;
+            self.center[1] += delta.y as f64 / view.scale;
+        }
+
+        let scroll = ui.input().scroll_delta.y;
+        let pointer = ui.input().pointer.hover_pos();
+        if response.hovered() && scroll != 0.0 && !pointer.is_some_and(|p| settings.contains(p)) {
+            let factor = (scroll as f64 / 200.0).exp();
+            // keep the point under the mouse in the same place
+            let anchor = pointer.map_or(self.center, |p| view.to_plane(p));
+            self.center = [
+                anchor[0] - (anchor[0] - self.center[0]) / factor,
+                anchor[1] - (anchor[1] - self.center[1]) / factor,
+            ];
+            self.zoom = (self.zoom * factor).clamp(0.01, 1e4);
+        }
+    }
+
+    // Where the flame is drawn, a zoom of 1 fits -1 to 1 on the screen
+    fn view(&self, rect: Rect) -> View {
+        View {
+            center: self.center,
+            scale: self.zoom * rect.width().min(rect.height()) as f64 / 2.0,
+            screen_center: rect.center(),
+        }
+    }
+
+    // An empty histogram for an image of the given size
+    fn new_histogram(width: usize, height: usize) -> Histogram {
+        Histogram {
+            width,
+            height,
+            bins: vec![[0.0; 4]; width * height],
+            samples: 0,
+            point: [0.0, 0.0],
+            color: 0.0,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+
+    // Adds samples of the chaos game to the histogram
+    fn sample(&self, histogram: &mut Histogram, rect: Rect, samples: usize) {
+        let view = self.view(rect);
+        let total: f64 = self
+            .transforms
+            .iter()
+            .map(|t| t.affine.probability.max(0.0))
+            .sum();
+        let rotation = self.rotation.to_radians().sin_cos();
+        let colors: Vec<[f32; 3]> = (0..=255)
+            .map(|i| {
+                let color = palette::gradient(PALETTES[self.palette].1, i as f32 / 255.0);
+                [color.r(), color.g(), color.b()].map(|c| c as f32 / 255.0)
+            })
+            .collect();
+
+        let Histogram {
+            width,
+            height,
+            bins,
+            samples: done,
+            point,
+            color,
+            rng,
+        } = histogram;
+        for k in *done..*done + samples {
+            // picks a transform by weight, or evenly if the weights are all 0
+            let mut i = self.transforms.len() - 1;
+            if total > 0.0 {
+                let mut target = rng.gen::<f64>() * total;
+                for (j, transform) in self.transforms.iter().enumerate() {
+                    target -= transform.affine.probability.max(0.0);
+                    if target < 0.0 {
+                        i = j;
+                        break;
+                    }
+                }
+            } else {
+                i = rng.gen_range(0..self.transforms.len());
+            }
+
+            let transform = &self.transforms[i];
+            *point = transform.apply(*point, rotation, rng);
+            *color = (*color + transform.color) / 2.0;
+            if !point[0].is_finite() || !point[1].is_finite() || point[0].abs() > 1e10 {
+                // the point flew off, start again somewhere random
+                *point = [rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)];
+                continue;
+            }
+
+            // the first points are still on their way to the flame
+            let pixel = view.to_screen(*point) - rect.min;
+            if k < 20 || pixel.x < 0.0 || pixel.y < 0.0 {
+                continue;
+            }
+            let (x, y) = (pixel.x as usize, pixel.y as usize);
+            if x < *width && y < *height {
+                let rgb = colors[(*color * 255.0).round() as usize];
+                let bin = &mut bins[y * *width + x];
+                *bin = [
+                    bin[0] + rgb[0],
+                    bin[1] + rgb[1],
+                    bin[2] + rgb[2],
+                    bin[3] + 1.0,
+                ];
+            }
+        }
+        *done += samples;
+    }
+
+    // Density estimation: every pixel is spread over a circle that is wider the fewer samples
+    // it has, so sparse areas are smoothed while dense ones stay sharp
+    fn estimate_density(&self, histogram: &Histogram) -> Vec<[f32; 4]> {
+        let (width, height) = (histogram.width, histogram.height);
+        if self.estimator_radius < 0.5 {
+            return histogram.bins.clone();
+        }
+
+        // the radii are rounded to steps of 1 / KERNEL_STEPS, so the kernels are only worked out
+        // once for every step up to the widest radius
+        let kernels: Vec<Vec<(i64, i64, f32)>> = (0
+            ..=(self.estimator_radius * KERNEL_STEPS).round() as usize)
+            .map(|step| gaussian_kernel(step as f32 / KERNEL_STEPS))
+            .collect();
+
+        let mut filtered = vec![[0.0_f32; 4]; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let bin = histogram.bins[y * width + x];
+                if bin[3] == 0.0 {
+                    continue;
+                }
+                let radius = self.estimator_radius / bin[3].powf(0.4);
+                let step = ((radius * KERNEL_STEPS).round() as usize).min(kernels.len() - 1);
+                for &(dx, dy, w) in &kernels[step] {
+                    let (fx, fy) = (x as i64 + dx, y as i64 + dy);
+                    if fx < 0 || fy < 0 || fx >= width as i64 || fy >= height as i64 {
+                        continue;
+                    }
+                    let out = &mut filtered[fy as usize * width + fx as usize];
+                    for channel in 0..4 {
+                        out[channel] += bin[channel] * w;
+                    }
+                }
+            }
+        }
+        filtered
+    }
+
+    // Turns the histogram into an image with log density, gamma and vibrancy
+    fn tone_map(&self, histogram: &Histogram, background: Color32) -> Image {
+        let bins = self.estimate_density(histogram);
+        let mut image = Image::new(histogram.width, histogram.height, background);
+        let max = bins.iter().map(|bin| bin[3]).fold(0.0, f32::max);
+        if max <= 0.0 {
+            return image;
+        }
+
+        let inverse_gamma = 1.0 / self.gamma;
+        for (i, bin) in bins.iter().enumerate() {
+            if bin[3] <= 0.0 {
+                continue;
+            }
+            let alpha = (self.brightness * bin[3].ln_1p() / max.ln_1p()).min(1.0);
+            let gamma_alpha = alpha.powf(inverse_gamma);
+            let channel = |sum: f32| {
+                let average = sum / bin[3];
+                let vivid = average * gamma_alpha;
+                let soft = (average * alpha).powf(inverse_gamma);
+                let value = self.vibrancy * vivid + (1.0 - self.vibrancy) * soft;
+                (value.min(1.0) * 255.0).round() as u8
+            };
+            let color = Color32::from_rgba_premultiplied(
+                channel(bin[0]),
+                channel(bin[1]),
+                channel(bin[2]),
+                (gamma_alpha * 255.0).round() as u8,
+            );
+            image.blend(
+                (i % histogram.width) as i64,
+                (i / histogram.width) as i64,
+                color,
+                1.0,
+            );
+        }
+        image
+    }
+
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
+        if self.seed != self.last_seed {
+            self.transforms = Self::random_flame(self.seed);
+            self.selected = 0;
+            self.last_seed = self.seed;
+        }
+
+        let rect = painter.clip_rect();
+        let size = rect.size().round();
+        let input = (
+            self.transforms.clone(),
+            self.rotation,
+            self.palette,
+            self.center,
+            self.zoom,
+            size,
+        );
+        if self.last_input.as_ref() != Some(&input) {
+            self.histogram = Some(Self::new_histogram(size.x as usize, size.y as usize));
+            self.last_input = Some(input);
+            self.last_tone_map = None;
+        }
+
+        // Adds more samples every frame until the quality is reached
+        let image_rect = Rect::from_min_size(rect.min, size);
+        if let Some(mut histogram) = self.histogram.take() {
+            let target = (self.quality * size.x * size.y) as usize;
+            if histogram.samples < target {
+                let samples = SAMPLES_PER_FRAME.min(target - histogram.samples);
+                self.sample(&mut histogram, image_rect, samples);
+                painter.ctx().request_repaint();
+            }
+
+            // The density estimation is slow, so while the flame refines the texture is only
+            // made again once the samples have doubled or the quality is reached, and right
+            // away when the tone mapping changed
+            let tone_map_input = |samples| {
+                (
+                    samples,
+                    self.gamma,
+                    self.vibrancy,
+                    self.brightness,
+                    self.estimator_radius,
+                )
+            };
+            let refresh = match self.last_tone_map {
+                Some(last) => {
+                    let samples = histogram.samples;
+                    let refined = samples > last.0 && (samples >= 2 * last.0 || samples >= target);
+                    refined || last != tone_map_input(last.0)
+                }
+                None => true,
+            };
+            let tone_map_input = tone_map_input(histogram.samples);
+            if refresh {
+                let image = self.tone_map(&histogram, Color32::TRANSPARENT);
+                if let Some((texture, _)) = self.texture.take() {
+                    frame.tex_allocator().free(texture);
+                }
+                let texture = frame
+                    .tex_allocator()
+                    .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+                self.texture = Some((texture, size));
+                self.last_tone_map = Some(tone_map_input);
+            }
+            self.histogram = Some(histogram);
+        }
+
+        if let Some((texture, size)) = self.texture {
+            let mut mesh = epaint::Mesh::with_texture(texture);
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+    }
+}
+
+// A gaussian kernel cut off at the radius, as the offsets with their weights, which add up to 1.
+// Radii under half a pixel keep the pixel as it is.
+fn gaussian_kernel(radius: f32) -> Vec<(i64, i64, f32)> {
+    if radius < 0.5 {
+        return vec![(0, 0, 1.0)];
+    }
+    let reach = radius.ceil() as i64;
+    let sigma2 = 2.0 * (radius / 2.0).powi(2);
+    let mut kernel = Vec::new();
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let d2 = (dx * dx + dy * dy) as f32;
+            if d2 <= radius * radius {
+                kernel.push((dx, dy, (-d2 / sigma2).exp()));
+            }
+        }
+    }
+    let total: f32 = kernel.iter().map(|&(_, _, w)| w).sum();
+    kernel
+        .iter()
+        .map(|&(dx, dy, w)| (dx, dy, w / total))
+        .collect()
+}
+
+impl Animate for FractalFlame {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Rotation", self.rotation as f32, -180.0..=180.0),
+            Parameter::new("Gamma", self.gamma, 1.0..=5.0),
+            Parameter::new("Vibrancy", self.vibrancy, 0.0..=1.0),
+            Parameter::new("Brightness", self.brightness, 0.2..=4.0),
+            Parameter::new("Quality", self.quality, 1.0..=200.0),
+            Parameter::new("Zoom", self.zoom as f32, 0.01..=10.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Rotation" => self.rotation = value as f64,
+            "Gamma" => self.gamma = value,
+            "Vibrancy" => self.vibrancy = value,
+            "Brightness" => self.brightness = value,
+            "Quality" => self.quality = value,
+            "Zoom" => self.zoom = value as f64,
+            _ => {}
+        }
+    }
+}
+
+impl Render for FractalFlame {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        let mut histogram = Self::new_histogram(width, height);
+        let samples = (self.quality * (width * height) as f32) as usize;
+        self.sample(&mut histogram, rect, samples);
+        self.tone_map(&histogram, Color32::BLACK)
+    }
+}
+
+impl FractalApp for FractalFlame {}
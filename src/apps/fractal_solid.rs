@@ -0,0 +1,311 @@
+use super::FractalApp;
+use crate::raster::{Image, Render};
+use crate::solid::{self, Axis, Solid, Vec3};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the 3D viewer for the Menger sponge and the Sierpinski tetrahedron.
+The meshes come from solid.rs and are drawn with a small software renderer: every triangle is
+turned by the orbit camera and projected with perspective, triangles facing away are skipped,
+each one is shaded by how much it faces the light, and they are sorted from back to front so
+that the nearest ones are painted last (the painter's algorithm).
+Fractal Explanation: https://en.wikipedia.org/wiki/Menger_sponge
+Source: https://en.wikipedia.org/wiki/Painter%27s_algorithm
+*/
+
+pub struct FractalSolid {
+    shape: Shape3d,                 // The solid that is drawn
+    level: usize,                   // The level of the recursion
+    cut: bool,                      // If true, the sponge is cut to show its inside
+    cut_axis: Axis,                 // The axis the sponge is cut along
+    cut_position: f32,              // Where the sponge is cut along the axis, 0 to 1
+    yaw: f32,                       // The angle (in degrees) of the camera around the solid
+    pitch: f32,                     // The angle (in degrees) of the camera above the solid
+    zoom: f32,                      // How big the solid is drawn
+    color: Color32,                 // The color of the solid
+    cut_color: Color32,             // The color of the faces on the cut
+    solid: Solid,                   // The cached mesh
+    last_solid: Option<SolidInput>, // The input of the cached mesh
+    drawn_count: usize,             // The number of triangles drawn in the last frame
+}
+
+// The solids that can be drawn
+#[derive(Clone, Copy, PartialEq)]
+enum Shape3d {
+    MengerSponge,
+    SierpinskiTetrahedron,
+}
+
+impl Shape3d {
+    const ALL: [Shape3d; 2] = [Shape3d::MengerSponge, Shape3d::SierpinskiTetrahedron];
+
+    fn name(self) -> &'static str {
+        match self {
+            Shape3d::MengerSponge => "Menger Sponge",
+            Shape3d::SierpinskiTetrahedron => "Sierpinski Tetrahedron",
+        }
+    }
+
+    // The highest level that can still be turned and drawn smoothly
+    fn max_level(self) -> usize {
+        match self {
+            Shape3d::MengerSponge => 4,
+            Shape3d::SierpinskiTetrahedron => 7,
+        }
+    }
+}
+
+// Everything the mesh is made from: the solid, the level and the cut (axis and layer)
+type SolidInput = (Shape3d, usize, Option<(Axis, usize)>);
+
+impl Default for FractalSolid {
+    fn default() -> Self {
+        Self {
+            shape: Shape3d::MengerSponge,
+            level: 2,
+            cut: false,
+            cut_axis: Axis::Z,
+            cut_position: 0.5,
+            yaw: 35.0,
+            pitch: 25.0,
+            zoom: 1.0,
+            color: Color32::from_rgb(0, 150, 199),
+            cut_color: Color32::from_rgb(255, 165, 0),
+            solid: Solid::default(),
+            last_solid: None,
+            drawn_count: 0,
+        }
+    }
+}
+
+impl epi::App for FractalSolid {
+    fn name(&self) -> &str {
+        "3D Fractals"
+    }
+
+    // Called every frame
+    fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui));
+    }
+}
+
+impl FractalSolid {
+    // Paints the fractal
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter);
+
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        let settings = Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+
+        // After the settings, so that their sliders get dragged instead of the camera
+        self.orbit(ui, painter.clip_rect(), settings.response.rect);
+    }
+
+    // Dragging turns the camera around the solid and scrolling zooms
+    fn orbit(&mut self, ui: &mut Ui, rect: Rect, settings: Rect) {
+        let response = ui.interact(rect, ui.id().with("solid_view"), Sense::drag());
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.yaw = (self.yaw - delta.x * 0.5 + 180.0).rem_euclid(360.0) - 180.0;
+            self.pitch = (self.pitch + delta.y * 0.5).clamp(-89.0, 89.0);
+        }
+
+        let scroll = ui.input().scroll_delta.y;
+        let pointer = ui.input().pointer.hover_pos();
+        if response.hovered() && scroll != 0.0 && !pointer.is_some_and(|p| settings.contains(p)) {
+            self.zoom = (self.zoom * (scroll / 200.0).exp()).clamp(0.2, 20.0);
+        }
+    }
+
+    // An options window for picking the solid and the camera
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ComboBox::from_label("Solid")
+            .selected_text(self.shape.name())
+            .show_ui(ui, |ui| {
+                for shape in Shape3d::ALL {
+                    ui.selectable_value(&mut self.shape, shape, shape.name());
+                }
+            });
+        ui.add(Slider::new(&mut self.level, 0..=self.shape.max_level()).text("Level"));
+
+        if self.shape == Shape3d::MengerSponge {
+            ui.checkbox(&mut self.cut, "Cut Through the Sponge");
+            if self.cut {
+                ui.horizontal(|ui| {
+                    ui.label("Axis");
+                    for axis in Axis::ALL {
+                        ui.radio_value(&mut self.cut_axis, axis, axis.name());
+                    }
+                });
+                ui.add(Slider::new(&mut self.cut_position, 0.0..=1.0).text("Cut position"));
+            }
+        }
+
+        ui.add(Slider::new(&mut self.yaw, -180.0..=180.0).text("Yaw"));
+        ui.add(Slider::new(&mut self.pitch, -89.0..=89.0).text("Pitch"));
+        ui.add(
+            Slider::new(&mut self.zoom, 0.2..=20.0)
+                .logarithmic(true)
+                .text("Zoom"),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            ui.color_edit_button_srgba(&mut self.color);
+            if self.shape == Shape3d::MengerSponge && self.cut {
+                ui.label("Cut");
+                ui.color_edit_button_srgba(&mut self.cut_color);
+            }
+        });
+        ui.label(format!(
+            "Triangles: {} ({} drawn)",
+            self.solid.triangles.len(),
+            self.drawn_count
+        ));
+        egui::reset_button(ui, self);
+    }
+
+    // Makes the mesh again if the solid, level or cut changed
+    fn update_solid(&mut self) {
+        self.level = self.level.min(self.shape.max_level());
+        let cut = if self.cut && self.shape == Shape3d::MengerSponge {
+            let size = 3_usize.pow(self.level as u32);
+            Some((
+                self.cut_axis,
+                (self.cut_position * size as f32).round() as usize,
+            ))
+        } else {
+            None
+        };
+        let input = (self.shape, self.level, cut);
+        if self.last_solid == Some(input) {
+            return;
+        }
+        self.solid = match self.shape {
+            Shape3d::MengerSponge => solid::menger_sponge(self.level, cut),
+            Shape3d::SierpinskiTetrahedron => solid::sierpinski_tetrahedron(self.level),
+        };
+        self.last_solid = Some(input);
+    }
+
+    // Turns a point of the solid to where the camera sees it, with z going away from the camera
+    fn to_camera(&self, p: Vec3) -> Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.to_radians().sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.to_radians().sin_cos();
+        let x = p.x * yaw_cos - p.z * yaw_sin;
+        let z = p.x * yaw_sin + p.z * yaw_cos;
+        let y = p.y * pitch_cos + z * pitch_sin;
+        let z = z * pitch_cos - p.y * pitch_sin;
+        Vec3::new(x, y, z)
+    }
+
+    // Projects, shades and sorts the triangles into one mesh
+    fn solid_shapes(&mut self, rect: Rect) -> Vec<Shape> {
+        self.update_solid();
+
+        const DISTANCE: f32 = 3.0; // How far the camera is from the center of the solid
+        let focal = 0.45 * rect.width().min(rect.height()) * DISTANCE * self.zoom;
+        let light = Vec3::new(-0.4, 0.6, -0.7).normalized();
+
+        let camera: Vec<Vec3> = self
+            .solid
+            .vertices
+            .iter()
+            .map(|&p| self.to_camera(p) + Vec3::new(0.0, 0.0, DISTANCE))
+            .collect();
+        let screen: Vec<Pos2> = camera
+            .iter()
+            .map(|p| rect.center() + vec2(p.x, -p.y) * (focal / p.z.max(1e-3)))
+            .collect();
+
+        // the triangles facing the camera, with their depth and color
+        let mut visible: Vec<(f32, [usize; 3], Color32)> = Vec::new();
+        for triangle in &self.solid.triangles {
+            let [a, b, c] = triangle.corners;
+            let normal = self.to_camera(triangle.normal);
+            if normal.dot(camera[a]) >= 0.0 || camera[a].z < 0.1 {
+                continue;
+            }
+            let brightness = 0.25 + 0.75 * normal.dot(light).max(0.0);
+            let color = if triangle.cut {
+                self.cut_color
+            } else {
+                self.color
+            };
+            let shade = |c: u8| (c as f32 * brightness).round() as u8;
+            let color = Color32::from_rgb(shade(color.r()), shade(color.g()), shade(color.b()));
+            visible.push((camera[a].z + camera[b].z + camera[c].z, [a, b, c], color));
+        }
+        visible.sort_by(|x, y| y.0.total_cmp(&x.0));
+        self.drawn_count = visible.len();
+
+        let mut mesh = epaint::Mesh::default();
+        for (_, corners, color) in visible {
+            let first = mesh.vertices.len() as u32;
+            for corner in corners {
+                mesh.colored_vertex(screen[corner], color);
+            }
+            mesh.add_triangle(first, first + 1, first + 2);
+        }
+        vec![Shape::mesh(mesh)]
+    }
+
+    fn paint(&mut self, painter: &Painter) {
+        let shapes = self.solid_shapes(painter.clip_rect());
+        painter.extend(shapes);
+    }
+}
+
+impl Animate for FractalSolid {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Level", self.level as f32, 0.0..=7.0),
+            Parameter::new("Yaw", self.yaw, -180.0..=180.0),
+            Parameter::new("Pitch", self.pitch, -89.0..=89.0),
+            Parameter::new("Zoom", self.zoom, 0.2..=20.0),
+            Parameter::new("Cut position", self.cut_position, 0.0..=1.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Level" => self.level = value.round() as usize,
+            "Yaw" => self.yaw = value,
+            "Pitch" => self.pitch = value,
+            "Zoom" => self.zoom = value,
+            "Cut position" => self.cut_position = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for FractalSolid {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        Image::from_shapes(width, height, Color32::BLACK, &self.solid_shapes(rect))
+    }
+}
+
+impl FractalApp for FractalSolid {
+    fn model(&mut self, _thickness: f32) -> Option<Solid> {
+        self.update_solid();
+        Some(self.solid.clone())
+    }
+}
@@ -0,0 +1,443 @@
+use super::FractalApp;
+use crate::dimension::{self, Counted, KnownDimension};
+use crate::raster::{Image, Render};
+use crate::solid::{self, Solid, Vec3};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the code for recursively generating a fractal tree (also called a fractal canopy).
+Every branch splits into smaller branches that are spread between the right and the left angle.
+The left and right branches can have different angles and length factors, and with a seed the
+angles, lengths and whether a branch grows at all can be jittered to make natural-looking trees.
+Every branch gets its own random generator seeded from its parent, so the same seed always gives
+the same tree, even while it is growing.
+The tree can be animated: wind sways the branches with a smooth noise field that changes over
+time and is stronger for the thinner branches, and growth draws the tree level by level.
+Fractal Explanation: https://en.wikipedia.org/wiki/Fractal_canopy
+Noise: https://en.wikipedia.org/wiki/Value_noise
+*/
+
+pub struct FractalTree {
+    paused: bool,             // If true, the animation time stops moving
+    time: f64,                // The animation time in seconds
+    wind: bool,               // If true, the branches sway in the wind
+    wind_strength: f32,       // The largest angle (in radians) a branch sways by
+    wind_speed: f32,          // How quickly the wind changes
+    growth: bool,             // If true, the tree grows from the trunk to the leaves
+    growth_duration: f32,     // The number of seconds it takes the tree to grow
+    zoom: f32,                // The length of the first branches as a fraction of the screen height
+    start_line_width: f32,    // The width of the first branches
+    depth: usize,             // The number of times the branches split
+    branches: usize,          // The number of branches every branch splits into
+    left_angle: f32,          // The angle (in radians) of the leftmost branch
+    right_angle: f32,         // The angle (in radians) of the rightmost branch
+    left_length_factor: f32,  // How much shorter the leftmost branch is than its parent
+    right_length_factor: f32, // How much shorter the rightmost branch is than its parent
+    angle_jitter: f32,        // The largest random change (in radians) to a branch's angle
+    length_jitter: f32,       // The largest random change to a branch's length, as a fraction
+    survival: f64,            // The probability that a branch grows at all
+    seed: u64,                // The seed for the random jitter
+    luminance_factor: f32,    // How much darker each level of branches is than the one before it
+    width_factor: f32,        // How much thinner each level of branches is than the one before it
+    line_count: usize,        // The number of lines in the last drawn tree
+    hit_line_limit: bool,     // If true, the last drawn tree was cut off at MAX_LINES
+
+    shapes: Vec<Shape>,
+    r: u8,
+    g: u8,
+    b: u8,
+    up_down: f32,
+}
+
+// The most lines drawn for one tree, so that many branches and a high depth can't freeze the app
+const MAX_LINES: usize = 200_000;
+
+impl Default for FractalTree {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            time: 0.0,
+            wind: false,
+            wind_strength: 0.15,
+            wind_speed: 0.6,
+            growth: false,
+            growth_duration: 4.0,
+            zoom: 0.12,
+            start_line_width: 2.5,
+            depth: 9,
+            branches: 2,
+            left_angle: 0.26,
+            right_angle: 0.26,
+            left_length_factor: 0.8,
+            right_length_factor: 0.8,
+            angle_jitter: 0.0,
+            length_jitter: 0.0,
+            survival: 1.0,
+            seed: 0,
+            luminance_factor: 0.8,
+            width_factor: 0.9,
+            line_count: 0,
+            hit_line_limit: false,
+
+            shapes: Vec::new(),
+            r: 255,
+            g: 0,
+            b: 0,
+            up_down: 1.0,
+        }
+    }
+}
+
+impl epi::App for FractalTree {
+    fn name(&self) -> &str {
+        "Fractal Tree"
+    }
+
+    fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui));
+    }
+}
+
+impl FractalTree {
+    pub fn ui(&mut self, ui: &mut Ui) {
+        // Moves the animation forward
+        if (self.wind || self.growth) && !self.paused {
+            self.time += ui.input().unstable_dt as f64;
+            ui.ctx().request_repaint();
+        }
+
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter);
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        //sliders for all the input values
+        ui.add(Slider::new(&mut self.depth, 1..=16).text("Depth"));
+        ui.add(Slider::new(&mut self.zoom, 0.05..=0.5).text("Zoom"));
+        ui.add(Slider::new(&mut self.start_line_width, 0.5..=10.0).text("Line width"));
+        ui.add(Slider::new(&mut self.width_factor, 0.5..=1.0).text("Width factor"));
+        ui.add(Slider::new(&mut self.luminance_factor, 0.5..=1.0).text("Luminance factor"));
+
+        ui.add(Slider::new(&mut self.r, 0..=255).text("r"));
+
+        ui.add(Slider::new(&mut self.g, 0..=255).text("g"));
+        ui.add(Slider::new(&mut self.b, 0..=255).text("b"));
+        ui.add(Slider::new(&mut self.up_down, 0.1..=2.0).text("Vertical Shift"));
+        if self.hit_line_limit {
+            ui.label(format!("Lines: {} (limit reached)", self.line_count));
+        } else {
+            ui.label(format!("Lines: {}", self.line_count));
+        }
+
+        ui.separator();
+
+        //branching settings
+        ui.add(Slider::new(&mut self.branches, 2..=6).text("Branches"));
+        ui.add(Slider::new(&mut self.left_angle, 0.0..=1.5).text("Left angle"));
+        ui.add(Slider::new(&mut self.right_angle, 0.0..=1.5).text("Right angle"));
+        ui.add(Slider::new(&mut self.left_length_factor, 0.5..=0.95).text("Left length factor"));
+        ui.add(Slider::new(&mut self.right_length_factor, 0.5..=0.95).text("Right length factor"));
+        ui.add(Slider::new(&mut self.angle_jitter, 0.0..=0.5).text("Angle jitter"));
+        ui.add(Slider::new(&mut self.length_jitter, 0.0..=0.5).text("Length jitter"));
+        ui.add(Slider::new(&mut self.survival, 0.5..=1.0).text("Branch survival"));
+        super::seed_ui(ui, &mut self.seed);
+
+        ui.separator();
+
+        //animation settings
+        ui.checkbox(&mut self.wind, "Wind");
+        if self.wind {
+            ui.add(Slider::new(&mut self.wind_strength, 0.0..=0.6).text("Wind strength"));
+            ui.add(Slider::new(&mut self.wind_speed, 0.1..=3.0).text("Wind speed"));
+        }
+        ui.checkbox(&mut self.growth, "Growth");
+        if self.growth {
+            ui.add(Slider::new(&mut self.growth_duration, 0.5..=10.0).text("Growth duration"));
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.paused, "Paused");
+            if ui.button("Restart").clicked() {
+                self.time = 0.0;
+            }
+        });
+        egui::reset_button(ui, self);
+    }
+
+    // Draws the branches growing out of (x1, y1). `length` is the length of the parent branch
+    // and `seed` seeds the random jitter of this node and every node below it.
+    fn drawtree(&mut self, length: f32, x1: f32, y1: f32, angle: f32, level: usize, seed: u64) {
+        // while growing, only part of the branch is drawn
+        let grown = self.grown_fraction(level);
+        if grown <= 0.0 || self.shapes.len() >= MAX_LINES {
+            self.hit_line_limit |= self.shapes.len() >= MAX_LINES;
+            return;
+        }
+        let is_leaf = level + 1 >= self.depth;
+
+        // each level is darker than the one before it
+        let luminance = self.luminance_factor.powi(level as i32);
+        let darken = |channel: u8| (channel as f32 * luminance).round() as u8;
+        let mut curr_r = darken(self.r);
+        let mut curr_g = darken(self.g);
+        let mut curr_b = darken(self.b);
+
+        // switches to opposite color if it is the leaf node
+        if is_leaf {
+            curr_r = 255 - curr_r;
+            curr_g = 255 - curr_g;
+            curr_b = 255 - curr_b;
+        }
+        let color = Color32::from_rgb(curr_r, curr_g, curr_b);
+
+        // each level is thinner than the one before it
+        let width = self.start_line_width * self.width_factor.powi(level as i32);
+
+        // the wind rotates all the branches
+        let angle = angle + self.wind_sway(level, x1);
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        for i in 0..self.branches {
+            // the branches are spread evenly from the rightmost to the leftmost one.
+            // Every random value is drawn even when it isn't used, so the tree doesn't
+            // change while it grows.
+            let t = i as f32 / (self.branches - 1) as f32;
+            let survives = rng.gen_bool(self.survival) || level == 0;
+            let angle_change = self.angle_jitter * rng.gen_range(-1.0..=1.0);
+            let length_change = 1.0 + self.length_jitter * rng.gen_range(-1.0..=1.0);
+            let child_seed = rng.gen::<u64>();
+            if !survives {
+                continue;
+            }
+
+            let branch_angle =
+                angle - self.right_angle + t * (self.left_angle + self.right_angle) + angle_change;
+            let length_factor =
+                self.right_length_factor + t * (self.left_length_factor - self.right_length_factor);
+            let branch_length = length * length_factor * length_change;
+
+            //calculate tip x,y for the branch
+            let drawn_length = branch_length * grown;
+            let x2 = x1 + branch_angle.cos() * drawn_length;
+            let y2 = y1 - branch_angle.sin() * drawn_length;
+            self.paint_line([pos2(x1, y1), pos2(x2, y2)], color, width);
+
+            if !is_leaf && grown >= 1.0 {
+                //recursive call to draw the next subtree
+                self.drawtree(branch_length, x2, y2, branch_angle, level + 1, child_seed);
+            }
+        }
+    }
+
+    // How much of the branches at a level have grown, between 0 and 1.
+    // Every level takes an equal share of the growth duration.
+    fn grown_fraction(&self, level: usize) -> f32 {
+        if !self.growth {
+            return 1.0;
+        }
+        let progress = self.time as f32 / self.growth_duration * self.depth as f32;
+        (progress - level as f32).clamp(0.0, 1.0)
+    }
+
+    // The angle the wind rotates a branch by. The noise is sampled along time and the
+    // horizontal position so gusts travel across the tree, and every level has its own noise.
+    fn wind_sway(&self, level: usize, x: f32) -> f32 {
+        if !self.wind {
+            return 0.0;
+        }
+        let t = self.time as f32 * self.wind_speed;
+        let seed = level as u32;
+        let gust = 0.7 * value_noise(t + x * 0.004, seed)
+            + 0.3 * value_noise(2.7 * t + x * 0.01, seed + 1000);
+        // thinner branches sway more than the trunk
+        let flexibility = (level + 1) as f32 / self.depth as f32;
+        self.wind_strength * flexibility * gust
+    }
+
+    fn paint_line(&mut self, points: [Pos2; 2], color: Color32, width: f32) {
+        let line = [points[0], points[1]];
+
+        self.shapes.push(Shape::line_segment(line, (width, color)));
+    }
+
+    // Generates the shapes of the tree for the given rectangle
+    fn tree_shapes(&mut self, rect: Rect) -> Vec<Shape> {
+        self.shapes = Vec::new();
+        self.hit_line_limit = false;
+
+        //initial starting parameters for the tree, the first branches are zoom times the height
+        let average_factor = (self.left_length_factor + self.right_length_factor) / 2.0;
+        self.drawtree(
+            rect.height() * self.zoom / average_factor,
+            rect.width() / 2.0,
+            2.0 * rect.height() * self.up_down / 3.0,
+            //90 degrees in radians so rotation is correct
+            std::f32::consts::FRAC_PI_2,
+            0,
+            self.seed,
+        );
+
+        self.line_count = self.shapes.len();
+        std::mem::take(&mut self.shapes)
+    }
+
+    fn paint(&mut self, painter: &Painter) {
+        let shapes = self.tree_shapes(painter.clip_rect());
+        painter.extend(shapes);
+    }
+}
+
+// Smooth 1D value noise between -1 and 1, every seed gives a different curve
+fn value_noise(x: f32, seed: u32) -> f32 {
+    // a random value at every integer, from an integer hash
+    let random = |i: i32| {
+        let mut h = (i as u32).wrapping_mul(374_761_393) ^ seed.wrapping_mul(668_265_263);
+        h = (h ^ (h >> 13)).wrapping_mul(1_274_126_177);
+        h ^= h >> 16;
+        h as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+
+    // smoothstep between the two surrounding integers
+    let i = x.floor();
+    let f = x - i;
+    let t = f * f * (3.0 - 2.0 * f);
+    let (a, b) = (random(i as i32), random(i as i32 + 1));
+    a + (b - a) * t
+}
+
+impl Animate for FractalTree {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Depth", self.depth as f32, 1.0..=16.0),
+            Parameter::new("Time", self.time as f32, 0.0..=120.0),
+            Parameter::new("Wind strength", self.wind_strength, 0.0..=0.6),
+            Parameter::new("Zoom", self.zoom, 0.05..=0.5),
+            Parameter::new("Branches", self.branches as f32, 2.0..=6.0),
+            Parameter::new("Left angle", self.left_angle, 0.0..=1.5),
+            Parameter::new("Right angle", self.right_angle, 0.0..=1.5),
+            Parameter::new("Left length factor", self.left_length_factor, 0.5..=0.95),
+            Parameter::new("Right length factor", self.right_length_factor, 0.5..=0.95),
+            Parameter::new("Angle jitter", self.angle_jitter, 0.0..=0.5),
+            Parameter::new("Length jitter", self.length_jitter, 0.0..=0.5),
+            Parameter::new("Branch survival", self.survival as f32, 0.5..=1.0),
+            Parameter::new("Line width", self.start_line_width, 0.5..=10.0),
+            Parameter::new("Width factor", self.width_factor, 0.5..=1.0),
+            Parameter::new("Luminance factor", self.luminance_factor, 0.5..=1.0),
+            Parameter::new("Vertical Shift", self.up_down, 0.1..=2.0),
+            Parameter::new("r", self.r as f32, 0.0..=255.0),
+            Parameter::new("g", self.g as f32, 0.0..=255.0),
+            Parameter::new("b", self.b as f32, 0.0..=255.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Depth" => self.depth = value.round() as usize,
+            "Time" => self.time = value as f64,
+            "Wind strength" => self.wind_strength = value,
+            "Zoom" => self.zoom = value,
+            "Branches" => self.branches = value.round() as usize,
+            "Left angle" => self.left_angle = value,
+            "Right angle" => self.right_angle = value,
+            "Left length factor" => self.left_length_factor = value,
+            "Right length factor" => self.right_length_factor = value,
+            "Angle jitter" => self.angle_jitter = value,
+            "Length jitter" => self.length_jitter = value,
+            "Branch survival" => self.survival = value as f64,
+            "Line width" => self.start_line_width = value,
+            "Width factor" => self.width_factor = value,
+            "Luminance factor" => self.luminance_factor = value,
+            "Vertical Shift" => self.up_down = value,
+            "r" => self.r = value.round() as u8,
+            "g" => self.g = value.round() as u8,
+            "b" => self.b = value.round() as u8,
+            _ => {}
+        }
+    }
+}
+
+impl Render for FractalTree {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        Image::from_shapes(width, height, Color32::BLACK, &self.tree_shapes(rect))
+    }
+}
+
+impl FractalApp for FractalTree {
+    // The tree as tubes lying flat. The first branches are as wide as the thickness times the
+    // size of the tree, and the others get thinner by the width factor like the drawn lines.
+    fn model(&mut self, thickness: f32) -> Option<Solid> {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(1000.0, 1000.0));
+        let lines: Vec<([Pos2; 2], f32)> = self
+            .tree_shapes(rect)
+            .iter()
+            .filter_map(|shape| match shape {
+                Shape::LineSegment { points, stroke } => Some((*points, stroke.width)),
+                _ => None,
+            })
+            .collect();
+
+        let bounds = lines.iter().fold(Rect::NOTHING, |bounds, (points, _)| {
+            bounds.union(Rect::from_two_pos(points[0], points[1]))
+        });
+        let width_scale = thickness * bounds.width().max(bounds.height()) / self.start_line_width;
+        let on_floor = |p: Pos2| Vec3::new(p.x, 0.0, p.y);
+        let segments: Vec<(Vec3, Vec3, f32)> = lines
+            .iter()
+            .map(|(points, width)| {
+                (
+                    on_floor(points[0]),
+                    on_floor(points[1]),
+                    width * width_scale,
+                )
+            })
+            .collect();
+        Some(solid::tubes(&segments))
+    }
+
+    // The branches are copies of the tree scaled by their length factors. The tree is at least
+    // as rough as its lines and can't be rougher than the plane, and the random trees have no
+    // exact dimension.
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        if self.angle_jitter > 0.0 || self.length_jitter > 0.0 || self.survival < 1.0 {
+            return None;
+        }
+        let ratios: Vec<f64> = (0..self.branches)
+            .map(|i| {
+                let t = i as f64 / (self.branches - 1).max(1) as f64;
+                let right = self.right_length_factor as f64;
+                right + t * (self.left_length_factor as f64 - right)
+            })
+            .collect();
+        let formula = if self.left_length_factor == self.right_length_factor {
+            format!("log {} / log {:.3}", self.branches, 1.0 / ratios[0])
+        } else {
+            String::from("similarity dimension")
+        };
+        Some(KnownDimension {
+            formula,
+            value: dimension::similarity_dimension(&ratios).clamp(1.0, 2.0),
+            counted: Counted::Drawn,
+        })
+    }
+}
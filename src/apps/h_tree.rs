@@ -1,52 +1,41 @@
+use super::FractalApp;
+use crate::dimension::{Counted, KnownDimension};
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
 use eframe::{egui, epi};
 use egui::{containers::*, widgets::*, *};
-use std::mem::swap;
 
-// use std::time::{ Instant };
 #[derive(PartialEq)]
 
+/*
+This file contains the code for recursively generating an H tree.
+Every line segment gets two perpendicular segments centered on its ends, each one shorter by a
+factor of √2. The segments alternate between horizontal and vertical, and in the limit the tree
+fills a rectangle whose sides have a ratio of √2.
+Fractal Explanation: https://en.wikipedia.org/wiki/H_tree
+*/
+
 pub struct HTree {
-    paused: bool,
-    time: f64,
-    zoom: f32,
-    start_line_width: f32,
-    depth: usize,
-    length_factor: f32,
-    luminance_factor: f32,
-    width_factor: f32,
-    line_count: usize,
-
-    n: f32,
-    last_n: f32,
-    shapes: Vec<Shape>,
-    _branch_angle: f32,
-    r: u8,
-    g: u8,
-    b: u8,
-    up_down: f32,
+    depth: usize,          // The number of levels of segments
+    ratio: f32,            // How much shorter each level is than the one before it
+    start_line_width: f32, // The width of the first segment
+    taper_lines: bool,     // If true, each level is drawn thinner than the one before it
+    width_factor: f32,     // How much thinner each level is when tapering
+    show_limit: bool,      // If true, shows the rectangle that the tree fills in the limit
+    shapes: Vec<Shape>,    // A vector containing the shapes that will be painted on the screen
 }
 
 impl Default for HTree {
     fn default() -> Self {
         Self {
-            paused: false,
-            time: 0.0,
-            zoom: 0.25,
-            start_line_width: 2.5,
-            depth: 9,
-            length_factor: 0.8,
-            luminance_factor: 0.8,
-            width_factor: 0.9,
-            line_count: 0,
-
-            n: 1.0,
-            last_n: 1.0,
+            depth: 8,
+            ratio: std::f32::consts::SQRT_2,
+            start_line_width: 4.0,
+            taper_lines: true,
+            width_factor: 0.8,
+            show_limit: false,
             shapes: Vec::new(),
-            _branch_angle: 0.26,
-            r: 255,
-            g: 0,
-            b: 0,
-            up_down: 1.0,
         }
     }
 }
@@ -56,6 +45,7 @@ impl epi::App for HTree {
         "H Tree"
     }
 
+    // Called every frame
     fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
         egui::CentralPanel::default()
             .frame(Frame::dark_canvas(&ctx.style()))
@@ -64,18 +54,15 @@ impl epi::App for HTree {
 }
 
 impl HTree {
+    // Paints the fractal
     pub fn ui(&mut self, ui: &mut Ui) {
-        //Don't recalculate if we have the same n
-        if (self.last_n - self.n).abs() < 0.01 {
-            ui.ctx().request_repaint();
-        }
-
         let painter = Painter::new(
             ui.ctx().clone(),
             ui.layer_id(),
             ui.available_rect_before_wrap(),
         );
         self.paint(&painter);
+
         // Make sure we allocate what we used (everything)
         ui.expand_to_include_rect(painter.clip_rect());
 
@@ -85,120 +72,130 @@ impl HTree {
                 ui.set_max_width(270.0);
                 CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
             });
-
-        self.last_n = self.n;
     }
 
+    // An options window for setting the depth and look of the tree
     fn options_ui(&mut self, ui: &mut Ui) {
-        //sliders for all the input values
-        ui.add(Slider::new(&mut self.n, 1.0..=3.0).text("N"));
-
-        ui.add(Slider::new(&mut self._branch_angle, 0.01..=1.0).text("Branch angle"));
-        ui.add(Slider::new(&mut self.r, 0..=255).text("r"));
-
-        ui.add(Slider::new(&mut self.g, 0..=255).text("g"));
-        ui.add(Slider::new(&mut self.b, 0..=255).text("b"));
-        ui.add(Slider::new(&mut self.up_down, 0.1..=2.0).text("Vertical Shift"));
+        ui.add(Slider::new(&mut self.depth, 1..=14).text("Depth"));
+        ui.horizontal(|ui| {
+            ui.add(Slider::new(&mut self.ratio, 1.1..=2.0).text("Length ratio"));
+            if ui.small_button("√2").clicked() {
+                self.ratio = std::f32::consts::SQRT_2;
+            }
+        });
+        ui.add(Slider::new(&mut self.start_line_width, 0.5..=10.0).text("Line width"));
+        ui.checkbox(&mut self.taper_lines, "Taper Lines");
+        if self.taper_lines {
+            ui.add(Slider::new(&mut self.width_factor, 0.3..=1.0).text("Width factor"));
+        }
+        ui.checkbox(&mut self.show_limit, "Show Space-Filling Limit");
+        ui.label(format!("{} segments", (1_usize << self.depth) - 1));
         egui::reset_button(ui, self);
     }
 
-    fn drawtree(
-        &mut self,
-        length: f32,
-        x1: f32,
-        y1: f32,
-        angle: f32,
-        painter: &Painter,
-        // start: Instant,
-    ) {
-        // each branch is 87% of the length as previous
-        let _scaling_factor = 0.87;
-        //min length based on input slider
-        let _min_branch_length = 20.0 * (4.0 - self.n);
-
-        // switches to opposite color if it is the leaf node
-        let mut curr_r = self.r;
-        let mut curr_g = self.g;
-        let mut curr_b = self.b;
-        if length <= _min_branch_length {
-            curr_r = 255 - curr_r;
-            curr_g = 255 - curr_g;
-            curr_b = 255 - curr_b;
+    // The half width and half height of the rectangle that the tree fills in the limit,
+    // for a first segment of length 1. These are geometric series over every other level.
+    fn limit_half_size(&self) -> Vec2 {
+        let shrink_per_two_levels = 1.0 / (self.ratio * self.ratio);
+        let series = 1.0 / (1.0 - shrink_per_two_levels);
+        vec2(0.5 * series, 0.5 / self.ratio * series)
+    }
+
+    // A recursive function for creating the H tree. Draws the segment around its center
+    // and recurses on both of its ends with a perpendicular segment.
+    fn h_tree(&mut self, center: Pos2, length: f32, horizontal: bool, width: f32, level: usize) {
+        if level >= self.depth {
+            return;
         }
 
-        //calculate tip x,y for left and right trees
-        let xr = x1 + ((angle - self._branch_angle).cos() * length);
-        let yr = y1 - ((angle - self._branch_angle).sin() * length);
-        let xl = x1 + ((angle + self._branch_angle).cos() * length);
-        let yl = y1 - ((angle + self._branch_angle).sin() * length);
-        let p1 = pos2(x1, y1);
-        let p2 = pos2(xr, yr);
-        let p3 = pos2(xl, yl);
-        // draws two branches
-        self.paint_line(
-            [p1, p2],
-            Color32::from_rgb(curr_r, curr_g, curr_b),
-            0.5,
-            painter,
-        );
-        self.paint_line(
-            [p1, p3],
-            Color32::from_rgb(curr_r, curr_g, curr_b),
-            0.5,
-            painter,
-        );
-        if length > _min_branch_length {
-            //change color of leaves
-            //recursive call to draw next two subtrees
-            self.drawtree(
-                length * _scaling_factor,
-                xr,
-                yr,
-                angle - self._branch_angle,
-                painter,
-                // start,
-            );
-
-            self.drawtree(
-                length * _scaling_factor,
-                xl,
-                yl,
-                angle + self._branch_angle,
-                painter,
-                // start,
-            );
+        let half = if horizontal {
+            vec2(length / 2.0, 0.0)
         } else {
-            //benchmarks that can be printed
-            // let _end = start.elapsed();
-            // println!("{} seconds for single thread.",end.as_secs());
-        }
+            vec2(0.0, length / 2.0)
+        };
+        let (start, end) = (center - half, center + half);
+
+        // Each level gets its own hue
+        let hue = level as f32 / self.depth.max(2) as f32 * 0.8;
+        let (r, g, b) = palette::hsv_to_rgb(hue, 0.7, 1.0);
+        self.shapes.push(Shape::line_segment(
+            [start, end],
+            (width, Color32::from_rgb(r, g, b)),
+        ));
+
+        let next_length = length / self.ratio;
+        let next_width = if self.taper_lines {
+            width * self.width_factor
+        } else {
+            width
+        };
+        self.h_tree(start, next_length, !horizontal, next_width, level + 1);
+        self.h_tree(end, next_length, !horizontal, next_width, level + 1);
     }
 
-    fn paint_line(&mut self, points: [Pos2; 2], color: Color32, width: f32, _painter: &Painter) {
-        let line = [points[0], points[1]];
+    // Generates the shapes of the tree, scaled so that its limit fits in the rectangle
+    fn tree_shapes(&mut self, rect: Rect) -> Vec<Shape> {
+        self.shapes = Vec::new();
+
+        let limit = self.limit_half_size();
+        let length = 0.45 * (rect.width() / limit.x).min(rect.height() / limit.y);
 
-        self.shapes.push(Shape::line_segment(line, (width, color)));
+        if self.show_limit {
+            self.shapes.push(Shape::rect_filled(
+                Rect::from_center_size(rect.center(), 2.0 * limit * length),
+                0.0,
+                Color32::from_rgba_unmultiplied(255, 255, 255, 24),
+            ));
+        }
+
+        self.h_tree(rect.center(), length, true, self.start_line_width, 0);
+
+        std::mem::take(&mut self.shapes)
     }
 
     fn paint(&mut self, painter: &Painter) {
-        self.shapes = Vec::new();
+        let shapes = self.tree_shapes(painter.clip_rect());
+        painter.extend(shapes);
+    }
+}
 
-        let rect = painter.clip_rect();
-        // let start = Instant::now();
-
-        //initial starting parameters for the tree
-        self.drawtree(
-            100.0,
-            rect.width() / 2.0,
-            2.0 * rect.height() * self.up_down / 3.0,
-            //90 degrees in radians so rotation is correct
-            1.5708,
-            painter,
-            // start,
-        );
+impl Animate for HTree {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Depth", self.depth as f32, 1.0..=14.0),
+            Parameter::new("Length ratio", self.ratio, 1.1..=2.0),
+            Parameter::new("Line width", self.start_line_width, 0.5..=10.0),
+            Parameter::new("Width factor", self.width_factor, 0.3..=1.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Depth" => self.depth = value.round() as usize,
+            "Length ratio" => self.ratio = value,
+            "Line width" => self.start_line_width = value,
+            "Width factor" => self.width_factor = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for HTree {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        Image::from_shapes(width, height, Color32::BLACK, &self.tree_shapes(rect))
+    }
+}
 
-        let mut x: std::vec::Vec<Shape> = Vec::new();
-        swap(&mut x, &mut self.shapes);
-        painter.extend(x);
+impl FractalApp for HTree {
+    // Every segment has 2 children that are shorter by the ratio, and at a ratio of √2 or less
+    // the tree fills its rectangle
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        let ratio = self.ratio as f64;
+        Some(KnownDimension {
+            formula: format!("log 2 / log {:.3}", ratio),
+            value: (2.0_f64.ln() / ratio.ln()).clamp(1.0, 2.0),
+            counted: Counted::Drawn,
+        })
     }
 }
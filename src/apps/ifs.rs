@@ -0,0 +1,713 @@
+use super::FractalApp;
+use crate::dimension::{self, Counted, KnownDimension};
+use crate::palette;
+use crate::raster::{self, Image, Render};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the designer for iterated function systems (IFS). An IFS is a list of affine
+maps that each shrink the plane, and its attractor is the one shape that is made of the copies
+of itself that the maps give. It is drawn with the chaos game: a point is moved by a random map
+again and again (each map is picked with its own probability) and every pixel counts how often
+the point lands on it. The counts are shown on a log scale, since a few pixels are hit far more
+often than the rest.
+Every map is drawn as the parallelogram that it turns the frame around the attractor into. By
+the collage theorem, the attractor looks like the frame when the parallelograms cover it, so
+the maps can be designed by dragging the corners of the parallelograms over the shape.
+Fractal Explanation: https://en.wikipedia.org/wiki/Iterated_function_system
+Source: https://en.wikipedia.org/wiki/Collage_theorem
+*/
+
+pub struct Ifs {
+    maps: Vec<AffineMap>,               // The maps of the system
+    frame: [f64; 4],                    // The rectangle (x0, y0, x1, y1) around the attractor
+    selected: usize,                    // The map that is edited in the settings
+    dragged: Option<(usize, Handle)>,   // The map and the part of it that is being dragged
+    point_count: usize,                 // The number of points plotted by the chaos game
+    seed: u64,                          // The seed of the random map choices
+    color_by_map: bool,                 // If true, points get the color of the last map used
+    brightness: f32,                    // Brightens rarely hit pixels, 1 is a plain log scale
+    show_maps: bool,                    // If true, the maps are drawn on top as parallelograms
+    color: Color32,                     // The color of the attractor
+    texture: Option<(TextureId, Vec2)>, // The rendered attractor and its size
+    last_input: Option<ImageInput>,     // The input of the rendered attractor
+}
+
+// An affine map (x, y) -> (a x + b y + e, c x + d y + f), picked with the given probability.
+// The fractal flames are built on the same maps.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) struct AffineMap {
+    pub(super) a: f64,
+    pub(super) b: f64,
+    pub(super) c: f64,
+    pub(super) d: f64,
+    pub(super) e: f64,
+    pub(super) f: f64,
+    pub(super) probability: f64,
+}
+
+impl AffineMap {
+    pub(super) fn new(coefficients: [f64; 7]) -> Self {
+        let [a, b, c, d, e, f, probability] = coefficients;
+        Self {
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+            probability,
+        }
+    }
+
+    pub(super) fn apply(&self, p: [f64; 2]) -> [f64; 2] {
+        [
+            self.a * p[0] + self.b * p[1] + self.e,
+            self.c * p[0] + self.d * p[1] + self.f,
+        ]
+    }
+
+    // Drag values for the six coefficients, in the layout of the matrix and the translation
+    pub(super) fn coefficients_ui(&mut self, ui: &mut Ui) {
+        let coefficient = |ui: &mut Ui, value: &mut f64, name: &str, speed: f64| {
+            ui.add(
+                DragValue::new(value)
+                    .speed(speed)
+                    .prefix(format!("{}: ", name)),
+            );
+        };
+        Grid::new("affine_coefficients").show(ui, |ui| {
+            coefficient(ui, &mut self.a, "a", 0.005);
+            coefficient(ui, &mut self.b, "b", 0.005);
+            ui.end_row();
+            coefficient(ui, &mut self.c, "c", 0.005);
+            coefficient(ui, &mut self.d, "d", 0.005);
+            ui.end_row();
+            coefficient(ui, &mut self.e, "e", 0.01);
+            coefficient(ui, &mut self.f, "f", 0.01);
+            ui.end_row();
+        });
+    }
+
+    // How much the map scales areas by
+    fn determinant(&self) -> f64 {
+        self.a * self.d - self.b * self.c
+    }
+
+    // Where the map moves the corners of the frame, counter-clockwise from the bottom left
+    fn corners(&self, frame: [f64; 4]) -> [[f64; 2]; 4] {
+        let [x0, y0, x1, y1] = frame;
+        [[x0, y0], [x1, y0], [x1, y1], [x0, y1]].map(|p| self.apply(p))
+    }
+
+    // The map that moves the bottom left, bottom right and top left corners of the frame to
+    // the given points
+    fn from_corners(frame: [f64; 4], corners: [[f64; 2]; 3], probability: f64) -> Self {
+        let [x0, y0, x1, y1] = frame;
+        let [p0, p1, p3] = corners;
+        let (a, c) = ((p1[0] - p0[0]) / (x1 - x0), (p1[1] - p0[1]) / (x1 - x0));
+        let (b, d) = ((p3[0] - p0[0]) / (y1 - y0), (p3[1] - p0[1]) / (y1 - y0));
+        Self {
+            a,
+            b,
+            c,
+            d,
+            e: p0[0] - a * x0 - b * y0,
+            f: p0[1] - c * x0 - d * y0,
+            probability,
+        }
+    }
+}
+
+// The parts of a parallelogram that can be dragged
+#[derive(Clone, Copy, PartialEq)]
+enum Handle {
+    Corner(usize), // One of the three corners that set the map, the fourth one follows them
+    Inside,        // The whole parallelogram, which moves the map without changing its shape
+}
+
+// The named systems, with the coefficients a, b, c, d, e, f and the probability of every map
+const PRESETS: [(&str, &[[f64; 7]]); 4] = [
+    (
+        "Barnsley Fern",
+        &[
+            [0.0, 0.0, 0.0, 0.16, 0.0, 0.0, 0.01],
+            [0.85, 0.04, -0.04, 0.85, 0.0, 1.6, 0.85],
+            [0.2, -0.26, 0.23, 0.22, 0.0, 1.6, 0.07],
+            [-0.15, 0.28, 0.26, 0.24, 0.0, 0.44, 0.07],
+        ],
+    ),
+    (
+        "Heighway Dragon",
+        &[
+            [0.5, -0.5, 0.5, 0.5, 0.0, 0.0, 0.5],
+            [-0.5, -0.5, 0.5, -0.5, 1.0, 0.0, 0.5],
+        ],
+    ),
+    (
+        "Maple Leaf",
+        &[
+            [0.14, 0.01, 0.0, 0.51, -0.08, -1.31, 0.1],
+            [0.43, 0.52, -0.45, 0.5, 1.49, -0.75, 0.35],
+            [0.45, -0.49, 0.47, 0.47, -1.62, -0.74, 0.35],
+            [0.49, 0.0, 0.0, 0.51, 0.02, 1.62, 0.2],
+        ],
+    ),
+    (
+        "Sierpinski Triangle",
+        &[
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0 / 3.0],
+            [0.5, 0.0, 0.0, 0.5, 0.5, 0.0, 1.0 / 3.0],
+            [0.5, 0.0, 0.0, 0.5, 0.25, 0.433, 1.0 / 3.0],
+        ],
+    ),
+];
+
+// The most maps a system can have
+const MAX_MAPS: usize = 12;
+
+// How close (in pixels) the mouse has to be to grab a corner
+const HANDLE_RADIUS: f32 = 8.0;
+
+// Everything the rendered attractor is made from: the maps, the frame, the point count, the
+// seed, the coloring, the brightness, the color and the size of the image
+type ImageInput = (
+    Vec<AffineMap>,
+    [f64; 4],
+    usize,
+    u64,
+    bool,
+    f32,
+    Color32,
+    Vec2,
+);
+
+// Where the plane is drawn on the screen, with y going up
+pub(super) struct View {
+    pub(super) center: [f64; 2], // The point of the plane in the middle of the screen
+    pub(super) scale: f64,       // Pixels per unit
+    pub(super) screen_center: Pos2,
+}
+
+impl View {
+    pub(super) fn to_screen(&self, p: [f64; 2]) -> Pos2 {
+        self.screen_center
+            + vec2(
+                ((p[0] - self.center[0]) * self.scale) as f32,
+                ((self.center[1] - p[1]) * self.scale) as f32,
+            )
+    }
+
+    pub(super) fn to_plane(&self, p: Pos2) -> [f64; 2] {
+        let offset = p - self.screen_center;
+        [
+            self.center[0] + offset.x as f64 / self.scale,
+            self.center[1] - offset.y as f64 / self.scale,
+        ]
+    }
+}
+
+impl Default for Ifs {
+    fn default() -> Self {
+        let mut ifs = Self {
+            maps: Vec::new(),
+            frame: [-1.0, -1.0, 1.0, 1.0],
+            selected: 0,
+            dragged: None,
+            point_count: 500_000,
+            seed: 0,
+            color_by_map: false,
+            brightness: 1.0,
+            show_maps: true,
+            color: Color32::from_rgb(0, 200, 83),
+            texture: None,
+            last_input: None,
+        };
+        ifs.load_preset(PRESETS[0].1);
+        ifs
+    }
+}
+
+impl epi::App for Ifs {
+    fn name(&self) -> &str {
+        "IFS Designer"
+    }
+
+    // Called every frame
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui, frame));
+    }
+}
+
+impl Ifs {
+    // Paints the fractal
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter, frame);
+
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        let settings = Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+
+        // After the settings, so that their sliders get dragged instead of the maps
+        if self.show_maps {
+            self.edit_maps(ui, painter.clip_rect(), settings.response.rect);
+        }
+    }
+
+    // An options window for the maps and the rendering
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let preset = PRESETS
+            .iter()
+            .find(|(_, maps)| {
+                maps.iter()
+                    .map(|&m| AffineMap::new(m))
+                    .eq(self.maps.clone())
+            })
+            .map_or("Custom", |(name, _)| name);
+        ComboBox::from_label("Preset")
+            .selected_text(preset)
+            .show_ui(ui, |ui| {
+                for (name, maps) in PRESETS.iter() {
+                    if ui.selectable_label(preset == *name, *name).clicked() {
+                        self.load_preset(maps);
+                    }
+                }
+            });
+
+        ui.add(
+            Slider::new(&mut self.point_count, 10_000..=5_000_000)
+                .logarithmic(true)
+                .text("Points"),
+        );
+        super::seed_ui(ui, &mut self.seed);
+        ui.add(Slider::new(&mut self.brightness, 0.2..=3.0).text("Brightness"));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.color_by_map, "Color by Map");
+            if !self.color_by_map {
+                ui.color_edit_button_srgba(&mut self.color);
+            }
+        });
+        ui.checkbox(&mut self.show_maps, "Show Maps (drag to edit)");
+        if ui.button("Fit View").clicked() {
+            self.fit_frame();
+        }
+
+        ui.separator();
+        self.maps_ui(ui);
+        // The texture is kept out of the reset, so the next paint frees it when it draws the default
+        // attractor
+        let texture = self.texture.take();
+        egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    // A list of the maps, with the coefficients of the selected one
+    fn maps_ui(&mut self, ui: &mut Ui) {
+        let mut removed = None;
+        for i in 0..self.maps.len() {
+            ui.horizontal(|ui| {
+                ui.colored_label(map_color(i, self.maps.len()), "■");
+                ui.radio_value(&mut self.selected, i, format!("Map {}", i + 1));
+                ui.add(
+                    DragValue::new(&mut self.maps[i].probability)
+                        .clamp_range(0.0..=1.0)
+                        .speed(0.005)
+                        .prefix("p: "),
+                );
+                if self.maps.len() > 1 && ui.small_button("✖").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            self.maps.remove(i);
+        }
+        self.selected = self.selected.min(self.maps.len() - 1);
+
+        let map = &mut self.maps[self.selected];
+        map.coefficients_ui(ui);
+        ui.label(format!("Area scale: {:.4}", map.determinant().abs()));
+
+        ui.horizontal(|ui| {
+            if self.maps.len() < MAX_MAPS && ui.button("Add Map").clicked() {
+                // a half size copy of the frame in its middle
+                let [x0, y0, x1, y1] = self.frame;
+                let center = [(x0 + x1) / 2.0, (y0 + y1) / 2.0];
+                self.maps.push(AffineMap::new([
+                    0.5,
+                    0.0,
+                    0.0,
+                    0.5,
+                    center[0] / 2.0,
+                    center[1] / 2.0,
+                    0.25,
+                ]));
+                self.selected = self.maps.len() - 1;
+            }
+            // small maps are picked less often, so that every part gets about as many points
+            if ui.button("Probabilities by Area").clicked() {
+                let areas: Vec<f64> = self
+                    .maps
+                    .iter()
+                    .map(|m| m.determinant().abs().max(0.01))
+                    .collect();
+                let total: f64 = areas.iter().sum();
+                for (map, area) in self.maps.iter_mut().zip(areas) {
+                    map.probability = area / total;
+                }
+            }
+        });
+    }
+
+    // Replaces the maps with a preset and fits the view around its attractor
+    fn load_preset(&mut self, maps: &[[f64; 7]]) {
+        self.maps = maps.iter().map(|&m| AffineMap::new(m)).collect();
+        self.selected = 0;
+        self.fit_frame();
+    }
+
+    // Picks the next map with the maps' probabilities, or evenly if they are all 0
+    fn pick_map(&self, total: f64, rng: &mut StdRng) -> usize {
+        if total <= 0.0 {
+            return rng.gen_range(0..self.maps.len());
+        }
+        let mut target = rng.gen::<f64>() * total;
+        for (i, map) in self.maps.iter().enumerate() {
+            target -= map.probability;
+            if target < 0.0 {
+                return i;
+            }
+        }
+        self.maps.len() - 1
+    }
+
+    // Plays the chaos game, calling plot for every point after the first ones, which are still
+    // on their way to the attractor
+    fn chaos_game(&self, point_count: usize, mut plot: impl FnMut([f64; 2], usize)) {
+        let total: f64 = self.maps.iter().map(|m| m.probability.max(0.0)).sum();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut point = [0.0, 0.0];
+        for i in 0..point_count + 20 {
+            let map = self.pick_map(total, &mut rng);
+            point = self.maps[map].apply(point);
+            if !point[0].is_finite() || !point[1].is_finite() {
+                // maps that grow send the point away, start again
+                point = [0.0, 0.0];
+            } else if i >= 20 {
+                plot(point, map);
+            }
+        }
+    }
+
+    // Makes the frame a rectangle around the attractor with a small margin
+    fn fit_frame(&mut self) {
+        let mut bounds = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+        self.chaos_game(20_000, |p, _| {
+            bounds = [
+                bounds[0].min(p[0]),
+                bounds[1].min(p[1]),
+                bounds[2].max(p[0]),
+                bounds[3].max(p[1]),
+            ];
+        });
+        let size = (bounds[2] - bounds[0]).max(bounds[3] - bounds[1]);
+        if !(size.is_finite() && size > 1e-9 && size < 1e9) {
+            self.frame = [-1.0, -1.0, 1.0, 1.0];
+            return;
+        }
+        // thin attractors still get a frame that can be seen
+        let margin = 0.05 * size;
+        let [mut x0, mut y0, mut x1, mut y1] = bounds;
+        if x1 - x0 < 0.1 * size {
+            let middle = (x0 + x1) / 2.0;
+            (x0, x1) = (middle - 0.05 * size, middle + 0.05 * size);
+        }
+        if y1 - y0 < 0.1 * size {
+            let middle = (y0 + y1) / 2.0;
+            (y0, y1) = (middle - 0.05 * size, middle + 0.05 * size);
+        }
+        self.frame = [x0 - margin, y0 - margin, x1 + margin, y1 + margin];
+    }
+
+    // Fits the frame on the screen
+    fn view(&self, rect: Rect) -> View {
+        let [x0, y0, x1, y1] = self.frame;
+        let scale = 0.9 * (rect.width() as f64 / (x1 - x0)).min(rect.height() as f64 / (y1 - y0));
+        View {
+            center: [(x0 + x1) / 2.0, (y0 + y1) / 2.0],
+            scale,
+            screen_center: rect.center(),
+        }
+    }
+
+    // Renders the attractor by counting the points of the chaos game on every pixel
+    fn attractor_image(&self, rect: Rect, background: Color32) -> Image {
+        let (width, height) = (rect.width() as usize, rect.height() as usize);
+        let view = self.view(rect);
+        let colors: Vec<[f32; 3]> = (0..self.maps.len())
+            .map(|i| {
+                let color = map_color(i, self.maps.len());
+                [color.r() as f32, color.g() as f32, color.b() as f32]
+            })
+            .collect();
+
+        // the number of points and the sum of their colors on every pixel
+        let mut counts = vec![0_u32; width * height];
+        let mut sums = vec![[0.0_f32; 3]; if self.color_by_map { width * height } else { 0 }];
+        self.chaos_game(self.point_count, |p, map| {
+            let pixel = view.to_screen(p) - rect.min;
+            if pixel.x < 0.0 || pixel.y < 0.0 {
+                return;
+            }
+            let (x, y) = (pixel.x as usize, pixel.y as usize);
+            if x < width && y < height {
+                counts[y * width + x] += 1;
+                if self.color_by_map {
+                    let sum = &mut sums[y * width + x];
+                    for channel in 0..3 {
+                        sum[channel] += colors[map][channel];
+                    }
+                }
+            }
+        });
+
+        let mut image = Image::new(width, height, background);
+        let max = counts.iter().copied().max().unwrap_or(0) as f32;
+        if max == 0.0 {
+            return image;
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let level = ((count as f32).ln_1p() / max.ln_1p()).powf(1.0 / self.brightness);
+            let [r, g, b] = if self.color_by_map {
+                sums[i].map(|channel| channel / count as f32)
+            } else {
+                [self.color.r(), self.color.g(), self.color.b()].map(|channel| channel as f32)
+            };
+            let shade = |channel: f32| (channel * level).round() as u8;
+            let color =
+                Color32::from_rgba_premultiplied(shade(r), shade(g), shade(b), shade(255.0));
+            image.blend((i % width) as i64, (i / width) as i64, color, 1.0);
+        }
+        image
+    }
+
+    // The parallelograms of the maps, with handles on the corners that can be dragged
+    fn map_shapes(&self, rect: Rect) -> Vec<Shape> {
+        let view = self.view(rect);
+        let mut shapes = Vec::new();
+        let [x0, y0, x1, y1] = self.frame;
+        let frame = [[x0, y0], [x1, y0], [x1, y1], [x0, y1]].map(|p| view.to_screen(p));
+        shapes.push(Shape::closed_line(
+            frame.to_vec(),
+            Stroke::new(1.0, Color32::from_gray(90)),
+        ));
+
+        for (i, map) in self.maps.iter().enumerate() {
+            let color = map_color(i, self.maps.len());
+            let width = if i == self.selected { 2.5 } else { 1.0 };
+            let corners = map.corners(self.frame).map(|p| view.to_screen(p));
+            shapes.push(Shape::closed_line(
+                corners.to_vec(),
+                Stroke::new(width, color),
+            ));
+            // the first edge is thicker so it can be seen when the map flips or turns
+            shapes.push(Shape::line_segment(
+                [corners[0], corners[1]],
+                Stroke::new(width + 1.5, color),
+            ));
+            for &corner in &[corners[0], corners[1], corners[3]] {
+                shapes.push(Shape::circle_filled(corner, 4.0, color));
+            }
+        }
+        shapes
+    }
+
+    // The part of a map's parallelogram under a point of the screen. The selected map is found
+    // first, and corners are found before insides.
+    fn handle_at(&self, rect: Rect, point: Pos2) -> Option<(usize, Handle)> {
+        let view = self.view(rect);
+        let mut order: Vec<usize> = (0..self.maps.len()).collect();
+        order.sort_by_key(|&i| i != self.selected);
+
+        for &i in &order {
+            let corners = self.maps[i].corners(self.frame).map(|p| view.to_screen(p));
+            // the corners that set the map are tried from last to first, so that a flat map
+            // whose corners are in the same place can still be pulled open
+            for &corner in &[3, 1, 0] {
+                if corners[corner].distance(point) <= HANDLE_RADIUS {
+                    return Some((i, Handle::Corner(corner)));
+                }
+            }
+        }
+        order
+            .into_iter()
+            .find(|&i| {
+                let corners = self.maps[i].corners(self.frame).map(|p| view.to_screen(p));
+                raster::point_in_polygon(point, &corners)
+            })
+            .map(|i| (i, Handle::Inside))
+    }
+
+    // Dragging a corner of a parallelogram moves that corner and dragging inside it moves the
+    // whole map. Clicking on a map selects it.
+    fn edit_maps(&mut self, ui: &mut Ui, rect: Rect, settings: Rect) {
+        let response = ui.interact(rect, ui.id().with("ifs_maps"), Sense::click_and_drag());
+        let pointer = ui.input().pointer.interact_pos();
+        let outside_settings = pointer.is_some_and(|p| !settings.contains(p));
+
+        if response.clicked() && outside_settings {
+            if let Some((i, _)) = pointer.and_then(|p| self.handle_at(rect, p)) {
+                self.selected = i;
+            }
+        }
+        if response.drag_started() && outside_settings {
+            let origin = ui.input().pointer.press_origin();
+            self.dragged = origin.and_then(|p| self.handle_at(rect, p));
+            if let Some((i, _)) = self.dragged {
+                self.selected = i;
+            }
+        }
+        if !response.dragged() {
+            self.dragged = None;
+        }
+
+        let (i, handle, pointer) = match (self.dragged, pointer) {
+            (Some((i, handle)), Some(pointer)) => (i, handle, pointer),
+            _ => return,
+        };
+        let view = self.view(rect);
+        let map = self.maps[i];
+        match handle {
+            Handle::Corner(corner) => {
+                let mut corners = map.corners(self.frame);
+                corners[corner] = view.to_plane(pointer);
+                self.maps[i] = AffineMap::from_corners(
+                    self.frame,
+                    [corners[0], corners[1], corners[3]],
+                    map.probability,
+                );
+            }
+            Handle::Inside => {
+                let delta = response.drag_delta();
+                self.maps[i].e += delta.x as f64 / view.scale;
+                self.maps[i].f -= delta.y as f64 / view.scale;
+            }
+        }
+    }
+
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
+        let rect = painter.clip_rect();
+        let size = rect.size().round();
+        let input = (
+            self.maps.clone(),
+            self.frame,
+            self.point_count,
+            self.seed,
+            self.color_by_map,
+            self.brightness,
+            self.color,
+            size,
+        );
+
+        // Need to recalculate due to a change in one of the settings
+        if self.last_input.as_ref() != Some(&input) {
+            let image =
+                self.attractor_image(Rect::from_min_size(rect.min, size), Color32::TRANSPARENT);
+            if let Some((texture, _)) = self.texture.take() {
+                frame.tex_allocator().free(texture);
+            }
+            let texture = frame
+                .tex_allocator()
+                .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+            self.texture = Some((texture, size));
+            self.last_input = Some(input);
+        }
+
+        if let Some((texture, size)) = self.texture {
+            let mut mesh = epaint::Mesh::with_texture(texture);
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+        if self.show_maps {
+            painter.extend(self.map_shapes(rect));
+        }
+    }
+}
+
+// The color of the i-th of n maps
+fn map_color(i: usize, n: usize) -> Color32 {
+    let (r, g, b) = palette::hsv_to_rgb(i as f32 / n as f32, 0.7, 1.0);
+    Color32::from_rgb(r, g, b)
+}
+
+impl Animate for Ifs {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Points", self.point_count as f32, 10_000.0..=5_000_000.0),
+            Parameter::new("Brightness", self.brightness, 0.2..=3.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Points" => self.point_count = value.round() as usize,
+            "Brightness" => self.brightness = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for Ifs {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        self.attractor_image(rect, Color32::BLACK)
+    }
+}
+
+impl FractalApp for Ifs {
+    // Known when every map is a rotation and a scaling, possibly mirrored, as the similarity
+    // dimension. This is only the dimension if the copies don't overlap.
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        let similar = |m: &AffineMap| {
+            let size = m.a.abs() + m.b.abs() + m.c.abs() + m.d.abs();
+            let rotation = (m.a - m.d).abs() + (m.b + m.c).abs();
+            let mirrored = (m.a + m.d).abs() + (m.b - m.c).abs();
+            rotation.min(mirrored) <= 1e-6 * size
+        };
+        if self.maps.is_empty() || !self.maps.iter().all(similar) {
+            return None;
+        }
+        let ratios: Vec<f64> = self
+            .maps
+            .iter()
+            .map(|m| (m.a * m.d - m.b * m.c).abs().sqrt())
+            .collect();
+        Some(KnownDimension {
+            formula: String::from("similarity dimension"),
+            value: dimension::similarity_dimension(&ratios).min(2.0),
+            counted: Counted::Drawn,
+        })
+    }
+}
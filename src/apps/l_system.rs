@@ -0,0 +1,277 @@
+use super::FractalApp;
+use crate::lsystem::{Grammar, Module, Segment, Turtle, PRESETS};
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the app for drawing any L-system with turtle graphics.
+The grammar is typed in (or picked from the presets) and rewritten with the engine in lsystem.rs,
+then the turtle's lines are scaled to fit the screen.
+Fractal Explanation: https://en.wikipedia.org/wiki/L-system
+*/
+
+pub struct LSystem {
+    preset: usize,                              // The index of the last chosen preset
+    axiom: String,                              // The starting word
+    rules: String,                              // The production rules, one per line
+    iterations: usize,                          // How many times the word is rewritten
+    angle: f32,                                 // The angle (in degrees) the turtle turns by
+    heading: f32,                               // The starting direction (in degrees)
+    step: f32,                                  // How far F moves when it has no parameter
+    fit: bool,                                  // If true, the curve is scaled to fill the screen
+    line_width: f32,                            // The width of the lines before any !
+    width_factor: f32,                          // How much thinner each ! makes the lines
+    gradient: bool,                             // If true, the color changes along the path
+    color: Color32,                             // The color of the lines without a gradient
+    seed: u64,                                  // The seed that picks the stochastic rules
+    last_grammar: (String, String, usize, u64), // The input of the cached word
+    word: Vec<Module>,                          // The cached word after rewriting
+    done_iterations: usize,                     // The rewrites that fit under MAX_WORD_LENGTH
+    error: Option<String>,                      // The error from parsing the grammar, if any
+    segment_count: usize,                       // The number of lines in the last drawn curve
+}
+
+// The longest word that will be generated, so that many iterations can't freeze the app
+const MAX_WORD_LENGTH: usize = 2_000_000;
+
+impl Default for LSystem {
+    fn default() -> Self {
+        let preset = &PRESETS[0];
+        Self {
+            preset: 0,
+            axiom: preset.axiom.to_owned(),
+            rules: preset.rules.to_owned(),
+            iterations: preset.iterations,
+            angle: preset.angle,
+            heading: preset.heading,
+            step: 10.0,
+            fit: true,
+            line_width: 1.0,
+            width_factor: 0.7,
+            gradient: true,
+            color: Color32::GREEN,
+            seed: 0,
+            last_grammar: (String::new(), String::new(), 0, 0),
+            word: Vec::new(),
+            done_iterations: 0,
+            error: None,
+            segment_count: 0,
+        }
+    }
+}
+
+impl epi::App for LSystem {
+    fn name(&self) -> &str {
+        "L-System"
+    }
+
+    fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui));
+    }
+}
+
+impl LSystem {
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter);
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(300.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let mut chosen = None;
+        ComboBox::from_label("Preset")
+            .selected_text(PRESETS[self.preset].name)
+            .show_ui(ui, |ui| {
+                for (i, preset) in PRESETS.iter().enumerate() {
+                    if ui.selectable_label(self.preset == i, preset.name).clicked() {
+                        chosen = Some(i);
+                    }
+                }
+            });
+        if let Some(i) = chosen {
+            self.load_preset(i);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Axiom");
+            ui.text_edit_singleline(&mut self.axiom);
+        });
+        ui.label("Rules");
+        ui.code_editor(&mut self.rules);
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        ui.add(Slider::new(&mut self.iterations, 0..=16).text("Iterations"));
+        if self.done_iterations < self.iterations {
+            ui.label(format!(
+                "Stopped after {} iterations, the word got too long",
+                self.done_iterations
+            ));
+        }
+        ui.add(Slider::new(&mut self.angle, 0.0..=180.0).text("Angle"));
+        ui.add(Slider::new(&mut self.heading, -180.0..=180.0).text("Heading"));
+        // the step only sets the size of the curve when it isn't scaled to fit
+        ui.checkbox(&mut self.fit, "Fit to screen");
+        ui.add_enabled(
+            !self.fit,
+            Slider::new(&mut self.step, 1.0..=100.0).text("Step"),
+        );
+        ui.add(Slider::new(&mut self.line_width, 0.5..=5.0).text("Line width"));
+        ui.add(Slider::new(&mut self.width_factor, 0.3..=1.0).text("Width factor"));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.gradient, "Gradient");
+            if !self.gradient {
+                ui.color_edit_button_srgba(&mut self.color);
+            }
+        });
+        super::seed_ui(ui, &mut self.seed);
+        ui.label(format!(
+            "Symbols: {}, Lines: {}",
+            self.word.len(),
+            self.segment_count
+        ));
+        egui::reset_button(ui, self);
+    }
+
+    fn load_preset(&mut self, index: usize) {
+        let preset = &PRESETS[index];
+        self.preset = index;
+        self.axiom = preset.axiom.to_owned();
+        self.rules = preset.rules.to_owned();
+        self.iterations = preset.iterations;
+        self.angle = preset.angle;
+        self.heading = preset.heading;
+    }
+
+    // Rewrites the grammar again if it changed since the last frame
+    fn update_word(&mut self) {
+        let grammar = (
+            self.axiom.clone(),
+            self.rules.clone(),
+            self.iterations,
+            self.seed,
+        );
+        if grammar == self.last_grammar {
+            return;
+        }
+
+        match Grammar::parse(&self.axiom, &self.rules) {
+            Ok(parsed) => {
+                let (word, done) = parsed.generate(self.iterations, self.seed, MAX_WORD_LENGTH);
+                self.word = word;
+                self.done_iterations = done;
+                self.error = None;
+            }
+            // keep showing the last curve that worked
+            Err(error) => self.error = Some(error),
+        }
+        self.last_grammar = grammar;
+    }
+
+    // Generates the lines of the curve centered in the rectangle, and scaled to fit it if fit is on
+    fn curve_shapes(&mut self, rect: Rect) -> Vec<Shape> {
+        self.update_word();
+
+        let turtle = Turtle {
+            angle: self.angle,
+            step: self.step,
+            heading: self.heading,
+            width_factor: self.width_factor,
+        };
+        let segments: Vec<Segment> = turtle.walk(&self.word);
+        self.segment_count = segments.len();
+        if segments.is_empty() {
+            return Vec::new();
+        }
+
+        let mut bounds = Rect::NOTHING;
+        for segment in &segments {
+            bounds.extend_with(segment.start);
+            bounds.extend_with(segment.end);
+        }
+        let scale = if self.fit {
+            0.9 * (rect.width() / bounds.width().max(1e-6))
+                .min(rect.height() / bounds.height().max(1e-6))
+        } else {
+            1.0
+        };
+        let to_screen = |p: Pos2| rect.center() + (p - bounds.center()) * scale;
+
+        let count = segments.len() as f32;
+        segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                let color = if self.gradient {
+                    let (r, g, b) = palette::hsv_to_rgb(0.8 * i as f32 / count, 0.8, 1.0);
+                    Color32::from_rgb(r, g, b)
+                } else {
+                    self.color
+                };
+                Shape::line_segment(
+                    [to_screen(segment.start), to_screen(segment.end)],
+                    (self.line_width * segment.width, color),
+                )
+            })
+            .collect()
+    }
+
+    fn paint(&mut self, painter: &Painter) {
+        let shapes = self.curve_shapes(painter.clip_rect());
+        painter.extend(shapes);
+    }
+}
+
+impl Animate for LSystem {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Iterations", self.iterations as f32, 0.0..=16.0),
+            Parameter::new("Angle", self.angle, 0.0..=180.0),
+            Parameter::new("Heading", self.heading, -180.0..=180.0),
+            Parameter::new("Step", self.step, 1.0..=100.0),
+            Parameter::new("Line width", self.line_width, 0.5..=5.0),
+            Parameter::new("Width factor", self.width_factor, 0.3..=1.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Iterations" => self.iterations = value.round() as usize,
+            "Angle" => self.angle = value,
+            "Heading" => self.heading = value,
+            "Step" => self.step = value,
+            "Line width" => self.line_width = value,
+            "Width factor" => self.width_factor = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for LSystem {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        Image::from_shapes(width, height, Color32::BLACK, &self.curve_shapes(rect))
+    }
+}
+
+impl FractalApp for LSystem {}
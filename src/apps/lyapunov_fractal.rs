@@ -0,0 +1,367 @@
+use super::bifurcation::UnimodalMap;
+use super::ifs::View;
+use super::FractalApp;
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+use std::ops::Range;
+
+#[derive(PartialEq)]
+
+/*
+This file contains the Lyapunov fractal (also called the Markus-Lyapunov fractal). The logistic
+map x -> r x (1 - x) is iterated with r switching between two values a and b, in the order given
+by a sequence of As and Bs that repeats. Every pixel is one pair (a, b), and is colored by the
+Lyapunov exponent of its orbit: pixels where the orbit settles on a cycle (negative exponents)
+get the escape-time colors of the Mandelbrot set, with the exponent in place of the iterations,
+and the chaotic ones (positive exponents) are black.
+The pixels are filled in a few rows at a time, so the app stays responsive while it renders.
+Fractal Explanation: https://en.wikipedia.org/wiki/Lyapunov_fractal
+Source: https://www.scientificamerican.com/article/mathematical-recreations-1991-09/
+*/
+
+pub struct LyapunovFractal {
+    sequence: String,                   // The order of a and b, like AABAB
+    error: Option<String>,              // The error from parsing the sequence, if any
+    center: [f64; 2],                   // The (a, b) in the middle of the screen
+    span: f64,                          // The range of a and b that fits on the screen
+    warmup: usize,                      // The iterations before the exponent is measured
+    iterations: usize,                  // The iterations the exponent is averaged over
+    max_steps: u32,                     // The number of colors the exponents are split into
+    image: Option<Image>,               // The pixels rendered so far
+    rows_done: usize,                   // The number of rows of the image that are rendered
+    last_input: Option<ImageInput>,     // The input of the image
+    texture: Option<(TextureId, Vec2)>, // The rendered image and its size
+}
+
+// Famous sequences with the (a, b) ranges that show them best
+const PRESETS: [(&str, &str, [f64; 4]); 4] = [
+    ("Classic", "AB", [2.0, 2.0, 4.0, 4.0]),
+    ("Zircon Zity", "BBBBBBAAAAAA", [3.4, 2.5, 4.0, 3.4]),
+    ("AABAB", "AABAB", [2.8, 2.8, 4.0, 4.0]),
+    ("BBABAB", "BBABAB", [2.5, 3.4, 3.4, 4.0]),
+];
+
+// The iterations done every frame while the image is rendered
+const ITERATIONS_PER_FRAME: usize = 20_000_000;
+
+// The most negative exponent that gets its own color, the ones below it share the last color
+const MIN_EXPONENT: f64 = -2.0;
+
+// Everything the image is made from: the parsed sequence, the view, the iterations, the colors
+// and the size of the image
+type ImageInput = (Vec<bool>, [f64; 2], f64, usize, usize, u32, Vec2);
+
+impl Default for LyapunovFractal {
+    fn default() -> Self {
+        let mut fractal = Self {
+            sequence: String::new(),
+            error: None,
+            center: [3.0, 3.0],
+            span: 2.0,
+            warmup: 200,
+            iterations: 400,
+            max_steps: 80,
+            image: None,
+            rows_done: 0,
+            last_input: None,
+            texture: None,
+        };
+        fractal.load_preset(&PRESETS[0]);
+        fractal
+    }
+}
+
+impl epi::App for LyapunovFractal {
+    fn name(&self) -> &str {
+        "Lyapunov Fractal"
+    }
+
+    // Called every frame
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui, frame));
+    }
+}
+
+impl LyapunovFractal {
+    // Paints the fractal
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter, frame);
+
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        let settings = Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+
+        // After the settings, so that their sliders get dragged instead of the fractal
+        self.navigate(ui, painter.clip_rect(), settings.response.rect);
+    }
+
+    // An options window for the sequence, the view and the colors
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let preset = PRESETS
+            .iter()
+            .find(|(_, sequence, _)| *sequence == self.sequence)
+            .map_or("Custom", |(name, _, _)| name);
+        ComboBox::from_label("Preset")
+            .selected_text(preset)
+            .show_ui(ui, |ui| {
+                for choice in PRESETS.iter() {
+                    if ui.selectable_label(preset == choice.0, choice.0).clicked() {
+                        self.load_preset(choice);
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Sequence");
+            ui.text_edit_singleline(&mut self.sequence);
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        ui.label(format!(
+            "a: {:.6} to {:.6}",
+            self.center[0] - self.span / 2.0,
+            self.center[0] + self.span / 2.0
+        ));
+        ui.label(format!(
+            "b: {:.6} to {:.6}",
+            self.center[1] - self.span / 2.0,
+            self.center[1] + self.span / 2.0
+        ));
+        ui.add(Slider::new(&mut self.warmup, 0..=2_000).text("Warmup"));
+        ui.add(
+            Slider::new(&mut self.iterations, 10..=5_000)
+                .logarithmic(true)
+                .text("Iterations"),
+        );
+        ui.add(Slider::new(&mut self.max_steps, 25..=255).text("Change Colors"));
+        if let Some(image) = &self.image {
+            if self.rows_done < image.height {
+                ui.label(format!(
+                    "Rendering: {}%",
+                    100 * self.rows_done / image.height.max(1)
+                ));
+            }
+        }
+        // The texture is kept out of the reset, so the next paint frees it when it starts over on
+        // the default fractal
+        let texture = self.texture.take();
+        egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    fn load_preset(&mut self, preset: &(&str, &str, [f64; 4])) {
+        let [a0, b0, a1, b1] = preset.2;
+        self.sequence = preset.1.to_owned();
+        self.center = [(a0 + a1) / 2.0, (b0 + b1) / 2.0];
+        self.span = (a1 - a0).max(b1 - b0);
+    }
+
+    // Turns the sequence into which of a (false) and b (true) is used at every step
+    fn parse_sequence(&self) -> Result<Vec<bool>, String> {
+        let sequence: Vec<bool> = self
+            .sequence
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c.to_ascii_uppercase() {
+                'A' => Ok(false),
+                'B' => Ok(true),
+                other => Err(format!("The sequence can only have A and B, not {}", other)),
+            })
+            .collect::<Result<_, _>>()?;
+        if sequence.is_empty() {
+            return Err("The sequence needs at least one A or B".to_owned());
+        }
+        Ok(sequence)
+    }
+
+    // Dragging moves the fractal and scrolling zooms in and out around the mouse
+    fn navigate(&mut self, ui: &mut Ui, rect: Rect, settings: Rect) {
+        let response = ui.interact(rect, ui.id().with("lyapunov_view"), Sense::drag());
+        let view = self.view(rect);
+
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.center[0] -= delta.x as f64 / view.scale;
+            self.center[1] += delta.y as f64 / view.scale;
+        }
+
+        let scroll = ui.input().scroll_delta.y;
+        let pointer = ui.input().pointer.hover_pos();
+        if response.hovered() && scroll != 0.0 && !pointer.is_some_and(|p| settings.contains(p)) {
+            let factor = (scroll as f64 / 200.0).exp();
+            // keep the point under the mouse in the same place
+            let anchor = pointer.map_or(self.center, |p| view.to_plane(p));
+            self.center = [
+                anchor[0] - (anchor[0] - self.center[0]) / factor,
+                anchor[1] - (anchor[1] - self.center[1]) / factor,
+            ];
+            self.span = (self.span / factor).clamp(1e-12, 8.0);
+        }
+    }
+
+    // Where the fractal is drawn, with a going right and b going up
+    fn view(&self, rect: Rect) -> View {
+        View {
+            center: self.center,
+            scale: rect.width().min(rect.height()) as f64 / self.span,
+            screen_center: rect.center(),
+        }
+    }
+
+    // The Lyapunov exponent of the orbit of (a, b): the average log of how much each step
+    // stretches the gaps between points near the orbit
+    fn exponent(&self, sequence: &[bool], a: f64, b: f64) -> f64 {
+        let map = UnimodalMap::Logistic;
+        let mut x = 0.5;
+        let mut sum = 0.0;
+        for n in 0..self.warmup + self.iterations {
+            let r = if sequence[n % sequence.len()] { b } else { a };
+            if n >= self.warmup {
+                sum += map.derivative(x, r).abs().max(1e-300).ln();
+            }
+            x = map.iterate(x, r);
+        }
+        sum / self.iterations as f64
+    }
+
+    // Negative exponents get the escape-time colors, with the most stable orbits the furthest
+    // along the palette, and chaos (or orbits that escape) is black like the Mandelbrot set
+    fn color(&self, exponent: f64) -> Color32 {
+        let steps = if exponent < 0.0 {
+            let t = (exponent / MIN_EXPONENT).min(1.0);
+            ((t * (self.max_steps - 1) as f64) as u32).min(self.max_steps - 1)
+        } else {
+            self.max_steps
+        };
+        palette::escape_time_color(steps, self.max_steps)
+    }
+
+    // Renders some rows of the image
+    fn render_rows(&self, sequence: &[bool], image: &mut Image, rows: Range<usize>) {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(image.width as f32, image.height as f32));
+        let view = self.view(rect);
+        for y in rows {
+            for x in 0..image.width {
+                let [a, b] = view.to_plane(pos2(x as f32 + 0.5, y as f32 + 0.5));
+                let exponent = self.exponent(sequence, a, b);
+                image.set(x, y, self.color(exponent));
+            }
+        }
+    }
+
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
+        let sequence = match self.parse_sequence() {
+            Ok(sequence) => {
+                self.error = None;
+                sequence
+            }
+            // keep showing the last fractal that worked
+            Err(error) => {
+                self.error = Some(error);
+                self.last_input
+                    .as_ref()
+                    .map_or(vec![false, true], |input| input.0.clone())
+            }
+        };
+
+        let rect = painter.clip_rect();
+        let size = rect.size().round();
+        let input = (
+            sequence.clone(),
+            self.center,
+            self.span,
+            self.warmup,
+            self.iterations,
+            self.max_steps,
+            size,
+        );
+        if self.last_input.as_ref() != Some(&input) {
+            self.image = Some(Image::new(
+                size.x as usize,
+                size.y as usize,
+                Color32::TRANSPARENT,
+            ));
+            self.rows_done = 0;
+            self.last_input = Some(input);
+        }
+
+        // Renders a few more rows every frame until the image is done
+        if let Some(mut image) = self.image.take() {
+            if self.rows_done < image.height {
+                let per_row = image.width * (self.warmup + self.iterations);
+                let rows = (ITERATIONS_PER_FRAME / per_row.max(1)).max(1);
+                let end = (self.rows_done + rows).min(image.height);
+                self.render_rows(&sequence, &mut image, self.rows_done..end);
+                self.rows_done = end;
+                if let Some((texture, _)) = self.texture.take() {
+                    frame.tex_allocator().free(texture);
+                }
+                let texture = frame
+                    .tex_allocator()
+                    .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+                self.texture = Some((texture, size));
+                painter.ctx().request_repaint();
+            }
+            self.image = Some(image);
+        }
+
+        if let Some((texture, size)) = self.texture {
+            let mut mesh = epaint::Mesh::with_texture(texture);
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+    }
+}
+
+impl Animate for LyapunovFractal {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Center A", self.center[0] as f32, 0.0..=4.0),
+            Parameter::new("Center B", self.center[1] as f32, 0.0..=4.0),
+            Parameter::new("Span", self.span as f32, 0.0001..=4.0),
+            Parameter::new("Iterations", self.iterations as f32, 10.0..=5_000.0),
+            Parameter::new("Change Colors", self.max_steps as f32, 25.0..=255.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Center A" => self.center[0] = value as f64,
+            "Center B" => self.center[1] = value as f64,
+            "Span" => self.span = value as f64,
+            "Iterations" => self.iterations = value.round() as usize,
+            "Change Colors" => self.max_steps = value.round() as u32,
+            _ => {}
+        }
+    }
+}
+
+impl Render for LyapunovFractal {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height, Color32::BLACK);
+        if let Ok(sequence) = self.parse_sequence() {
+            self.render_rows(&sequence, &mut image, 0..height);
+        }
+        image
+    }
+}
+
+impl FractalApp for LyapunovFractal {}
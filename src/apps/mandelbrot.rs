@@ -1,6 +1,13 @@
+use super::FractalApp;
+use crate::dimension::{Counted, KnownDimension};
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use crate::zoom_video::ZoomVideoPanel;
 use eframe::{egui, epi};
 use egui::{containers::*, widgets::*, *};
 use num::complex::Complex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 #[derive(PartialEq)]
 
@@ -8,33 +15,83 @@ use num::complex::Complex;
     Generates the Mandelbrot set, which is the set of complex numbers c
     for which the function f(z) = z^2 + c does not diverge to infinity when iterated from z = 0 to infinity.
 
+    The statistics measure the part of the set in view. Its area is estimated by counting the
+    pixels in the set, and by Monte Carlo sampling with a 95% Wilson confidence interval. The
+    boundary length is the length of the pixel edges between the set and the outside, corrected
+    by pi / 4 for their staircase shape; it keeps growing with the resolution, since the boundary
+    has dimension 2. The area of the whole set is about 1.50659 (Förstemann).
+
     Sources:
     https://levelup.gitconnected.com/mandelbrot-set-with-python-983e9fc47f56
     https://mathigon.org/course/fractals/mandelbrot
+    https://en.wikipedia.org/wiki/Binomial_proportion_confidence_interval#Wilson_score_interval
+    https://www.foerstemann.name/labor.html
 */
 
 pub struct Mandelbrot {
-    zoom: f32,          // The level of the recursion when generating the fractal
-    last_zoom: f32,     // The depth of the last drawn fractal
+    zoom: f32,      // The level of the recursion when generating the fractal
+    last_zoom: f32, // The depth of the last drawn fractal
+    center_x: f32,  // The real part of the point in the center of the view
+    last_center_x: f32,
+    center_y: f32, // The imaginary part of the point in the center of the view
+    last_center_y: f32,
     shapes: Vec<Shape>, // A vector containing the shapes that will be painted on the screen,
     max_steps: u8,      // change this for more detail in the fractal
     last_max_steps: u8,
     num_pixels: f32, // change this for a larger set
     last_num_pixels: f32,
-    threshold: f32, // no need to change this
+    threshold: f32,             // no need to change this
+    zoom_video: ZoomVideoPanel, // Settings for rendering a zoom video into the set
+    escape_times: Vec<u8>,      // The escape time of every pixel, column by column
+    samples: usize,             // The number of random points for the Monte Carlo area
+    seed: u64,                  // The seed of the random points
+    log_histogram: bool,        // If true, the histogram's bars have a log scale
+    statistics: Option<Statistics>,
+    last_statistics: Option<StatisticsInput>,
 }
 
+// What is measured about the part of the set in view
+#[derive(PartialEq)]
+struct Statistics {
+    view_area: f64,               // The area of the plane covered by the pixels
+    pixel_area: f64,              // The area of the pixels in the set
+    pixel_range: (f64, f64),      // Without and with the pixels along the boundary
+    sampled_area: f64,            // The Monte Carlo estimate
+    sampled_interval: (f64, f64), // Its 95% confidence interval
+    boundary_length: f64,         // At the resolution of the plot
+    coarse_boundary_length: f64,  // At half the resolution of the plot
+    histogram: Vec<usize>,        // The number of pixels for every escape time
+}
+
+// Everything the statistics depend on: the bounds of the view, the maximum number of steps, the
+// number of pixels, the samples and the seed
+type StatisticsInput = ((f32, f32, f32, f32), u8, f32, usize, u64);
+
+// The area of the whole set (Förstemann, 2012)
+const KNOWN_AREA: f64 = 1.506_591_88;
+
 impl Default for Mandelbrot {
     fn default() -> Self {
         Self {
             zoom: 1.0,
             last_zoom: 0.9,
+            center_x: -0.765,
+            last_center_x: -0.765,
+            center_y: 0.2,
+            last_center_y: 0.2,
             shapes: Vec::new(),
             max_steps: 80,
             last_max_steps: 100,
             num_pixels: 255.0,
             last_num_pixels: 255.0,
             threshold: 4.0,
+            zoom_video: ZoomVideoPanel::default(),
+            escape_times: Vec::new(),
+            samples: 100_000,
+            seed: 0,
+            log_histogram: true,
+            statistics: None,
+            last_statistics: None,
         }
     }
 }
@@ -70,112 +127,289 @@ impl Mandelbrot {
             .show(ui, |ui| {
                 ui.set_max_width(270.0);
                 CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+                CollapsingHeader::new("Zoom Video").show(ui, |ui| {
+                    let (x_min, x_max, y_min, y_max) = self.bounds();
+                    let current_view = (
+                        ((x_min + x_max) / 2.0) as f64,
+                        ((y_min + y_max) / 2.0) as f64,
+                        (x_max - x_min) as f64,
+                    );
+                    self.zoom_video.ui(ui, current_view);
+                });
+                CollapsingHeader::new("Statistics").show(ui, |ui| self.statistics_ui(ui));
             });
+
+        self.zoom_video.update(ui.ctx());
     }
 
     // An options window for setting the zoom of the fractal generation
     fn options_ui(&mut self, ui: &mut Ui) {
         ui.add(Slider::new(&mut self.zoom, 0.0000001..=1.0).text("Zoom"));
+        ui.add(Slider::new(&mut self.center_x, -2.0..=1.0).text("Center X"));
+        ui.add(Slider::new(&mut self.center_y, -1.5..=1.5).text("Center Y"));
         ui.add(Slider::new(&mut self.num_pixels, 100.0..=750.0).text("Size"));
         ui.add(Slider::new(&mut self.max_steps, 25..=255).text("Change Colors"));
         egui::reset_button(ui, self);
     }
 
-    // Paints a rectangular given its center and size
-    fn paint_rect(&mut self, center: Pos2, size: Vec2, color: Color32) {
-        self.shapes.push(Shape::rect_filled(
-            Rect::from_center_size(center, size),
-            0.0,
-            color,
-        ));
+    // Measures the set in view when it or the settings change, and shows the measurements
+    fn statistics_ui(&mut self, ui: &mut Ui) {
+        ui.add(
+            Slider::new(&mut self.samples, 1_000..=1_000_000)
+                .logarithmic(true)
+                .text("Samples"),
+        );
+        super::seed_ui(ui, &mut self.seed);
+        ui.checkbox(&mut self.log_histogram, "Log scale histogram");
+
+        let input = (
+            self.bounds(),
+            self.max_steps,
+            self.num_pixels,
+            self.samples,
+            self.seed,
+        );
+        if self.last_statistics != Some(input) && !self.escape_times.is_empty() {
+            self.statistics = Some(self.measure());
+            self.last_statistics = Some(input);
+        }
+        let statistics = match &self.statistics {
+            Some(statistics) => statistics,
+            None => return,
+        };
+
+        Grid::new("mandelbrot_statistics").show(ui, |ui| {
+            ui.label("View area");
+            ui.label(format!("{:.6}", statistics.view_area));
+            ui.end_row();
+            ui.label("Pixel area");
+            ui.label(format!("{:.6}", statistics.pixel_area));
+            ui.end_row();
+            ui.label("");
+            ui.label(format!(
+                "[{:.6}, {:.6}]",
+                statistics.pixel_range.0, statistics.pixel_range.1
+            ));
+            ui.end_row();
+            ui.label("Monte Carlo area");
+            ui.label(format!("{:.6}", statistics.sampled_area));
+            ui.end_row();
+            ui.label("95% interval");
+            ui.label(format!(
+                "[{:.6}, {:.6}]",
+                statistics.sampled_interval.0, statistics.sampled_interval.1
+            ));
+            ui.end_row();
+            ui.label("Whole set");
+            ui.label(format!("{:.6}", KNOWN_AREA));
+            ui.end_row();
+            ui.label("Boundary length");
+            ui.label(format!("{:.4}", statistics.boundary_length));
+            ui.end_row();
+            ui.label("At half resolution");
+            ui.label(format!("{:.4}", statistics.coarse_boundary_length));
+            ui.end_row();
+        });
+        ui.label("The pixel area's range leaves out or adds the pixels along the boundary");
+
+        self.histogram_ui(ui);
     }
 
-    // Converts a hsv value, where 0 <= h, s, v <= 1
-    // Source: https://stackoverflow.com/questions/17242144/javascript-convert-hsb-hsv-color-to-rgb-accurately
-    fn hsv_to_rgb(&mut self, h: f32, s: f32, v: f32) -> (u8, u8, u8) {
-        let mut r = 0.0;
-        let mut g = 0.0;
-        let mut b = 0.0;
-
-        let i = (h * 6.0).floor();
-        let f = h * 6.0 - i;
-        let p = v * (1.0 - s);
-        let q = v * (1.0 - f * s);
-        let t = v * (1.0 - (1.0 - f) * s);
-
-        match (i as i32) % 6 {
-            0 => {
-                r = v;
-                g = t;
-                b = p;
+    // Draws the number of pixels with each escape time as bars in the colors of the plot, the
+    // pixels in the set are the last (gray) bar
+    fn histogram_ui(&self, ui: &mut Ui) {
+        let statistics = match &self.statistics {
+            Some(statistics) => statistics,
+            None => return,
+        };
+        let histogram = &statistics.histogram;
+        let escaped: usize = histogram[..histogram.len() - 1].iter().sum();
+        let total_steps: usize = histogram[..histogram.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(steps, count)| steps * count)
+            .sum();
+        if escaped > 0 {
+            ui.label(format!(
+                "Mean escape time {:.2} steps",
+                total_steps as f64 / escaped as f64
+            ));
+        }
+
+        let width = ui.available_width().max(100.0);
+        let (response, painter) = ui.allocate_painter(vec2(width, 80.0), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let scale = |count: usize| {
+            if self.log_histogram {
+                (count as f32 + 1.0).ln()
+            } else {
+                count as f32
             }
-            1 => {
-                r = q;
-                g = v;
-                b = p;
+        };
+        let tallest = histogram
+            .iter()
+            .map(|&count| scale(count))
+            .fold(0.0, f32::max);
+        let bar_width = rect.width() / (histogram.len() - 1) as f32;
+        for (steps, &count) in histogram.iter().enumerate().skip(1) {
+            if count == 0 {
+                continue;
             }
-            2 => {
-                r = p;
-                g = v;
-                b = t;
+            let height = scale(count) / tallest.max(1.0) * rect.height();
+            let left = rect.left() + (steps - 1) as f32 * bar_width;
+            let color = if steps == histogram.len() - 1 {
+                Color32::GRAY
+            } else {
+                palette::escape_time_color(steps as u32, self.max_steps as u32)
+            };
+            painter.rect_filled(
+                Rect::from_min_max(
+                    pos2(left, rect.bottom() - height),
+                    pos2(left + bar_width, rect.bottom()),
+                ),
+                0.0,
+                color,
+            );
+        }
+    }
+
+    // Measures the area, boundary and escape times of the set in view from the plotted pixels,
+    // and samples random points of the view for the Monte Carlo area
+    fn measure(&mut self) -> Statistics {
+        let (x_min, x_max, y_min, y_max) = self.bounds();
+        let size = self.num_pixels as usize;
+        let mx = ((x_max - x_min) / (self.num_pixels - 1.0)) as f64;
+        let my = ((y_max - y_min) / (self.num_pixels - 1.0)) as f64;
+        let inside = |x: usize, y: usize| self.escape_times[x * size + y] == self.max_steps;
+
+        let mut histogram = vec![0; self.max_steps as usize + 1];
+        for &steps in &self.escape_times {
+            histogram[steps as usize] += 1;
+        }
+
+        // pixels in the set next to the outside could be outside, and the other way around
+        let neighbors = |x: usize, y: usize| {
+            let mut neighbors = Vec::with_capacity(4);
+            if x > 0 {
+                neighbors.push((x - 1, y));
+            }
+            if x + 1 < size {
+                neighbors.push((x + 1, y));
             }
-            3 => {
-                r = p;
-                g = q;
-                b = v;
+            if y > 0 {
+                neighbors.push((x, y - 1));
             }
-            4 => {
-                r = t;
-                g = p;
-                b = v;
+            if y + 1 < size {
+                neighbors.push((x, y + 1));
             }
-            5 => {
-                r = v;
-                g = p;
-                b = q;
+            neighbors
+        };
+        let (mut inside_count, mut sure_inside, mut maybe_inside) = (0, 0, 0);
+        for x in 0..size {
+            for y in 0..size {
+                let on_boundary = neighbors(x, y)
+                    .iter()
+                    .any(|&(nx, ny)| inside(nx, ny) != inside(x, y));
+                if inside(x, y) {
+                    inside_count += 1;
+                    if !on_boundary {
+                        sure_inside += 1;
+                    }
+                } else if on_boundary {
+                    maybe_inside += 1;
+                }
             }
-            _ => println!("Something went wrong"),
         }
-
-        let r_val = (r * 255.0).ceil();
-        let g_val = (g * 255.0).ceil();
-        let b_val = (b * 255.0).ceil();
-
-        return (r_val as u8, g_val as u8, b_val as u8);
+        let cell = mx * my;
+
+        // the edges between the set and the outside, every `step` pixels
+        let boundary_length = |step: usize| {
+            let mut length = 0.0;
+            for x in (0..size).step_by(step) {
+                for y in (0..size).step_by(step) {
+                    if x + step < size && inside(x, y) != inside(x + step, y) {
+                        length += my * step as f64;
+                    }
+                    if y + step < size && inside(x, y) != inside(x, y + step) {
+                        length += mx * step as f64;
+                    }
+                }
+            }
+            length * std::f64::consts::PI / 4.0
+        };
+        let (fine, coarse) = (boundary_length(1), boundary_length(2));
+
+        // the samples are spread over the same area as the pixels
+        let (width, height) = (mx * size as f64, my * size as f64);
+        let (left, top) = (x_min as f64 - mx / 2.0, y_min as f64 - my / 2.0);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut hits = 0;
+        for _ in 0..self.samples {
+            let c = Complex::new(
+                (left + rng.gen::<f64>() * width) as f32,
+                (top + rng.gen::<f64>() * height) as f32,
+            );
+            if self.get_divergence(c, self.threshold, self.max_steps) == self.max_steps {
+                hits += 1;
+            }
+        }
+        let (low, high) = wilson_interval(hits, self.samples);
+        let view_area = width * height;
+
+        Statistics {
+            view_area,
+            pixel_area: inside_count as f64 * cell,
+            pixel_range: (
+                sure_inside as f64 * cell,
+                (inside_count + maybe_inside) as f64 * cell,
+            ),
+            sampled_area: hits as f64 / self.samples as f64 * view_area,
+            sampled_interval: (low * view_area, high * view_area),
+            boundary_length: fine,
+            coarse_boundary_length: coarse,
+            histogram,
+        }
     }
 
-    // Returns a color for a specific pixel given a distance
-    fn calculate_color(&mut self, i: u8) -> Color32 {
-        let hue = (359.0 * (i as f32)) / (self.max_steps as f32);
-        let saturation = 1.0;
-        let value = if i < self.max_steps { 1.0 } else { 0.0 };
-
-        let color = self.hsv_to_rgb(hue, saturation, value);
-        return Color32::from_rgb(color.0, color.1, color.2);
+    // Paints a rectangular given its center and size
+    fn paint_rect(&mut self, center: Pos2, size: Vec2, color: Color32) {
+        self.shapes.push(Shape::rect_filled(
+            Rect::from_center_size(center, size),
+            0.0,
+            color,
+        ));
     }
 
     // Used to find the divergence of a complex number
     fn get_divergence(&mut self, c: Complex<f32>, threshold: f32, max_steps: u8) -> u8 {
-        let mut z = c.clone();
+        let mut z = c;
         let mut i = 1;
         while i < max_steps && (z * z.conj()).re < threshold {
             z = z * z + c;
             i += 1;
         }
-        return i;
+        i
     }
 
-    // Generates the mandelbrot set
-    fn plot_mandelbrot(&mut self, width_shift: f32, height_shift: f32) {
+    // The x and y bounds that will be used to create complex numbers
+    fn bounds(&self) -> (f32, f32, f32, f32) {
         // Handles the mandelbrot zoom
         let zoom_dif = 1.0 - self.zoom;
 
-        // The x and y bounds that will be used to create complex numbers
-        let x_min = -2.0 + zoom_dif;
-        let x_max = 0.47 - zoom_dif;
+        let x_min = self.center_x - 1.235 + zoom_dif;
+        let x_max = self.center_x + 1.235 - zoom_dif;
 
-        let y_min = -0.92 + zoom_dif;
-        let y_max = 1.32 - zoom_dif;
+        let y_min = self.center_y - 1.12 + zoom_dif;
+        let y_max = self.center_y + 1.12 - zoom_dif;
+
+        (x_min, x_max, y_min, y_max)
+    }
+
+    // Generates the mandelbrot set
+    fn plot_mandelbrot(&mut self, width_shift: f32, height_shift: f32) {
+        let (x_min, x_max, y_min, y_max) = self.bounds();
 
         let mx = (x_max - x_min) / (self.num_pixels - 1.0);
         let my = (y_max - y_min) / (self.num_pixels - 1.0);
@@ -187,12 +421,13 @@ impl Mandelbrot {
 
                 let complex_num = Complex::new(mapped_x, mapped_y);
                 let it = self.get_divergence(complex_num, self.threshold, self.max_steps);
+                self.escape_times.push(it);
 
                 // Applies shift to center the set on the screen
                 let x_coord = (x as f32) + width_shift;
                 let y_coord = (y as f32) + height_shift;
 
-                let color = self.calculate_color(it);
+                let color = palette::escape_time_color(it as u32, self.max_steps as u32);
 
                 // Adds square with a special color
                 self.paint_rect(pos2(x_coord, y_coord), vec2(0.5, 0.5), color);
@@ -204,6 +439,8 @@ impl Mandelbrot {
         let rect = painter.clip_rect();
 
         let need_to_recalculate = self.zoom != self.last_zoom
+            || self.center_x != self.last_center_x
+            || self.center_y != self.last_center_y
             || self.max_steps != self.last_max_steps
             || self.num_pixels != self.last_num_pixels;
 
@@ -214,11 +451,14 @@ impl Mandelbrot {
         // Need to recalculate due to a change in one of the settings
         if need_to_recalculate {
             self.shapes.clear();
+            self.escape_times.clear();
             self.plot_mandelbrot(width_shift, height_shift);
         }
 
         // Update the the markers in order to prevent further recalculations
         self.last_zoom = self.zoom;
+        self.last_center_x = self.center_x;
+        self.last_center_y = self.center_y;
         self.last_max_steps = self.max_steps;
         self.last_num_pixels = self.num_pixels;
 
@@ -229,3 +469,103 @@ impl Mandelbrot {
         painter.extend(cloned_shapes);
     }
 }
+
+// The 95% Wilson score interval for the fraction of samples that hit, which stays between 0 and 1
+// even when almost none or almost all of them hit
+fn wilson_interval(hits: usize, samples: usize) -> (f64, f64) {
+    let z: f64 = 1.96;
+    let n = samples as f64;
+    let p = hits as f64 / n;
+    let center = (p + z * z / (2.0 * n)) / (1.0 + z * z / n);
+    let spread = z / (1.0 + z * z / n) * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+    ((center - spread).max(0.0), (center + spread).min(1.0))
+}
+
+impl Render for Mandelbrot {
+    // Samples the set directly at the image's resolution, fitted into a centered square
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let mut image = Image::new(width, height, Color32::BLACK);
+        let size = width.min(height);
+        let (x_min, x_max, y_min, y_max) = self.bounds();
+
+        let mx = (x_max - x_min) / (size as f32 - 1.0).max(1.0);
+        let my = (y_max - y_min) / (size as f32 - 1.0).max(1.0);
+        let x_offset = (width - size) / 2;
+        let y_offset = (height - size) / 2;
+
+        for x in 0..size {
+            for y in 0..size {
+                let complex_num = Complex::new(mx * (x as f32) + x_min, my * (y as f32) + y_min);
+                let it = self.get_divergence(complex_num, self.threshold, self.max_steps);
+                let color = palette::escape_time_color(it as u32, self.max_steps as u32);
+                image.set(x + x_offset, y + y_offset, color);
+            }
+        }
+
+        image
+    }
+}
+
+impl Animate for Mandelbrot {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Zoom", self.zoom, 0.0000001..=1.0),
+            Parameter::new("Center X", self.center_x, -2.0..=1.0),
+            Parameter::new("Center Y", self.center_y, -1.5..=1.5),
+            Parameter::new("Size", self.num_pixels, 100.0..=750.0),
+            Parameter::new("Change Colors", self.max_steps as f32, 25.0..=255.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Zoom" => self.zoom = value,
+            "Center X" => self.center_x = value,
+            "Center Y" => self.center_y = value,
+            "Size" => self.num_pixels = value.round(),
+            "Change Colors" => self.max_steps = value.round() as u8,
+            _ => {}
+        }
+    }
+}
+
+impl FractalApp for Mandelbrot {
+    // The boundary of the set is as rough as it can be for a curve in the plane (Shishikura)
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        Some(KnownDimension {
+            formula: String::from("boundary (Shishikura)"),
+            value: 2.0,
+            counted: Counted::Boundary,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_interval(hits: usize, samples: usize, expected: (f64, f64)) {
+        let (low, high) = wilson_interval(hits, samples);
+        assert!((low - expected.0).abs() < 1e-4, "{} != {}", low, expected.0);
+        assert!(
+            (high - expected.1).abs() < 1e-4,
+            "{} != {}",
+            high,
+            expected.1
+        );
+    }
+
+    #[test]
+    fn wilson_intervals() {
+        assert_interval(5, 10, (0.2366, 0.7634));
+        assert_interval(81, 263, (0.2553, 0.3662));
+
+        // no hits or only hits still give an interval of the same width, inside 0 to 1
+        assert_interval(0, 10, (0.0, 0.2775));
+        assert_interval(10, 10, (0.7225, 1.0));
+
+        // more samples make the interval narrower around the fraction
+        let (low, high) = wilson_interval(5000, 10000);
+        assert!(low < 0.5 && high > 0.5 && high - low < 0.02);
+    }
+}
@@ -1,13 +1,65 @@
+mod bifurcation;
 mod fibonacci_word;
+mod fractal_flame;
+mod fractal_solid;
+mod fractal_tree;
 mod h_tree;
+mod ifs;
+mod l_system;
+mod lyapunov_fractal;
 mod mandelbrot;
+mod morphic_word;
 mod sierpinski;
+mod sierpinski_triangle;
+mod strange_attractor;
 
 /*
 Using Rust's module system to split up the fractal code into individual files.
 */
 
+pub use bifurcation::BifurcationDiagram;
 pub use fibonacci_word::FibonacciWord;
+pub use fractal_flame::FractalFlame;
+pub use fractal_solid::FractalSolid;
+pub use fractal_tree::FractalTree;
 pub use h_tree::HTree;
+pub use ifs::Ifs;
+pub use l_system::LSystem;
+pub use lyapunov_fractal::LyapunovFractal;
 pub use mandelbrot::Mandelbrot;
+pub use morphic_word::MorphicWord;
 pub use sierpinski::SierpinskiCarpet;
+pub use sierpinski_triangle::SierpinskiTriangle;
+pub use strange_attractor::StrangeAttractor;
+
+use crate::dimension::KnownDimension;
+use crate::raster::Render;
+use crate::solid::Solid;
+use crate::timeline::Animate;
+use eframe::{egui, epi};
+
+// An app that can be selected in the navbar, driven by the timeline and exported. The apps only
+// implement the methods for what their fractal supports.
+pub trait FractalApp: epi::App + Animate + Render {
+    // The solid for the current settings, e.g. to 3D print it, or None for fractals that are only
+    // drawn. Flat fractals are made as thick as the given fraction of their size.
+    fn model(&mut self, _thickness: f32) -> Option<Solid> {
+        None
+    }
+
+    // The dimension worked out from the math of the fractal, if it is known for the settings
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        None
+    }
+}
+
+// A seed for an app's random generator, with a button that picks a new one. The same seed
+// always gives the same fractal, so it can be shared or exported again.
+pub(crate) fn seed_ui(ui: &mut egui::Ui, seed: &mut u64) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(seed).prefix("Seed: "));
+        if ui.button("Reroll").clicked() {
+            *seed = rand::random::<u32>() as u64;
+        }
+    });
+}
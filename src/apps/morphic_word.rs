@@ -0,0 +1,523 @@
+use super::FractalApp;
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use crate::words::Morphism;
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the app for drawing the words made by any substitution morphism.
+The Fibonacci word comes from 0 -> 01, 1 -> 0, and other morphisms give the Tribonacci, Thue-Morse,
+period-doubling and paperfolding words. The word can be drawn in two ways:
+- As a turtle curve, where every letter moves forward, turns, or both.
+- As a Rauzy projection, where every letter is a step along its own axis and the path is projected
+  onto the plane that the substitution shrinks. For the Tribonacci word the points fill the
+  Rauzy fractal.
+Fractal Explanation: https://en.wikipedia.org/wiki/Rauzy_fractal
+Source: https://en.wikipedia.org/wiki/Morphic_word
+*/
+
+pub struct MorphicWord {
+    preset: usize,                       // The index of the last chosen preset
+    rules: String,                       // The morphism, one rule per line
+    start: String,                       // The word the morphism starts from
+    iterations: usize,                   // How many times the morphism is applied
+    mode: DrawMode,                      // How the word is drawn
+    angle: f32,                          // The angle (in degrees) of the turtle's turns
+    actions: Vec<(char, Action)>,        // What the turtle does for every letter
+    line_width: f32,                     // The width of the turtle's lines
+    point_size: f32,                     // The size of the points in the Rauzy projection
+    last_word: (String, String, usize),  // The rules, start and iterations of the cached word
+    word: Vec<usize>,                    // The cached word, as indices into the alphabet
+    done_iterations: usize,              // The iterations that fit under MAX_WORD_LENGTH
+    alphabet: Vec<char>,                 // The letters of the morphism and the start
+    colors: Vec<Color32>,                // The color of every letter of the alphabet
+    projection: Option<Vec<(f64, f64)>>, // The projected step of every letter, if there is one
+    error: Option<String>,               // The error from parsing the rules, if any
+    texture: Option<(TextureId, Vec2)>,  // The rendered word and its size
+    last_input: Option<ImageInput>,      // The input of the rendered word
+}
+
+// The longest word that will be generated, so that many iterations can't freeze the app
+const MAX_WORD_LENGTH: usize = 500_000;
+
+// Everything the rendered word is made from: the rules, start and iterations of the word, the
+// mode, the angle, the actions, the line width, the point size and the size of the image
+type ImageInput = (
+    (String, String, usize),
+    DrawMode,
+    f32,
+    Vec<(char, Action)>,
+    f32,
+    f32,
+    Vec2,
+);
+
+#[derive(Clone, Copy, PartialEq)]
+enum DrawMode {
+    Turtle,
+    Rauzy,
+}
+
+// What the turtle does for a letter
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Forward,
+    ForwardLeft,
+    ForwardRight,
+    Left,
+    Right,
+    Nothing,
+}
+
+impl Action {
+    const ALL: [Action; 6] = [
+        Action::Forward,
+        Action::ForwardLeft,
+        Action::ForwardRight,
+        Action::Left,
+        Action::Right,
+        Action::Nothing,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::Forward => "Forward",
+            Action::ForwardLeft => "Forward, turn left",
+            Action::ForwardRight => "Forward, turn right",
+            Action::Left => "Turn left",
+            Action::Right => "Turn right",
+            Action::Nothing => "Nothing",
+        }
+    }
+}
+
+struct Preset {
+    name: &'static str,
+    rules: &'static str,
+    start: &'static str,
+    iterations: usize,
+    mode: DrawMode,
+    angle: f32,
+    actions: &'static [(char, Action)],
+}
+
+const PRESETS: [Preset; 4] = [
+    Preset {
+        name: "Tribonacci (Rauzy fractal)",
+        rules: "0 -> 01\n1 -> 02\n2 -> 0",
+        start: "0",
+        iterations: 20,
+        mode: DrawMode::Rauzy,
+        angle: 90.0,
+        actions: &[],
+    },
+    // Source: https://en.wikipedia.org/wiki/Thue%E2%80%93Morse_sequence#Fractal_curve
+    Preset {
+        name: "Thue-Morse (Koch curve)",
+        rules: "0 -> 01\n1 -> 10",
+        start: "0",
+        iterations: 12,
+        mode: DrawMode::Turtle,
+        angle: 60.0,
+        actions: &[('0', Action::Forward), ('1', Action::Left)],
+    },
+    Preset {
+        name: "Period-doubling",
+        rules: "0 -> 01\n1 -> 00",
+        start: "0",
+        iterations: 14,
+        mode: DrawMode::Turtle,
+        angle: 120.0,
+        actions: &[('0', Action::Forward), ('1', Action::Left)],
+    },
+    // The letters a and b are the 1s of the paperfolding sequence, and c and d are the 0s
+    // Source: https://en.wikipedia.org/wiki/Regular_paperfolding_sequence
+    Preset {
+        name: "Paperfolding (dragon curve)",
+        rules: "a -> ab\nb -> cb\nc -> ad\nd -> cd",
+        start: "a",
+        iterations: 14,
+        mode: DrawMode::Turtle,
+        angle: 90.0,
+        actions: &[
+            ('a', Action::ForwardLeft),
+            ('b', Action::ForwardLeft),
+            ('c', Action::ForwardRight),
+            ('d', Action::ForwardRight),
+        ],
+    },
+];
+
+impl Default for MorphicWord {
+    fn default() -> Self {
+        let mut app = Self {
+            preset: 0,
+            rules: String::new(),
+            start: String::new(),
+            iterations: 0,
+            mode: DrawMode::Turtle,
+            angle: 90.0,
+            actions: Vec::new(),
+            line_width: 1.0,
+            point_size: 1.5,
+            last_word: (String::new(), String::new(), 0),
+            word: Vec::new(),
+            done_iterations: 0,
+            alphabet: Vec::new(),
+            colors: Vec::new(),
+            projection: None,
+            error: None,
+            texture: None,
+            last_input: None,
+        };
+        app.load_preset(0);
+        app
+    }
+}
+
+impl epi::App for MorphicWord {
+    fn name(&self) -> &str {
+        "Morphic Words"
+    }
+
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui, frame));
+    }
+}
+
+impl MorphicWord {
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter, frame);
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(300.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let mut chosen = None;
+        ComboBox::from_label("Preset")
+            .selected_text(PRESETS[self.preset].name)
+            .show_ui(ui, |ui| {
+                for (i, preset) in PRESETS.iter().enumerate() {
+                    if ui.selectable_label(self.preset == i, preset.name).clicked() {
+                        chosen = Some(i);
+                    }
+                }
+            });
+        if let Some(i) = chosen {
+            self.load_preset(i);
+        }
+
+        ui.label("Morphism");
+        ui.code_editor(&mut self.rules);
+        ui.horizontal(|ui| {
+            ui.label("Start");
+            ui.text_edit_singleline(&mut self.start);
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+        ui.add(Slider::new(&mut self.iterations, 0..=30).text("Iterations"));
+        if self.done_iterations < self.iterations {
+            ui.label(format!(
+                "Stopped after {} iterations, the word got too long",
+                self.done_iterations
+            ));
+        }
+        ui.label(format!("Letters: {}", self.word.len()));
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.mode, DrawMode::Turtle, "Turtle curve");
+            ui.radio_value(&mut self.mode, DrawMode::Rauzy, "Rauzy projection");
+        });
+        match self.mode {
+            DrawMode::Turtle => {
+                ui.add(Slider::new(&mut self.angle, 0.0..=180.0).text("Turn angle"));
+                ui.add(Slider::new(&mut self.line_width, 0.5..=5.0).text("Line width"));
+                for (letter, action) in self.actions.iter_mut() {
+                    ComboBox::from_id_source(("morphic_action", *letter))
+                        .selected_text(action.name())
+                        .show_ui(ui, |ui| {
+                            for a in Action::ALL {
+                                ui.selectable_value(action, a, a.name());
+                            }
+                        });
+                    ui.label(format!("for {}", letter));
+                }
+            }
+            DrawMode::Rauzy => {
+                ui.add(Slider::new(&mut self.point_size, 0.5..=5.0).text("Point size"));
+                if self.projection.is_none() {
+                    ui.label("The morphism doesn't shrink any direction, so it can't be projected");
+                }
+            }
+        }
+        // The texture isn't part of the state that's reset: it's put back so the next paint can
+        // free it once it sees the word changed
+        let texture = self.texture.take();
+        egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    fn load_preset(&mut self, index: usize) {
+        let preset = &PRESETS[index];
+        self.preset = index;
+        self.rules = preset.rules.to_owned();
+        self.start = preset.start.to_owned();
+        self.iterations = preset.iterations;
+        self.mode = preset.mode;
+        self.angle = preset.angle;
+        self.actions = preset.actions.to_vec();
+    }
+
+    // Applies the morphism again if it changed since the last frame
+    fn update_word(&mut self) {
+        let key = (self.rules.clone(), self.start.clone(), self.iterations);
+        if key == self.last_word {
+            return;
+        }
+        self.last_word = key;
+
+        let morphism = match Morphism::parse(&self.rules) {
+            Ok(morphism) => morphism,
+            // keep showing the last word that worked
+            Err(error) => {
+                self.error = Some(error);
+                return;
+            }
+        };
+        self.error = None;
+
+        let start: Vec<char> = self.start.chars().filter(|c| !c.is_whitespace()).collect();
+        let (word, done) = morphism.iterate(&start, self.iterations, MAX_WORD_LENGTH);
+        self.done_iterations = done;
+        self.projection = morphism.contracting_projection();
+
+        // the letters of the start without a rule stay as they are, so they are added at the end
+        self.alphabet = morphism.alphabet;
+        for &letter in &start {
+            if !self.alphabet.contains(&letter) {
+                self.alphabet.push(letter);
+            }
+        }
+        self.word = word
+            .iter()
+            .map(|letter| self.alphabet.iter().position(|l| l == letter).unwrap())
+            .collect();
+        let count = self.alphabet.len();
+        self.colors = (0..count)
+            .map(|i| {
+                let (r, g, b) = palette::hsv_to_rgb(i as f32 / count as f32, 0.7, 1.0);
+                Color32::from_rgb(r, g, b)
+            })
+            .collect();
+
+        // every letter gets an action, new ones move forward
+        let actions = std::mem::take(&mut self.actions);
+        self.actions = self
+            .alphabet
+            .iter()
+            .map(|&letter| {
+                let action = actions.iter().find(|(l, _)| *l == letter);
+                (letter, action.map_or(Action::Forward, |(_, a)| *a))
+            })
+            .collect();
+    }
+
+    // The lines of the turtle curve, colored by the letter that drew them
+    fn turtle_lines(&self) -> Vec<([Pos2; 2], Color32)> {
+        // the action of every letter of the alphabet, letters without one move forward
+        let actions: Vec<Action> = self
+            .alphabet
+            .iter()
+            .map(|&letter| {
+                let action = self.actions.iter().find(|(l, _)| *l == letter);
+                action.map_or(Action::Forward, |(_, a)| *a)
+            })
+            .collect();
+
+        let turn = self.angle.to_radians();
+        let mut heading: f32 = 0.0;
+        let mut position = pos2(0.0, 0.0);
+        let mut lines = Vec::new();
+        for &letter in &self.word {
+            let action = actions[letter];
+            if matches!(
+                action,
+                Action::Forward | Action::ForwardLeft | Action::ForwardRight
+            ) {
+                let end = position + vec2(heading.cos(), -heading.sin());
+                lines.push(([position, end], self.colors[letter]));
+                position = end;
+            }
+            match action {
+                Action::ForwardLeft | Action::Left => heading += turn,
+                Action::ForwardRight | Action::Right => heading -= turn,
+                _ => {}
+            }
+        }
+        lines
+    }
+
+    // The projected points of the broken line, colored by the letter that comes next
+    fn rauzy_points(&self) -> Vec<(Pos2, Color32)> {
+        let projection = match &self.projection {
+            Some(projection) => projection,
+            None => return Vec::new(),
+        };
+
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut points = Vec::with_capacity(self.word.len());
+        for &letter in &self.word {
+            points.push((pos2(x as f32, y as f32), self.colors[letter]));
+            // the letters that were only in the start don't move
+            if let Some(step) = projection.get(letter) {
+                x += step.0;
+                y -= step.1;
+            }
+        }
+        points
+    }
+
+    // Generates the shapes of the word, scaled to fit in the rectangle
+    fn word_shapes(&mut self, rect: Rect) -> Vec<Shape> {
+        self.update_word();
+
+        let mut bounds = Rect::NOTHING;
+        let fit = |bounds: Rect| {
+            let scale = 0.9
+                * (rect.width() / bounds.width().max(1e-6))
+                    .min(rect.height() / bounds.height().max(1e-6));
+            move |p: Pos2| rect.center() + (p - bounds.center()) * scale
+        };
+
+        match self.mode {
+            DrawMode::Turtle => {
+                let lines = self.turtle_lines();
+                for (line, _) in &lines {
+                    bounds.extend_with(line[0]);
+                    bounds.extend_with(line[1]);
+                }
+                let to_screen = fit(bounds);
+                lines
+                    .into_iter()
+                    .map(|(line, color)| {
+                        Shape::line_segment(
+                            [to_screen(line[0]), to_screen(line[1])],
+                            (self.line_width, color),
+                        )
+                    })
+                    .collect()
+            }
+            DrawMode::Rauzy => {
+                let points = self.rauzy_points();
+                for (point, _) in &points {
+                    bounds.extend_with(*point);
+                }
+                let to_screen = fit(bounds);
+                let size = Vec2::splat(self.point_size);
+                points
+                    .into_iter()
+                    .map(|(point, color)| {
+                        Shape::rect_filled(
+                            Rect::from_center_size(to_screen(point), size),
+                            0.0,
+                            color,
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Long words have hundreds of thousands of lines or points, so the word is drawn into a
+    // texture once and only drawn again when the word or the settings change
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
+        self.update_word();
+        let rect = painter.clip_rect();
+        let size = rect.size().round();
+        let input = (
+            self.last_word.clone(),
+            self.mode,
+            self.angle,
+            self.actions.clone(),
+            self.line_width,
+            self.point_size,
+            size,
+        );
+
+        // Need to recalculate due to a change in one of the settings
+        if self.last_input.as_ref() != Some(&input) {
+            let shapes = self.word_shapes(Rect::from_min_size(Pos2::ZERO, size));
+            let image = Image::from_shapes(
+                size.x as usize,
+                size.y as usize,
+                Color32::TRANSPARENT,
+                &shapes,
+            );
+            if let Some((texture, _)) = self.texture.take() {
+                frame.tex_allocator().free(texture);
+            }
+            let texture = frame
+                .tex_allocator()
+                .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+            self.texture = Some((texture, size));
+            self.last_input = Some(input);
+        }
+
+        if let Some((texture, size)) = self.texture {
+            let mut mesh = epaint::Mesh::with_texture(texture);
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+    }
+}
+
+impl Animate for MorphicWord {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Iterations", self.iterations as f32, 0.0..=30.0),
+            Parameter::new("Turn angle", self.angle, 0.0..=180.0),
+            Parameter::new("Line width", self.line_width, 0.5..=5.0),
+            Parameter::new("Point size", self.point_size, 0.5..=5.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Iterations" => self.iterations = value.round() as usize,
+            "Turn angle" => self.angle = value,
+            "Line width" => self.line_width = value,
+            "Point size" => self.point_size = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for MorphicWord {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        Image::from_shapes(width, height, Color32::BLACK, &self.word_shapes(rect))
+    }
+}
+
+impl FractalApp for MorphicWord {}
@@ -1,12 +1,33 @@
+use super::FractalApp;
+use crate::dimension::{Counted, KnownDimension};
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::solid::{self, Solid};
+use crate::timeline::{Animate, Parameter};
 use eframe::{egui, epi};
 use egui::{containers::*, widgets::*, *};
-use rand::Rng; // 0.8.0
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng}; // 0.8.0
 
 #[derive(PartialEq)]
 
 /*
 This file contains the code for recursively generating and then rendering the Sierpinski Carpet.
+The square is split into a grid of cells by a mask. Filled cells are painted, recursing cells are
+split again with the same mask and empty cells are left alone. The classic carpet fills the middle
+cell of a 3x3 grid, and other masks give the Vicsek fractal, Cantor dust and many more.
+The k recursing cells of an n x n grid are copies scaled by 1/n, so the dimension is log(k)/log(n).
+Grids with more columns than rows (or the other way around) give self-affine carpets, whose
+dimension comes from McMullen's formula.
 Fractal Explanation: https://en.wikipedia.org/wiki/Sierpi%C5%84ski_carpet
+With randomness, each square keeps its filled cells with a probability (which can change with
+the level), and a seeded generator picks the same squares every time for the same seed.
+Deep rendering colors every pixel on its own instead: the pixel's position is written in base n
+and each digit picks the next cell, until a filled or empty cell is found or the cells get
+smaller than a pixel. The view is kept relative to a small square deep inside the carpet, so it
+can be zoomed in forever without running out of floating point precision. There the random
+squares come from a hash of the seed and the square's position instead of a generator.
+Source: https://en.wikipedia.org/wiki/List_of_fractals_by_Hausdorff_dimension
 */
 
 pub struct SierpinskiCarpet {
@@ -16,9 +37,248 @@ pub struct SierpinskiCarpet {
     last_show_randomness: bool, // Used to determine if show_randomness was changed
     randomness_probability: f64, // When showing randomness, this represents the probability that
     last_randomness_probability: f64, // Same as last_show_randomness
+    per_level: bool,       // If true, every level has its own probability
+    last_per_level: bool,  // Same as last_show_randomness
+    level_probabilities: [f64; 7], // The probability for each level when per_level is true
+    last_level_probabilities: [f64; 7], // Same as last_show_randomness
+    seed: u64,             // The seed of the random generator used when showing randomness
+    last_seed: u64,        // Same as last_show_randomness
     show_blue_shades: bool, // If true, changes the Carpet's color to ROY G BIV
     last_show_blue_shades: bool, // Same as last_show_randomness
+    mask: Mask,            // Which cells of the grid are filled, recurse or are left empty
+    last_mask: Mask,       // Same as last_show_randomness
+    deep: bool,            // If true, the carpet is colored pixel by pixel and can be zoomed
+    last_deep: bool,       // Same as last_show_randomness
+    view: DeepView,        // Where the deep rendering is looking
+    last_view: DeepView,   // Same as last_show_randomness
+    texture: Option<(TextureId, Vec2)>, // The deep rendering and its size
     shapes: Vec<Shape>,    // A vector containing the shapes that will be painted on the screen
+    last_size: Vec2,       // The size of the screen the shapes were fitted to
+}
+
+// What happens to a cell of the grid
+#[derive(Clone, Copy, PartialEq)]
+enum Cell {
+    Recurse, // The cell is split again with the same mask
+    Fill,    // The cell is painted
+    Empty,   // The cell is left alone
+}
+
+impl Cell {
+    // The cell after clicking on it in the mask editor
+    fn next(self) -> Self {
+        match self {
+            Cell::Recurse => Cell::Fill,
+            Cell::Fill => Cell::Empty,
+            Cell::Empty => Cell::Recurse,
+        }
+    }
+}
+
+// A grid of cells that says how every square of the carpet is split
+#[derive(Clone, PartialEq)]
+struct Mask {
+    columns: usize,
+    rows: usize,
+    cells: Vec<Cell>, // The cells row by row
+}
+
+// The named masks, one row per line with R for recursing cells, F for filled cells and . for empty
+const PRESETS: [(&str, &str); 6] = [
+    ("Sierpinski Carpet", "RRR/RFR/RRR"),
+    ("Vicsek Fractal", "FRF/RRR/FRF"),
+    ("Vicsek Saltire", "RFR/FRF/RFR"),
+    ("Cantor Dust", "RFR/FFF/RFR"),
+    ("Sierpinski Triangle", "RF/RR"),
+    ("McMullen Carpet", "RFR/FRF"),
+];
+
+// The default probabilities per level, the big squares near the top are dropped less often
+const LEVEL_PROBABILITIES: [f64; 7] = [0.95, 0.85, 0.75, 0.65, 0.55, 0.45, 0.35];
+
+// The most squares drawn, the depth is lowered for masks that grow too fast
+const MAX_SHAPES: usize = 300_000;
+
+// The most cells along a side of a 3D printed carpet, smaller holes are left closed. At 100 mm a
+// cell is 0.4 mm wide, about as thin as printers can go.
+const MAX_PRINT_CELLS: usize = 243;
+
+impl Mask {
+    fn parse(pattern: &str) -> Self {
+        let lines: Vec<&str> = pattern.split('/').collect();
+        let cells = lines
+            .iter()
+            .flat_map(|line| line.chars())
+            .map(|c| match c {
+                'R' => Cell::Recurse,
+                'F' => Cell::Fill,
+                _ => Cell::Empty,
+            })
+            .collect();
+        Self {
+            columns: lines[0].len(),
+            rows: lines.len(),
+            cells,
+        }
+    }
+
+    fn cell(&self, column: usize, row: usize) -> Cell {
+        self.cells[row * self.columns + column]
+    }
+
+    // Changes the size of the grid, keeping the cells that are still in it
+    fn resize(&mut self, columns: usize, rows: usize) {
+        let mut cells = vec![Cell::Recurse; columns * rows];
+        for row in 0..rows.min(self.rows) {
+            for column in 0..columns.min(self.columns) {
+                cells[row * columns + column] = self.cell(column, row);
+            }
+        }
+        *self = Self {
+            columns,
+            rows,
+            cells,
+        };
+    }
+
+    fn count(&self, kind: Cell) -> usize {
+        self.cells.iter().filter(|&&cell| cell == kind).count()
+    }
+
+    // The deepest level, up to depth, that draws at most MAX_SHAPES squares
+    fn max_depth(&self, depth: usize) -> usize {
+        let (recursing, filled) = (self.count(Cell::Recurse), self.count(Cell::Fill));
+        let (mut squares, mut total) = (1, 0);
+        for level in 0..=depth {
+            // every square at this level is visited and paints its filled cells
+            total += squares * (filled + 1);
+            if total > MAX_SHAPES {
+                return level.saturating_sub(1);
+            }
+            squares *= recursing;
+        }
+        depth
+    }
+
+    // The Hausdorff dimension of the limit of the recursing cells. For a grid with n columns
+    // and m rows (n > m), where the j-th row has t(j) recursing cells, McMullen's formula gives
+    // log_m(sum of t(j)^(log m / log n)), which is log(k)/log(n) for a square grid.
+    fn dimension(&self) -> f64 {
+        let (fine, coarse) = (self.columns.max(self.rows), self.columns.min(self.rows));
+        let exponent = (coarse as f64).ln() / (fine as f64).ln();
+        let sum: f64 = (0..coarse)
+            .map(|j| {
+                // the cells in the j-th row, or column if there are more rows than columns
+                let recursing = (0..fine)
+                    .filter(|&i| {
+                        let (column, row) = if self.columns >= self.rows {
+                            (i, j)
+                        } else {
+                            (j, i)
+                        };
+                        self.cell(column, row) == Cell::Recurse
+                    })
+                    .count();
+                (recursing as f64).powf(exponent)
+            })
+            .sum();
+        if sum <= 1.0 {
+            0.0
+        } else {
+            sum.ln() / (coarse as f64).ln()
+        }
+    }
+}
+
+// Where the deep rendering is looking. The view is relative to a square deep in the carpet, where
+// the square goes from 0 to 1, so that the numbers stay small however far it is zoomed in.
+#[derive(Clone, PartialEq)]
+struct DeepView {
+    path: Vec<(usize, usize)>, // The cells (column, row) from the whole carpet down to the square
+    center: [f64; 2],          // The point of the square in the middle of the screen
+    zoom: [f64; 2],            // How far the square is zoomed in, 1 fits the whole carpet
+}
+
+impl Default for DeepView {
+    fn default() -> Self {
+        Self {
+            path: Vec::new(),
+            center: [0.5, 0.5],
+            zoom: [1.0, 1.0],
+        }
+    }
+}
+
+impl DeepView {
+    // How many pixels one unit of the square is on the screen
+    fn scale(&self, rect: Rect) -> [f64; 2] {
+        let fit = 0.9 * rect.width().min(rect.height()) as f64;
+        [fit * self.zoom[0], fit * self.zoom[1]]
+    }
+
+    // The point of the square under a point on the screen
+    fn to_square(&self, rect: Rect, p: Pos2) -> [f64; 2] {
+        let scale = self.scale(rect);
+        [
+            self.center[0] + (p.x - rect.center().x) as f64 / scale[0],
+            self.center[1] + (p.y - rect.center().y) as f64 / scale[1],
+        ]
+    }
+
+    // Moves the view into the cell in the middle of the screen once the screen fits inside it,
+    // and back out to the bigger square when zoomed out or moved off the square
+    fn normalize(&mut self, rect: Rect, mask: &Mask) {
+        let (columns, rows) = (mask.columns as f64, mask.rows as f64);
+        loop {
+            let scale = self.scale(rect);
+            let window = [
+                rect.width() as f64 / scale[0],
+                rect.height() as f64 / scale[1],
+            ];
+            let inside =
+                (0.0..1.0).contains(&self.center[0]) && (0.0..1.0).contains(&self.center[1]);
+
+            if let Some(&(column, row)) = self.path.last() {
+                if window[0] > 1.0 || window[1] > 1.0 || !inside {
+                    self.path.pop();
+                    self.center = [
+                        (self.center[0] + column as f64) / columns,
+                        (self.center[1] + row as f64) / rows,
+                    ];
+                    self.zoom = [self.zoom[0] * columns, self.zoom[1] * rows];
+                    continue;
+                }
+            }
+
+            let (column, row) = (
+                (self.center[0] * columns).floor(),
+                (self.center[1] * rows).floor(),
+            );
+            if inside
+                && window[0] < 1.0 / columns
+                && window[1] < 1.0 / rows
+                && mask.cell(column as usize, row as usize) == Cell::Recurse
+            {
+                self.path.push((column as usize, row as usize));
+                self.center = [
+                    self.center[0] * columns - column,
+                    self.center[1] * rows - row,
+                ];
+                self.zoom = [self.zoom[0] / columns, self.zoom[1] / rows];
+                continue;
+            }
+            return;
+        }
+    }
+}
+
+// Mixes a number into a hash, used to give every square of the deep carpet its own random number
+// Source: https://prng.di.unimi.it/splitmix64.c
+fn mix(hash: u64, value: u64) -> u64 {
+    let mut z = (hash ^ value).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
 }
 
 /*
@@ -35,8 +295,22 @@ impl Default for SierpinskiCarpet {
             last_show_randomness: false,
             randomness_probability: 0.5,
             last_randomness_probability: 0.5,
+            per_level: false,
+            last_per_level: false,
+            level_probabilities: LEVEL_PROBABILITIES,
+            last_level_probabilities: LEVEL_PROBABILITIES,
+            seed: 0,
+            last_seed: 0,
             show_blue_shades: false,
             last_show_blue_shades: false,
+            mask: Mask::parse(PRESETS[0].1),
+            last_mask: Mask::parse(PRESETS[0].1),
+            deep: false,
+            last_deep: false,
+            view: DeepView::default(),
+            last_view: DeepView::default(),
+            last_size: Vec2::ZERO,
+            texture: None,
             shapes: Vec::new(),
         }
     }
@@ -48,48 +322,149 @@ impl epi::App for SierpinskiCarpet {
     }
 
     // Called every frame
-    fn update(&mut self, ctx: &egui::CtxRef, _frame: &mut epi::Frame<'_>) {
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
         egui::CentralPanel::default()
             .frame(Frame::dark_canvas(&ctx.style()))
-            .show(ctx, |ui| self.ui(ui));
+            .show(ctx, |ui| self.ui(ui, frame));
     }
 }
 
 impl SierpinskiCarpet {
     // Paints the fractal
-    pub fn ui(&mut self, ui: &mut Ui) {
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
         let painter = Painter::new(
             ui.ctx().clone(),
             ui.layer_id(),
             ui.available_rect_before_wrap(),
         );
-        self.paint(&painter);
+        self.paint(&painter, frame);
 
         // Make sure we allocate what we used (everything)
         ui.expand_to_include_rect(painter.clip_rect());
 
-        Frame::popup(ui.style())
+        let settings = Frame::popup(ui.style())
             .stroke(Stroke::none())
             .show(ui, |ui| {
                 ui.set_max_width(270.0);
                 CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
             });
+
+        // After the settings, so that their sliders get dragged instead of the carpet
+        if self.deep {
+            self.navigate(ui, painter.clip_rect(), settings.response.rect);
+        }
     }
 
     // An options window for setting the depth of the fractal generation
     fn options_ui(&mut self, ui: &mut Ui) {
-        ui.add(Slider::new(&mut self.depth, 1..=6).text("Depth"));
+        ui.checkbox(&mut self.deep, "Deep Rendering (per pixel)");
+        if self.deep {
+            let columns = self.mask.columns as f64;
+            let zoom = self.view.zoom[0].log10() + self.view.path.len() as f64 * columns.log10();
+            ui.label(format!(
+                "Zoom: 10^{:.1}, drag to move and scroll to zoom",
+                zoom
+            ));
+            if ui.button("Reset View").clicked() {
+                self.view = DeepView::default();
+            }
+        } else {
+            ui.add(Slider::new(&mut self.depth, 1..=6).text("Depth"));
+        }
         ui.checkbox(&mut self.show_randomness, "Include Randomness");
 
         if self.show_randomness {
-            ui.add(
-                Slider::new(&mut self.randomness_probability, 0.0..=1.0)
-                    .text("Randomness Probability"),
-            );
+            ui.checkbox(&mut self.per_level, "Probability per Level");
+            if self.per_level {
+                let last_level = if self.deep { 6 } else { self.depth };
+                for level in 0..=last_level {
+                    ui.add(
+                        Slider::new(&mut self.level_probabilities[level], 0.0..=1.0)
+                            .text(format!("Level {}", level)),
+                    );
+                }
+            } else {
+                ui.add(
+                    Slider::new(&mut self.randomness_probability, 0.0..=1.0)
+                        .text("Randomness Probability"),
+                );
+            }
+            super::seed_ui(ui, &mut self.seed);
         }
 
         ui.checkbox(&mut self.show_blue_shades, "Shades of Blue");
+
+        ui.separator();
+        self.mask_ui(ui);
+        // Reset would drop the deep rendering without freeing it, so it's kept aside and put back
+        // for the next paint to free
+        let texture = self.texture.take();
         egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    // An editor for the grid of cells, and the dimension of the fractal it makes
+    fn mask_ui(&mut self, ui: &mut Ui) {
+        let preset = PRESETS
+            .iter()
+            .find(|(_, pattern)| Mask::parse(pattern) == self.mask)
+            .map_or("Custom", |(name, _)| name);
+        ComboBox::from_label("Mask")
+            .selected_text(preset)
+            .show_ui(ui, |ui| {
+                for (name, pattern) in PRESETS.iter() {
+                    if ui.selectable_label(preset == *name, *name).clicked() {
+                        self.mask = Mask::parse(pattern);
+                    }
+                }
+            });
+
+        let (mut columns, mut rows) = (self.mask.columns, self.mask.rows);
+        ui.add(Slider::new(&mut columns, 2..=7).text("Columns"));
+        ui.add(Slider::new(&mut rows, 2..=7).text("Rows"));
+        if (columns, rows) != (self.mask.columns, self.mask.rows) {
+            self.mask.resize(columns, rows);
+        }
+
+        ui.label("Click a cell: R recurses, F is filled and empty cells are left alone");
+        Grid::new("carpet_mask").spacing([2.0, 2.0]).show(ui, |ui| {
+            for row in 0..self.mask.rows {
+                for column in 0..self.mask.columns {
+                    let cell = &mut self.mask.cells[row * self.mask.columns + column];
+                    let (text, fill) = match cell {
+                        Cell::Recurse => ("R", Color32::from_gray(60)),
+                        Cell::Fill => ("F", Color32::BLUE),
+                        Cell::Empty => (" ", Color32::BLACK),
+                    };
+                    if ui.add(Button::new(text).fill(fill)).clicked() {
+                        *cell = cell.next();
+                    }
+                }
+                ui.end_row();
+            }
+        });
+
+        let recursing = self.mask.count(Cell::Recurse);
+        if self.mask.columns == self.mask.rows {
+            ui.label(format!(
+                "Hausdorff dimension: log({})/log({}) = {:.4}",
+                recursing,
+                self.mask.columns,
+                self.mask.dimension()
+            ));
+        } else {
+            ui.label(format!(
+                "Hausdorff dimension (McMullen): {:.4}",
+                self.mask.dimension()
+            ));
+        }
+        let max_depth = self.mask.max_depth(self.depth);
+        if max_depth < self.depth {
+            ui.label(format!(
+                "Only {} levels are drawn, this mask grows fast",
+                max_depth
+            ));
+        }
     }
 
     // Paints a rectangular given its center and size
@@ -102,101 +477,279 @@ impl SierpinskiCarpet {
     }
 
     // A recursive function for creatng the sierpinski carpet
-    fn sierpinski_carpet(
-        &mut self,
-        center_x: f32,
-        center_y: f32,
-        width: f32,
-        height: f32,
-        n: i64,
-        level: i64,
-    ) {
+    fn sierpinski_carpet(&mut self, square: Rect, n: i64, level: i64, rng: &mut StdRng) {
         let mut can_draw = true;
 
         if self.show_randomness {
-            let num: f64 = rand::thread_rng().gen();
-            can_draw = num <= self.randomness_probability;
+            let num: f64 = rng.gen();
+            can_draw = num <= self.probability(level as usize);
         }
 
         if can_draw {
-            // let colors = vec![Color32::RED, Color32::from_rgb(255, 165, 0), Color32::YELLOW, Color32::GREEN, Color32::BLUE, Color32::from_rgb(75, 0, 130), Color32::from_rgb(143, 0, 255)];
-            let blue_colors = vec![
-                Color32::from_rgb(144, 224, 239),
-                Color32::from_rgb(72, 202, 228),
-                Color32::from_rgb(90, 180, 216),
-                Color32::from_rgb(0, 150, 199),
-                Color32::from_rgb(0, 119, 182),
-                Color32::from_rgb(2, 62, 138),
-                Color32::from_rgb(3, 4, 94),
-            ];
+            // paint the filled cells
+            let color = self.level_color(level as usize);
+            for cell in self.cells(square, Cell::Fill) {
+                self.paint_rect(cell.center(), cell.size(), color);
+            }
+        }
 
-            // draw in middle square
-            if self.show_blue_shades {
-                if level >= 0 && level <= 6 {
-                    let color = blue_colors[level as usize];
-                    self.paint_rect(
-                        pos2(center_x, center_y),
-                        vec2(width / 3.0, height / 3.0),
-                        color,
-                    );
-                } else {
-                    self.paint_rect(
-                        pos2(center_x, center_y),
-                        vec2(width / 3.0, height / 3.0),
-                        Color32::WHITE,
-                    );
+        // recurse on the other cells until you hit the base case n = 0
+        if n > 0 {
+            for cell in self.cells(square, Cell::Recurse) {
+                self.sierpinski_carpet(cell, n - 1, level + 1, rng);
+            }
+        }
+    }
+
+    // Cuts the holes of a square out of a printed carpet, where the square is given by its corner
+    // and size in cells of the print grid. The random numbers are drawn in the same order as in
+    // sierpinski_carpet, so that the printed carpet is the drawn one.
+    fn carve(
+        &self,
+        material: &mut [bool],
+        columns: usize,
+        square: [usize; 4],
+        n: i64,
+        level: i64,
+        rng: &mut StdRng,
+    ) {
+        let mut keep_holes = true;
+        if self.show_randomness {
+            let num: f64 = rng.gen();
+            keep_holes = num <= self.probability(level as usize);
+        }
+
+        let [x, y, width, height] = square;
+        let (cell_width, cell_height) = (width / self.mask.columns, height / self.mask.rows);
+        for row in 0..self.mask.rows {
+            for column in 0..self.mask.columns {
+                let (cell_x, cell_y) = (x + column * cell_width, y + row * cell_height);
+                match self.mask.cell(column, row) {
+                    Cell::Fill if !keep_holes => {}
+                    Cell::Fill | Cell::Empty => {
+                        for line in cell_y..cell_y + cell_height {
+                            let start = line * columns + cell_x;
+                            material[start..start + cell_width].fill(false);
+                        }
+                    }
+                    Cell::Recurse => {
+                        if n > 0 {
+                            let cell = [cell_x, cell_y, cell_width, cell_height];
+                            self.carve(material, columns, cell, n - 1, level + 1, rng);
+                        }
+                    }
                 }
-            } else {
-                self.paint_rect(
-                    pos2(center_x, center_y),
-                    vec2(width / 3.0, height / 3.0),
-                    Color32::BLUE,
-                );
             }
         }
+    }
 
-        // recurse on 8 other squares until you hit the base case n = 0
-        if n > 0 {
-            for row in -1..=1 {
-                for col in -1..=1 {
-                    if row != 0 || col != 0 {
-                        let new_center_x = center_x + (row as f32) * width / 3.0;
-                        let new_center_y = center_y + (col as f32) * height / 3.0;
-                        self.sierpinski_carpet(
-                            new_center_x,
-                            new_center_y,
-                            width / 3.0,
-                            height / 3.0,
-                            n - 1,
-                            level + 1,
-                        );
+    // The probability that a square at some level keeps its filled cells when showing randomness
+    fn probability(&self, level: usize) -> f64 {
+        if self.per_level {
+            self.level_probabilities[level.min(self.level_probabilities.len() - 1)]
+        } else {
+            self.randomness_probability
+        }
+    }
+
+    // The color of the filled cells at some level
+    fn level_color(&self, level: usize) -> Color32 {
+        if self.show_blue_shades {
+            palette::blue_shade(level)
+        } else {
+            Color32::BLUE
+        }
+    }
+
+    // The color of a pixel of the deep carpet, given the point of the view's square under it.
+    // hashes holds the hash of every square on the view's path.
+    fn deep_pixel(&self, point: [f64; 2], scale: [f64; 2], hashes: &[u64]) -> Option<Color32> {
+        let (columns, rows) = (self.mask.columns, self.mask.rows);
+        let (mut point, mut scale, mut level) = (point, scale, self.view.path.len());
+
+        // go out to the first square on the path that has the point in it
+        while !(0.0..1.0).contains(&point[0]) || !(0.0..1.0).contains(&point[1]) {
+            if level == 0 {
+                return None;
+            }
+            level -= 1;
+            let (column, row) = self.view.path[level];
+            point = [
+                (point[0] + column as f64) / columns as f64,
+                (point[1] + row as f64) / rows as f64,
+            ];
+            scale = [scale[0] * columns as f64, scale[1] * rows as f64];
+        }
+
+        // then read the digits of the point until a cell that isn't split again
+        let mut hash = hashes[level];
+        loop {
+            scale = [scale[0] / columns as f64, scale[1] / rows as f64];
+            if scale[0] < 1.0 || scale[1] < 1.0 {
+                // the cells are smaller than a pixel
+                return None;
+            }
+            let column = ((point[0] * columns as f64) as usize).min(columns - 1);
+            let row = ((point[1] * rows as f64) as usize).min(rows - 1);
+            match self.mask.cell(column, row) {
+                Cell::Fill => {
+                    let num = (hash >> 11) as f64 / (1_u64 << 53) as f64;
+                    if self.show_randomness && num > self.probability(level) {
+                        return None;
                     }
+                    return Some(self.level_color(level));
+                }
+                Cell::Empty => return None,
+                Cell::Recurse => {
+                    point = [
+                        point[0] * columns as f64 - column as f64,
+                        point[1] * rows as f64 - row as f64,
+                    ];
+                    hash = mix(hash, (row * columns + column) as u64);
+                    level += 1;
+                }
+            }
+        }
+    }
+
+    // Colors every pixel of the deep carpet in the rectangle
+    fn deep_image(&self, rect: Rect, background: Color32) -> Image {
+        let (width, height) = (rect.width() as usize, rect.height() as usize);
+        let mut image = Image::new(width, height, background);
+        let scale = self.view.scale(rect);
+
+        let mut hashes = vec![mix(self.seed, 0)];
+        for &(column, row) in &self.view.path {
+            let last = hashes[hashes.len() - 1];
+            hashes.push(mix(last, (row * self.mask.columns + column) as u64));
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = rect.min + vec2(x as f32 + 0.5, y as f32 + 0.5);
+                let point = self.view.to_square(rect, pixel);
+                if let Some(color) = self.deep_pixel(point, scale, &hashes) {
+                    image.set(x, y, color);
+                }
+            }
+        }
+        image
+    }
+
+    // Dragging moves the deep carpet and scrolling zooms in and out around the mouse
+    fn navigate(&mut self, ui: &mut Ui, rect: Rect, settings: Rect) {
+        let response = ui.interact(rect, ui.id().with("carpet_view"), Sense::drag());
+
+        if response.dragged() {
+            let delta = response.drag_delta();
+            let scale = self.view.scale(rect);
+            self.view.center[0] -= delta.x as f64 / scale[0];
+            self.view.center[1] -= delta.y as f64 / scale[1];
+        }
+
+        let scroll = ui.input().scroll_delta.y;
+        let pointer = ui.input().pointer.hover_pos();
+        if response.hovered() && scroll != 0.0 && !pointer.is_some_and(|p| settings.contains(p)) {
+            let factor = (scroll as f64 / 200.0).exp();
+            // keep the point under the mouse in the same place
+            let anchor = pointer.map_or(self.view.center, |p| self.view.to_square(rect, p));
+            let center = self.view.center;
+            self.view.center = [
+                anchor[0] - (anchor[0] - center[0]) / factor,
+                anchor[1] - (anchor[1] - center[1]) / factor,
+            ];
+            self.view.zoom = [self.view.zoom[0] * factor, self.view.zoom[1] * factor];
+            // the whole carpet stays at least a little bit on the screen
+            if self.view.path.is_empty() && self.view.zoom[0] < 0.1 {
+                self.view.zoom = [0.1, 0.1];
+            }
+        }
+        self.view.normalize(rect, &self.mask);
+    }
+
+    // The cells of one kind in a square of the carpet
+    fn cells(&self, square: Rect, kind: Cell) -> Vec<Rect> {
+        let size = vec2(
+            square.width() / self.mask.columns as f32,
+            square.height() / self.mask.rows as f32,
+        );
+        let mut cells = Vec::new();
+        for row in 0..self.mask.rows {
+            for column in 0..self.mask.columns {
+                if self.mask.cell(column, row) == kind {
+                    let offset = vec2(column as f32, row as f32) * size;
+                    cells.push(Rect::from_min_size(square.min + offset, size));
                 }
             }
         }
+        cells
+    }
+
+    // Generates the shapes of the carpet in the center of the given rectangle
+    fn carpet_shapes(&mut self, rect: Rect) -> Vec<Shape> {
+        // The recursion paints into self.shapes, so keep the cached shapes aside
+        let cached_shapes = std::mem::take(&mut self.shapes);
+
+        let num_levels = self.mask.max_depth(self.depth) as i64;
+        let center = pos2(rect.width() / 2.0, rect.height() / 2.0);
+        let side = 0.9 * rect.width().min(rect.height());
+        self.sierpinski_carpet(
+            Rect::from_center_size(center, vec2(side, side)),
+            num_levels,
+            0,
+            &mut StdRng::seed_from_u64(self.seed),
+        );
+
+        std::mem::replace(&mut self.shapes, cached_shapes)
     }
 
-    fn paint(&mut self, painter: &Painter) {
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
         let rect = painter.clip_rect();
 
+        // the zoomed in path only makes sense for the mask it was made with
+        let path_is_valid = self.view.path.iter().all(|&(column, row)| {
+            column < self.mask.columns
+                && row < self.mask.rows
+                && self.mask.cell(column, row) == Cell::Recurse
+        });
+        if !path_is_valid {
+            self.view = DeepView::default();
+        }
+
         let need_to_recalculate = self.depth != self.last_depth
             || self.show_blue_shades != self.last_show_blue_shades
             || self.show_randomness != self.last_show_randomness
-            || self.randomness_probability != self.last_randomness_probability;
+            || self.randomness_probability != self.last_randomness_probability
+            || self.per_level != self.last_per_level
+            || self.level_probabilities != self.last_level_probabilities
+            || self.seed != self.last_seed
+            || self.mask != self.last_mask
+            || self.deep != self.last_deep;
 
         // Need to recalculate due to a change in one of the settings
-        if need_to_recalculate {
-            self.shapes.clear();
-            // Calculates the fractal in the center of the screen
-            let num_levels = self.depth as i64;
-            self.sierpinski_carpet(
-                rect.width() / 2.0,
-                rect.height() / 2.0,
-                500.0,
-                500.0,
-                num_levels,
-                0,
-            );
+        if self.deep {
+            let size = rect.size().round();
+            let resized = self
+                .texture
+                .is_none_or(|(_, texture_size)| texture_size != size);
+            if need_to_recalculate || resized || self.view != self.last_view {
+                let image =
+                    self.deep_image(Rect::from_min_size(rect.min, size), Color32::TRANSPARENT);
+                if let Some((texture, _)) = self.texture.take() {
+                    frame.tex_allocator().free(texture);
+                }
+                let texture = frame
+                    .tex_allocator()
+                    .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+                self.texture = Some((texture, size));
+            }
+        } else {
+            if let Some((texture, _)) = self.texture.take() {
+                frame.tex_allocator().free(texture);
+            }
+            if need_to_recalculate || rect.size() != self.last_size {
+                self.shapes = self.carpet_shapes(rect);
+            }
         }
 
         // Update the the markers in order to prevent further recalculations
@@ -204,6 +757,23 @@ impl SierpinskiCarpet {
         self.last_show_randomness = self.show_randomness;
         self.last_show_blue_shades = self.show_blue_shades;
         self.last_randomness_probability = self.randomness_probability;
+        self.last_per_level = self.per_level;
+        self.last_level_probabilities = self.level_probabilities;
+        self.last_seed = self.seed;
+        self.last_mask = self.mask.clone();
+        self.last_deep = self.deep;
+        self.last_view = self.view.clone();
+        self.last_size = rect.size();
+
+        if self.deep {
+            if let Some((texture, size)) = self.texture {
+                let mut mesh = epaint::Mesh::with_texture(texture);
+                let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+                mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+                painter.add(Shape::mesh(mesh));
+            }
+            return;
+        }
 
         // Copy over shapes since painter.extend doesn't take a reference
         let cloned_shapes: std::vec::Vec<Shape> = self.shapes.clone();
@@ -212,3 +782,76 @@ impl SierpinskiCarpet {
         painter.extend(cloned_shapes);
     }
 }
+
+impl Animate for SierpinskiCarpet {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Depth", self.depth as f32, 1.0..=6.0),
+            Parameter::new(
+                "Randomness Probability",
+                self.randomness_probability as f32,
+                0.0..=1.0,
+            ),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Depth" => self.depth = value.round() as usize,
+            "Randomness Probability" => self.randomness_probability = value as f64,
+            _ => {}
+        }
+    }
+}
+
+impl Render for SierpinskiCarpet {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        if self.deep {
+            return self.deep_image(rect, Color32::BLACK);
+        }
+        Image::from_shapes(width, height, Color32::BLACK, &self.carpet_shapes(rect))
+    }
+}
+
+impl FractalApp for SierpinskiCarpet {
+    // The printed carpet is what is left of the square: the filled cells are holes and the empty
+    // cells are cut away
+    fn model(&mut self, thickness: f32) -> Option<Solid> {
+        // the print grid is as fine as the smallest cells, up to MAX_PRINT_CELLS
+        let num_levels = self.mask.max_depth(self.depth);
+        let largest = self.mask.columns.max(self.mask.rows);
+        let mut grid_levels = num_levels as u32 + 1;
+        while largest.pow(grid_levels) > MAX_PRINT_CELLS {
+            grid_levels -= 1;
+        }
+        let columns = self.mask.columns.pow(grid_levels);
+        let rows = self.mask.rows.pow(grid_levels);
+
+        let mut material = vec![true; columns * rows];
+        self.carve(
+            &mut material,
+            columns,
+            [0, 0, columns, rows],
+            num_levels as i64,
+            0,
+            &mut StdRng::seed_from_u64(self.seed),
+        );
+        Some(solid::extrude(columns, rows, &material, thickness))
+    }
+
+    // A square mask keeps R of its c x c cells, so the carpet is R copies of itself scaled by
+    // 1 / c. The carpet is what is left between the filled holes, which is their boundary.
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        let recursing = self.mask.count(Cell::Recurse);
+        let columns = self.mask.columns;
+        if self.show_randomness || columns != self.mask.rows || recursing == 0 {
+            return None;
+        }
+        Some(KnownDimension {
+            formula: format!("log {} / log {}", recursing, columns),
+            value: (recursing as f64).ln() / (columns as f64).ln(),
+            counted: Counted::Boundary,
+        })
+    }
+}
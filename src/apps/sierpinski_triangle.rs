@@ -0,0 +1,451 @@
+use super::FractalApp;
+use crate::dimension::{Counted, KnownDimension};
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the code for drawing the Sierpinski triangle in three different ways.
+The recursive generator splits a triangle into four halves and keeps the three in the corners.
+The chaos game starts at a corner and jumps part of the way to a random corner again and again.
+Pascal's triangle mod p colors the binomial coefficients that are not divisible by p.
+With 3 corners, a jump ratio of 1/2 and p = 2 they all give the same shape, which the outline of
+the recursive triangles can be laid over to compare.
+Fractal Explanation: https://en.wikipedia.org/wiki/Sierpi%C5%84ski_triangle
+Source: https://en.wikipedia.org/wiki/Chaos_game
+*/
+
+pub struct SierpinskiTriangle {
+    generator: Generator,               // How the triangle is drawn
+    depth: usize,                       // The recursion level, also used for the outline
+    corners: usize,                     // The corners of the polygon in the chaos game
+    jump_ratio: f32,                    // How far a jump goes towards the chosen corner
+    point_count: usize,                 // The points plotted by the chaos game
+    color_by_corner: bool,              // If true, points get the color of their corner
+    seed: u64,                          // The seed of the random corners in the chaos game
+    rows: usize,                        // The number of rows of Pascal's triangle
+    prime: u32,                         // Pascal's triangle is taken mod this prime
+    color_residues: bool,               // If true, each residue mod p gets its own color
+    show_outline: bool,                 // If true, the recursive outline is drawn on top
+    color: Color32,                     // The color of the triangle
+    texture: Option<(TextureId, Vec2)>, // The rendered triangle and its size
+    last_input: Option<ImageInput>,     // The input of the rendered triangle
+}
+
+// The ways of drawing the triangle
+#[derive(Clone, Copy, PartialEq)]
+enum Generator {
+    Recursive,
+    ChaosGame,
+    Pascal,
+}
+
+impl Generator {
+    const ALL: [Generator; 3] = [
+        Generator::Recursive,
+        Generator::ChaosGame,
+        Generator::Pascal,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Generator::Recursive => "Recursive Subdivision",
+            Generator::ChaosGame => "Chaos Game",
+            Generator::Pascal => "Pascal's Triangle mod p",
+        }
+    }
+}
+
+// The primes that Pascal's triangle can be taken mod
+const PRIMES: [u32; 11] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
+
+// Everything the rendered triangle is made from: the generator, the depth, the corners, the jump
+// ratio, the point count, the coloring by corner, the seed, the rows, the prime, the coloring of
+// the residues, the color and the size of the image
+type ImageInput = (
+    Generator,
+    usize,
+    usize,
+    f32,
+    usize,
+    bool,
+    u64,
+    usize,
+    u32,
+    bool,
+    Color32,
+    Vec2,
+);
+
+impl Default for SierpinskiTriangle {
+    fn default() -> Self {
+        Self {
+            generator: Generator::Recursive,
+            depth: 6,
+            corners: 3,
+            jump_ratio: 0.5,
+            point_count: 50_000,
+            color_by_corner: false,
+            seed: 0,
+            rows: 64,
+            prime: 2,
+            color_residues: false,
+            show_outline: false,
+            color: Color32::from_rgb(0, 150, 199),
+            texture: None,
+            last_input: None,
+        }
+    }
+}
+
+impl epi::App for SierpinskiTriangle {
+    fn name(&self) -> &str {
+        "Sierpinski Triangle"
+    }
+
+    // Called every frame
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui, frame));
+    }
+}
+
+impl SierpinskiTriangle {
+    // Paints the fractal
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter, frame);
+
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+    }
+
+    // An options window for picking the generator and its settings
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ComboBox::from_label("Generator")
+            .selected_text(self.generator.name())
+            .show_ui(ui, |ui| {
+                for generator in Generator::ALL {
+                    ui.selectable_value(&mut self.generator, generator, generator.name());
+                }
+            });
+
+        match self.generator {
+            Generator::Recursive => {
+                ui.add(Slider::new(&mut self.depth, 0..=10).text("Depth"));
+                ui.label(format!(
+                    "{} triangles, dimension log(3)/log(2) = {:.4}",
+                    3_usize.pow(self.depth as u32),
+                    3_f64.ln() / 2_f64.ln()
+                ));
+            }
+            Generator::ChaosGame => {
+                ui.add(Slider::new(&mut self.corners, 3..=8).text("Corners"));
+                ui.add(Slider::new(&mut self.jump_ratio, 0.1..=0.9).text("Jump ratio"));
+                ui.add(
+                    Slider::new(&mut self.point_count, 1000..=200_000)
+                        .logarithmic(true)
+                        .text("Points"),
+                );
+                ui.checkbox(&mut self.color_by_corner, "Color by Corner");
+                super::seed_ui(ui, &mut self.seed);
+                // each jump shrinks the whole polygon towards a corner by 1 - ratio
+                let dimension = (self.corners as f64).ln() / -(1.0 - self.jump_ratio as f64).ln();
+                ui.label(format!(
+                    "Similarity dimension log({})/log(1/{:.2}) = {:.4}",
+                    self.corners,
+                    1.0 - self.jump_ratio,
+                    dimension
+                ));
+                if dimension > 2.0 {
+                    ui.label("The copies overlap, so the points fill the polygon");
+                }
+            }
+            Generator::Pascal => {
+                ui.add(Slider::new(&mut self.rows, 2..=256).text("Rows"));
+                ComboBox::from_label("Prime p")
+                    .selected_text(self.prime.to_string())
+                    .show_ui(ui, |ui| {
+                        for prime in PRIMES {
+                            ui.selectable_value(&mut self.prime, prime, prime.to_string());
+                        }
+                    });
+                ui.checkbox(&mut self.color_residues, "Color Each Residue");
+                // a block of p rows keeps the p(p + 1)/2 coefficients that are not 0 mod p
+                let p = self.prime as f64;
+                ui.label(format!(
+                    "Dimension log(p(p+1)/2)/log(p) = {:.4}",
+                    (p * (p + 1.0) / 2.0).ln() / p.ln()
+                ));
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Color");
+            ui.color_edit_button_srgba(&mut self.color);
+        });
+        ui.checkbox(&mut self.show_outline, "Show Recursive Outline");
+        if self.show_outline && self.generator != Generator::Recursive {
+            ui.add(Slider::new(&mut self.depth, 0..=10).text("Outline depth"));
+        }
+        // Reset would drop the texture without freeing it, so it's kept aside and put back for the
+        // next paint to free
+        let texture = self.texture.take();
+        egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    // The corners of a regular polygon with the first corner at the top, fitted in the rectangle
+    fn polygon(corners: usize, rect: Rect) -> Vec<Pos2> {
+        let unit: Vec<Pos2> = (0..corners)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * i as f32 / corners as f32;
+                pos2(angle.sin(), -angle.cos())
+            })
+            .collect();
+        let bounds = Rect::from_points(&unit);
+        let scale = 0.9 * (rect.width() / bounds.width()).min(rect.height() / bounds.height());
+        unit.iter()
+            .map(|&p| rect.center() + (p - bounds.center()) * scale)
+            .collect()
+    }
+
+    // A recursive function that calls draw on the triangles left after depth subdivisions
+    fn subdivide(triangle: [Pos2; 3], depth: usize, draw: &mut impl FnMut([Pos2; 3])) {
+        if depth == 0 {
+            draw(triangle);
+            return;
+        }
+        let [a, b, c] = triangle;
+        let (ab, bc, ca) = (a + (b - a) / 2.0, b + (c - b) / 2.0, c + (a - c) / 2.0);
+        Self::subdivide([a, ab, ca], depth - 1, draw);
+        Self::subdivide([ab, b, bc], depth - 1, draw);
+        Self::subdivide([ca, bc, c], depth - 1, draw);
+    }
+
+    // Plays the chaos game, plotting a small square at every point after the first jump
+    fn chaos_game(&self, rect: Rect, shapes: &mut Vec<Shape>) {
+        let corners = Self::polygon(self.corners, rect);
+        let colors: Vec<Color32> = (0..self.corners)
+            .map(|i| {
+                let (r, g, b) = palette::hsv_to_rgb(i as f32 / self.corners as f32, 0.7, 1.0);
+                Color32::from_rgb(r, g, b)
+            })
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut point = corners[0];
+        for _ in 0..self.point_count {
+            let corner = rng.gen_range(0..self.corners);
+            point += (corners[corner] - point) * self.jump_ratio;
+            let color = if self.color_by_corner {
+                colors[corner]
+            } else {
+                self.color
+            };
+            shapes.push(Shape::rect_filled(
+                Rect::from_center_size(point, vec2(1.0, 1.0)),
+                0.0,
+                color,
+            ));
+        }
+    }
+
+    // Draws a cell for every coefficient of Pascal's triangle that is not divisible by the prime
+    fn pascal(&self, rect: Rect, shapes: &mut Vec<Shape>) {
+        let [top, left, right] = match Self::polygon(3, rect)[..] {
+            [top, right, left] => [top, left, right],
+            _ => unreachable!(),
+        };
+        let cell = vec2(
+            (right.x - left.x) / self.rows as f32,
+            (left.y - top.y) / self.rows as f32,
+        );
+
+        // each row is worked out mod p from the one above it
+        let mut row = vec![1_u32];
+        for r in 0..self.rows {
+            for (k, &value) in row.iter().enumerate() {
+                if value == 0 {
+                    continue;
+                }
+                let color = if self.color_residues {
+                    let hue = (value - 1) as f32 / (self.prime - 1).max(1) as f32 * 0.8;
+                    let (r, g, b) = palette::hsv_to_rgb(hue, 0.7, 1.0);
+                    Color32::from_rgb(r, g, b)
+                } else {
+                    self.color
+                };
+                let center = pos2(
+                    top.x + (k as f32 - r as f32 / 2.0) * cell.x,
+                    top.y + (r as f32 + 0.5) * cell.y,
+                );
+                shapes.push(Shape::rect_filled(
+                    Rect::from_center_size(center, cell),
+                    0.0,
+                    color,
+                ));
+            }
+            let mut next = vec![1_u32; r + 2];
+            for k in 1..=r {
+                next[k] = (row[k - 1] + row[k]) % self.prime;
+            }
+            row = next;
+        }
+    }
+
+    // The triangle with the first corner at the top, fitted in the rectangle
+    fn triangle(rect: Rect) -> [Pos2; 3] {
+        match Self::polygon(3, rect)[..] {
+            [a, b, c] => [a, b, c],
+            _ => unreachable!(),
+        }
+    }
+
+    // Generates the shapes of the chosen generator in the center of the given rectangle
+    fn generator_shapes(&self, rect: Rect) -> Vec<Shape> {
+        let mut shapes = Vec::new();
+        match self.generator {
+            Generator::Recursive => Self::subdivide(Self::triangle(rect), self.depth, &mut |t| {
+                shapes.push(Shape::convex_polygon(
+                    t.to_vec(),
+                    self.color,
+                    Stroke::none(),
+                ));
+            }),
+            Generator::ChaosGame => self.chaos_game(rect, &mut shapes),
+            Generator::Pascal => self.pascal(rect, &mut shapes),
+        }
+        shapes
+    }
+
+    // The outline of the recursive triangles, if it is shown
+    fn outline_shapes(&self, rect: Rect) -> Vec<Shape> {
+        let mut shapes = Vec::new();
+        if self.show_outline {
+            let stroke = Stroke::new(1.0, Color32::WHITE);
+            Self::subdivide(Self::triangle(rect), self.depth, &mut |t| {
+                shapes.push(Shape::closed_line(t.to_vec(), stroke));
+            });
+        }
+        shapes
+    }
+
+    // The chaos game and Pascal's triangle can have hundreds of thousands of cells, so the
+    // triangle is drawn into a texture once and only drawn again when the settings change
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
+        let rect = painter.clip_rect();
+        let size = rect.size().round();
+        let input = (
+            self.generator,
+            self.depth,
+            self.corners,
+            self.jump_ratio,
+            self.point_count,
+            self.color_by_corner,
+            self.seed,
+            self.rows,
+            self.prime,
+            self.color_residues,
+            self.color,
+            size,
+        );
+
+        // Need to recalculate due to a change in one of the settings
+        if self.last_input.as_ref() != Some(&input) {
+            let shapes = self.generator_shapes(Rect::from_min_size(Pos2::ZERO, size));
+            let image = Image::from_shapes(
+                size.x as usize,
+                size.y as usize,
+                Color32::TRANSPARENT,
+                &shapes,
+            );
+            if let Some((texture, _)) = self.texture.take() {
+                frame.tex_allocator().free(texture);
+            }
+            let texture = frame
+                .tex_allocator()
+                .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+            self.texture = Some((texture, size));
+            self.last_input = Some(input);
+        }
+
+        if let Some((texture, size)) = self.texture {
+            let mut mesh = epaint::Mesh::with_texture(texture);
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+        painter.extend(self.outline_shapes(Rect::from_min_size(rect.min, size)));
+    }
+}
+
+impl Animate for SierpinskiTriangle {
+    fn parameters(&self) -> Vec<Parameter> {
+        vec![
+            Parameter::new("Depth", self.depth as f32, 0.0..=10.0),
+            Parameter::new("Corners", self.corners as f32, 3.0..=8.0),
+            Parameter::new("Jump ratio", self.jump_ratio, 0.1..=0.9),
+            Parameter::new("Points", self.point_count as f32, 1000.0..=200_000.0),
+            Parameter::new("Rows", self.rows as f32, 2.0..=256.0),
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "Depth" => self.depth = value.round() as usize,
+            "Corners" => self.corners = value.round() as usize,
+            "Jump ratio" => self.jump_ratio = value,
+            "Points" => self.point_count = value.round() as usize,
+            "Rows" => self.rows = value.round() as usize,
+            _ => {}
+        }
+    }
+}
+
+impl Render for SierpinskiTriangle {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        let mut shapes = self.generator_shapes(rect);
+        shapes.extend(self.outline_shapes(rect));
+        Image::from_shapes(width, height, Color32::BLACK, &shapes)
+    }
+}
+
+impl FractalApp for SierpinskiTriangle {
+    // The triangle is 3 copies of itself at half the size. The chaos game makes one copy per
+    // corner, scaled by how far a jump doesn't go, and Pascal's triangle mod p has p (p + 1) / 2
+    // copies at 1 / p of the size.
+    fn known_dimension(&self) -> Option<KnownDimension> {
+        let (copies, scale) = match self.generator {
+            Generator::Recursive => (3.0, 2.0),
+            Generator::ChaosGame => (self.corners as f64, 1.0 / (1.0 - self.jump_ratio as f64)),
+            Generator::Pascal => {
+                let prime = self.prime as f64;
+                (prime * (prime + 1.0) / 2.0, prime)
+            }
+        };
+        Some(KnownDimension {
+            formula: format!("log {} / log {}", copies, (scale * 1000.0).round() / 1000.0),
+            value: (copies.ln() / scale.ln()).min(2.0),
+            counted: Counted::Drawn,
+        })
+    }
+}
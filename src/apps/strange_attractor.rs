@@ -0,0 +1,848 @@
+use super::FractalApp;
+use crate::palette;
+use crate::raster::{Image, Render};
+use crate::timeline::{Animate, Parameter};
+use eframe::{egui, epi};
+use egui::{containers::*, widgets::*, *};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[derive(PartialEq)]
+
+/*
+This file contains the explorer for strange attractors, the shapes that chaotic dynamical
+systems settle on. Flows (Lorenz and Rössler) are differential equations in 3D that are
+integrated with Runge-Kutta, either RK4 with a fixed time step or the adaptive Dormand-Prince
+RK45, which picks every step so that its error stays under a tolerance. Maps (Clifford, De Jong,
+Hénon, Ikeda and Tinkerbell) are iterated in the plane. Either way, every pixel counts how often
+the orbit passes over it, and the counts are shown on a log scale.
+The largest Lyapunov exponent says how fast two orbits that start next to each other move apart.
+It is estimated by following a second point a tiny distance away and pulling it back after every
+step. A positive exponent means the system is chaotic, so random parameters are tried until one
+is found whose orbit stays bounded with a positive exponent.
+Fractal Explanation: https://en.wikipedia.org/wiki/Attractor#Strange_attractor
+Source: https://sprott.physics.wisc.edu/sa.htm
+*/
+
+pub struct StrangeAttractor {
+    dynamics: Dynamics,                 // The system, its parameters and integrator
+    iterations: usize,                  // The number of points of a map that are plotted
+    duration: f64,                      // How long a flow is followed for
+    yaw: f32,                           // The angle (in degrees) a flow is turned by
+    pitch: f32,                         // The angle (in degrees) the flow is tilted by
+    zoom: f32,                          // Zooms into the middle of the attractor
+    brightness: f32,                    // Brightens rarely hit pixels, 1 is a plain log scale
+    seed: u64,                          // The seed of the search for chaotic parameters
+    last_seed: u64,                     // Used to determine if the seed was changed
+    search_failed: bool,                // True if the last search found nothing
+    exponent: Option<f64>,              // The Lyapunov exponent, None if the orbit escapes
+    last_dynamics: Option<Dynamics>,    // The dynamics the exponent was estimated for
+    texture: Option<(TextureId, Vec2)>, // The rendered attractor and its size
+    last_input: Option<ImageInput>,     // The input of the rendered attractor
+}
+
+// The dynamical systems, flows are integrated in 3D and maps are iterated in the plane
+#[derive(Clone, Copy, PartialEq)]
+enum System {
+    Lorenz,
+    Rossler,
+    Clifford,
+    DeJong,
+    Henon,
+    Ikeda,
+    Tinkerbell,
+}
+
+// A parameter of a system: its name, its classic value and the range of its slider
+type Coefficient = (&'static str, f64, f64, f64);
+
+impl System {
+    const ALL: [System; 7] = [
+        System::Lorenz,
+        System::Rossler,
+        System::Clifford,
+        System::DeJong,
+        System::Henon,
+        System::Ikeda,
+        System::Tinkerbell,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            System::Lorenz => "Lorenz",
+            System::Rossler => "Rössler",
+            System::Clifford => "Clifford",
+            System::DeJong => "De Jong",
+            System::Henon => "Hénon",
+            System::Ikeda => "Ikeda",
+            System::Tinkerbell => "Tinkerbell",
+        }
+    }
+
+    fn is_flow(self) -> bool {
+        matches!(self, System::Lorenz | System::Rossler)
+    }
+
+    // The parameters of the system, the classic values give the well known attractors
+    fn coefficients(self) -> &'static [Coefficient] {
+        match self {
+            System::Lorenz => &[
+                ("Sigma", 10.0, 0.0, 20.0),
+                ("Rho", 28.0, 0.0, 60.0),
+                ("Beta", 8.0 / 3.0, 0.0, 6.0),
+            ],
+            System::Rossler => &[
+                ("a", 0.2, 0.0, 0.5),
+                ("b", 0.2, 0.0, 2.0),
+                ("c", 5.7, 1.0, 20.0),
+            ],
+            System::Clifford => &[
+                ("a", -1.4, -3.0, 3.0),
+                ("b", 1.6, -3.0, 3.0),
+                ("c", 1.0, -3.0, 3.0),
+                ("d", 0.7, -3.0, 3.0),
+            ],
+            System::DeJong => &[
+                ("a", 1.4, -3.0, 3.0),
+                ("b", -2.3, -3.0, 3.0),
+                ("c", 2.4, -3.0, 3.0),
+                ("d", -2.1, -3.0, 3.0),
+            ],
+            System::Henon => &[("a", 1.4, 0.0, 2.0), ("b", 0.3, -1.0, 1.0)],
+            System::Ikeda => &[("u", 0.9, 0.0, 1.0)],
+            System::Tinkerbell => &[
+                ("a", 0.9, -1.5, 1.5),
+                ("b", -0.6013, -1.5, 1.5),
+                ("c", 2.0, -1.0, 3.0),
+                ("d", 0.5, -1.0, 1.5),
+            ],
+        }
+    }
+
+    // The point the orbit starts from
+    fn start(self) -> [f64; 3] {
+        match self {
+            System::Lorenz => [1.0, 1.0, 1.0],
+            System::Tinkerbell => [-0.72, -0.64, 0.0],
+            _ => [0.1, 0.1, 0.0],
+        }
+    }
+
+    // The view (yaw and pitch) that shows the attractor of a flow best
+    fn view(self) -> (f32, f32) {
+        match self {
+            System::Rossler => (0.0, 45.0),
+            _ => (0.0, 0.0),
+        }
+    }
+
+    // The velocity of a flow at a point
+    fn velocity(self, p: [f64; 3], k: &[f64; 4]) -> [f64; 3] {
+        let [x, y, z] = p;
+        match self {
+            System::Lorenz => [k[0] * (y - x), x * (k[1] - z) - y, x * y - k[2] * z],
+            System::Rossler => [-y - z, x + k[0] * y, k[1] + z * (x - k[2])],
+            _ => [0.0, 0.0, 0.0],
+        }
+    }
+
+    // The next point of a map, z is not used
+    fn iterate(self, p: [f64; 3], k: &[f64; 4]) -> [f64; 3] {
+        let [x, y, _] = p;
+        let [a, b, c, d] = *k;
+        let (x, y) = match self {
+            System::Clifford => (
+                (a * y).sin() + c * (a * x).cos(),
+                (b * x).sin() + d * (b * y).cos(),
+            ),
+            System::DeJong => ((a * y).sin() - (b * x).cos(), (c * x).sin() - (d * y).cos()),
+            System::Henon => (1.0 - a * x * x + y, b * x),
+            System::Ikeda => {
+                let t = 0.4 - 6.0 / (1.0 + x * x + y * y);
+                (
+                    1.0 + a * (x * t.cos() - y * t.sin()),
+                    a * (x * t.sin() + y * t.cos()),
+                )
+            }
+            System::Tinkerbell => (x * x - y * y + a * x + b * y, 2.0 * x * y + c * x + d * y),
+            _ => (x, y),
+        };
+        [x, y, 0.0]
+    }
+}
+
+// How a flow is integrated
+#[derive(Clone, Copy, PartialEq)]
+enum Integrator {
+    Rk4,
+    Rk45,
+}
+
+impl Integrator {
+    const ALL: [Integrator; 2] = [Integrator::Rk4, Integrator::Rk45];
+
+    fn name(self) -> &'static str {
+        match self {
+            Integrator::Rk4 => "RK4 (fixed step)",
+            Integrator::Rk45 => "RK45 (adaptive)",
+        }
+    }
+}
+
+// Everything the orbit is made from: the system, its parameters and the integration settings
+#[derive(Clone, Copy, PartialEq)]
+struct Dynamics {
+    system: System,
+    parameters: [f64; 4], // The values of the system's coefficients, unused ones are 0
+    integrator: Integrator,
+    step: f64,      // The time step of RK4, and the first step of RK45
+    tolerance: f64, // The largest error RK45 allows in a step
+}
+
+// The Dormand-Prince tableau: the weights of the earlier stages in every stage, and the weights
+// of the 5th and 4th order solutions. The flows don't depend on time, so the nodes aren't needed.
+const DP_STAGES: [[f64; 6]; 7] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+const DP_FIFTH: [f64; 7] = [
+    35.0 / 384.0,
+    0.0,
+    500.0 / 1113.0,
+    125.0 / 192.0,
+    -2187.0 / 6784.0,
+    11.0 / 84.0,
+    0.0,
+];
+const DP_FOURTH: [f64; 7] = [
+    5179.0 / 57600.0,
+    0.0,
+    7571.0 / 16695.0,
+    393.0 / 640.0,
+    -92097.0 / 339200.0,
+    187.0 / 2100.0,
+    1.0 / 40.0,
+];
+
+// The range of the steps RK45 can take, so it neither stalls nor jumps over the attractor
+const MIN_STEP: f64 = 1e-7;
+const MAX_STEP: f64 = 0.1;
+
+// The most steps a flow takes, so a tiny tolerance can't freeze the app
+const MAX_FLOW_STEPS: usize = 5_000_000;
+
+// The start of the orbit that is skipped, while it is still on its way to the attractor
+const TRANSIENT_ITERATIONS: usize = 1_000;
+const TRANSIENT_TIME: f64 = 100.0;
+
+// How far apart the two points of the Lyapunov exponent estimate are kept
+const SEPARATION: f64 = 1e-8;
+
+// How long the orbits are followed for the shown exponent (iterations or time) and when
+// searching, where many parameters have to be tried quickly
+const EXPONENT_LENGTH: (f64, f64) = (20_000.0, 200.0);
+const SEARCH_LENGTH: (f64, f64) = (2_000.0, 40.0);
+
+// The parameters tried by one search, and the smallest exponent that counts as chaos
+const SEARCH_ATTEMPTS: usize = 500;
+const CHAOS_THRESHOLD: f64 = 0.01;
+
+// Orbits that get this far from the origin are taken to escape to infinity
+const ESCAPE_RADIUS: f64 = 1e6;
+
+// The colors of the attractor, from rarely to often visited pixels
+const COLORS: [Color32; 4] = [
+    Color32::from_rgb(40, 30, 140),
+    Color32::from_rgb(190, 40, 150),
+    Color32::from_rgb(255, 150, 40),
+    Color32::from_rgb(255, 250, 210),
+];
+
+// Everything the rendered attractor is made from: the dynamics, the length of the orbit, the
+// view (yaw, pitch and zoom), the brightness and the size of the image
+type ImageInput = (Dynamics, usize, f64, f32, f32, f32, f32, Vec2);
+
+impl Dynamics {
+    fn new(system: System) -> Self {
+        let mut parameters = [0.0; 4];
+        for (value, coefficient) in parameters.iter_mut().zip(system.coefficients()) {
+            *value = coefficient.1;
+        }
+        Self {
+            system,
+            parameters,
+            integrator: Integrator::Rk4,
+            step: 0.005,
+            tolerance: 1e-6,
+        }
+    }
+
+    fn velocity(&self, p: [f64; 3]) -> [f64; 3] {
+        self.system.velocity(p, &self.parameters)
+    }
+
+    // The classic fourth order Runge-Kutta step
+    fn rk4(&self, p: [f64; 3], h: f64) -> [f64; 3] {
+        let k1 = self.velocity(p);
+        let k2 = self.velocity(offset(p, h / 2.0, &[(1.0, k1)]));
+        let k3 = self.velocity(offset(p, h / 2.0, &[(1.0, k2)]));
+        let k4 = self.velocity(offset(p, h, &[(1.0, k3)]));
+        offset(p, h / 6.0, &[(1.0, k1), (2.0, k2), (2.0, k3), (1.0, k4)])
+    }
+
+    // A Dormand-Prince step, returns the 5th order solution and the size of its error
+    fn dormand_prince(&self, p: [f64; 3], h: f64) -> ([f64; 3], f64) {
+        let mut k = [[0.0; 3]; 7];
+        for stage in 0..7 {
+            let terms: Vec<(f64, [f64; 3])> =
+                (0..stage).map(|j| (DP_STAGES[stage][j], k[j])).collect();
+            k[stage] = self.velocity(offset(p, h, &terms));
+        }
+        let weighted = |weights: &[f64; 7]| {
+            let terms: Vec<(f64, [f64; 3])> = weights.iter().copied().zip(k).collect();
+            offset(p, h, &terms)
+        };
+        let (fifth, fourth) = (weighted(&DP_FIFTH), weighted(&DP_FOURTH));
+        let error = (0..3)
+            .map(|i| (fifth[i] - fourth[i]).abs())
+            .fold(0.0, f64::max);
+        (fifth, error)
+    }
+
+    // A step of the chosen integrator with a given size
+    fn fixed_step(&self, p: [f64; 3], h: f64) -> [f64; 3] {
+        match self.integrator {
+            Integrator::Rk4 => self.rk4(p, h),
+            Integrator::Rk45 => self.dormand_prince(p, h).0,
+        }
+    }
+
+    // Moves the orbit one step on, returns the new point and how far it moved (one iteration for
+    // maps, a time for flows). RK45 shrinks the step h until its error is under the tolerance,
+    // then lets the next step grow again.
+    fn advance(&self, p: [f64; 3], h: &mut f64) -> ([f64; 3], f64) {
+        if !self.system.is_flow() {
+            return (self.system.iterate(p, &self.parameters), 1.0);
+        }
+        match self.integrator {
+            Integrator::Rk4 => (self.rk4(p, self.step), self.step),
+            Integrator::Rk45 => loop {
+                let (next, error) = self.dormand_prince(p, *h);
+                let factor = (0.9 * (self.tolerance / error.max(1e-300)).powf(0.2)).clamp(0.2, 5.0);
+                if error <= self.tolerance || *h <= MIN_STEP {
+                    let taken = *h;
+                    *h = (*h * factor).clamp(MIN_STEP, MAX_STEP);
+                    return (next, taken);
+                }
+                *h = (*h * factor).max(MIN_STEP);
+            },
+        }
+    }
+
+    // The length of an orbit, in iterations for maps and in time for flows
+    fn length(&self, lengths: (f64, f64)) -> f64 {
+        if self.system.is_flow() {
+            lengths.1
+        } else {
+            lengths.0
+        }
+    }
+
+    // Follows the orbit for the given length after the transient, calling visit with every
+    // point. Returns false if the orbit escaped to infinity.
+    fn orbit(&self, length: f64, mut visit: impl FnMut([f64; 3])) -> bool {
+        let transient = if self.system.is_flow() {
+            TRANSIENT_TIME
+        } else {
+            TRANSIENT_ITERATIONS as f64
+        };
+        let mut p = self.system.start();
+        let mut h = self.step;
+        let mut time = 0.0;
+        for _ in 0..MAX_FLOW_STEPS.max(length as usize + TRANSIENT_ITERATIONS) {
+            if time >= transient + length {
+                break;
+            }
+            let (next, taken) = self.advance(p, &mut h);
+            if escaped(next) {
+                return false;
+            }
+            p = next;
+            time += taken;
+            if time > transient {
+                visit(p);
+            }
+        }
+        true
+    }
+
+    // Estimates the largest Lyapunov exponent (per iteration for maps, per time for flows) by
+    // following a second point SEPARATION away and measuring how much the gap between them
+    // grows in every step, before pulling it back. None if the orbit escapes to infinity.
+    fn lyapunov(&self, length: f64) -> Option<f64> {
+        let mut p = self.system.start();
+        let mut q = offset(p, SEPARATION, &[(1.0, [1.0, 0.0, 0.0])]);
+        let mut h = self.step;
+        let (mut time, mut measured, mut sum) = (0.0, 0.0, 0.0);
+        let transient = if self.system.is_flow() {
+            TRANSIENT_TIME
+        } else {
+            TRANSIENT_ITERATIONS as f64
+        };
+        for _ in 0..MAX_FLOW_STEPS {
+            if time >= transient + length {
+                break;
+            }
+            let (next, taken) = self.advance(p, &mut h);
+            let neighbor = if self.system.is_flow() {
+                self.fixed_step(q, taken)
+            } else {
+                self.system.iterate(q, &self.parameters)
+            };
+            if escaped(next) || escaped(neighbor) {
+                return None;
+            }
+
+            let gap = [0, 1, 2].map(|i| neighbor[i] - next[i]);
+            let distance = gap.iter().map(|g| g * g).sum::<f64>().sqrt().max(1e-300);
+            time += taken;
+            if time > transient {
+                sum += (distance / SEPARATION).ln();
+                measured += taken;
+            }
+            q = offset(next, SEPARATION / distance, &[(1.0, gap)]);
+            p = next;
+        }
+        (measured > 0.0).then(|| sum / measured)
+    }
+}
+
+// p + h * (the weighted sum of the vectors)
+fn offset(p: [f64; 3], h: f64, terms: &[(f64, [f64; 3])]) -> [f64; 3] {
+    let mut result = p;
+    for (weight, v) in terms {
+        for i in 0..3 {
+            result[i] += h * weight * v[i];
+        }
+    }
+    result
+}
+
+fn escaped(p: [f64; 3]) -> bool {
+    p.iter().any(|c| !c.is_finite() || c.abs() > ESCAPE_RADIUS)
+}
+
+impl Default for StrangeAttractor {
+    fn default() -> Self {
+        Self {
+            dynamics: Dynamics::new(System::Lorenz),
+            iterations: 1_000_000,
+            duration: 500.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            zoom: 1.0,
+            brightness: 1.5,
+            seed: 0,
+            last_seed: 0,
+            search_failed: false,
+            exponent: None,
+            last_dynamics: None,
+            texture: None,
+            last_input: None,
+        }
+    }
+}
+
+impl epi::App for StrangeAttractor {
+    fn name(&self) -> &str {
+        "Strange Attractors"
+    }
+
+    // Called every frame
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        egui::CentralPanel::default()
+            .frame(Frame::dark_canvas(&ctx.style()))
+            .show(ctx, |ui| self.ui(ui, frame));
+    }
+}
+
+impl StrangeAttractor {
+    // Paints the fractal
+    pub fn ui(&mut self, ui: &mut Ui, frame: &mut epi::Frame<'_>) {
+        let painter = Painter::new(
+            ui.ctx().clone(),
+            ui.layer_id(),
+            ui.available_rect_before_wrap(),
+        );
+        self.paint(&painter, frame);
+
+        // Make sure we allocate what we used (everything)
+        ui.expand_to_include_rect(painter.clip_rect());
+
+        Frame::popup(ui.style())
+            .stroke(Stroke::none())
+            .show(ui, |ui| {
+                ui.set_max_width(270.0);
+                CollapsingHeader::new("Settings").show(ui, |ui| self.options_ui(ui));
+            });
+
+        // After the settings, so that their sliders get dragged instead of the attractor
+        if self.dynamics.system.is_flow() {
+            self.rotate(ui, painter.clip_rect());
+        }
+    }
+
+    // An options window for the system, its parameters and the view
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let system = self.dynamics.system;
+        ComboBox::from_label("System")
+            .selected_text(system.name())
+            .show_ui(ui, |ui| {
+                for choice in System::ALL {
+                    if ui
+                        .selectable_label(system == choice, choice.name())
+                        .clicked()
+                    {
+                        self.load_system(choice);
+                    }
+                }
+            });
+
+        for (i, (name, _, min, max)) in system.coefficients().iter().enumerate() {
+            ui.add(Slider::new(&mut self.dynamics.parameters[i], *min..=*max).text(*name));
+        }
+        if ui.button("Classic Parameters").clicked() {
+            self.dynamics.parameters = Dynamics::new(system).parameters;
+        }
+        ui.label("Find chaotic parameters:");
+        super::seed_ui(ui, &mut self.seed);
+        if self.search_failed {
+            ui.label(format!(
+                "No chaotic parameters in {} tries, try another seed",
+                SEARCH_ATTEMPTS
+            ));
+        }
+        ui.label(match self.exponent {
+            Some(exponent) if exponent > CHAOS_THRESHOLD => {
+                format!("Lyapunov exponent: {:.3} (chaotic)", exponent)
+            }
+            Some(exponent) => format!("Lyapunov exponent: {:.3} (not chaotic)", exponent),
+            None => "The orbit escapes to infinity".to_owned(),
+        });
+
+        ui.separator();
+        if system.is_flow() {
+            ComboBox::from_label("Integrator")
+                .selected_text(self.dynamics.integrator.name())
+                .show_ui(ui, |ui| {
+                    for integrator in Integrator::ALL {
+                        ui.selectable_value(
+                            &mut self.dynamics.integrator,
+                            integrator,
+                            integrator.name(),
+                        );
+                    }
+                });
+            ui.add(
+                Slider::new(&mut self.dynamics.step, 0.0005..=0.05)
+                    .logarithmic(true)
+                    .text("Time step"),
+            );
+            if self.dynamics.integrator == Integrator::Rk45 {
+                ui.add(
+                    Slider::new(&mut self.dynamics.tolerance, 1e-10..=1e-3)
+                        .logarithmic(true)
+                        .text("Tolerance"),
+                );
+            }
+            ui.add(
+                Slider::new(&mut self.duration, 10.0..=5_000.0)
+                    .logarithmic(true)
+                    .text("Duration"),
+            );
+            ui.add(Slider::new(&mut self.yaw, -180.0..=180.0).text("Yaw"));
+            ui.add(Slider::new(&mut self.pitch, -90.0..=90.0).text("Pitch"));
+        } else {
+            ui.add(
+                Slider::new(&mut self.iterations, 10_000..=5_000_000)
+                    .logarithmic(true)
+                    .text("Iterations"),
+            );
+        }
+        ui.add(Slider::new(&mut self.zoom, 0.5..=10.0).text("Zoom"));
+        ui.add(Slider::new(&mut self.brightness, 0.2..=4.0).text("Brightness"));
+        // Reset would lose the texture without freeing it, so it survives the reset and the next
+        // paint frees it
+        let texture = self.texture.take();
+        egui::reset_button(ui, self);
+        self.texture = texture;
+    }
+
+    // Switches to another system with its classic parameters
+    fn load_system(&mut self, system: System) {
+        let dynamics = Dynamics::new(system);
+        self.dynamics.system = system;
+        self.dynamics.parameters = dynamics.parameters;
+        let (yaw, pitch) = system.view();
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.search_failed = false;
+    }
+
+    // Dragging turns a flow around
+    fn rotate(&mut self, ui: &mut Ui, rect: Rect) {
+        let response = ui.interact(rect, ui.id().with("attractor_view"), Sense::drag());
+        if response.dragged() {
+            let delta = response.drag_delta();
+            self.yaw = (self.yaw + delta.x * 0.5 + 180.0).rem_euclid(360.0) - 180.0;
+            self.pitch = (self.pitch + delta.y * 0.5).clamp(-90.0, 90.0);
+        }
+    }
+
+    // Tries random parameters until the orbit stays bounded and its Lyapunov exponent is positive
+    fn search(&self) -> Option<[f64; 4]> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let coefficients = self.dynamics.system.coefficients();
+        for _ in 0..SEARCH_ATTEMPTS {
+            let mut candidate = self.dynamics;
+            for (value, (_, _, min, max)) in candidate.parameters.iter_mut().zip(coefficients) {
+                *value = rng.gen_range(*min..=*max);
+            }
+            let exponent = candidate.lyapunov(candidate.length(SEARCH_LENGTH));
+            if exponent.is_some_and(|exponent| exponent > CHAOS_THRESHOLD) {
+                return Some(candidate.parameters);
+            }
+        }
+        None
+    }
+
+    // Searches again if the seed was changed, and estimates the exponent of new parameters
+    fn update_dynamics(&mut self) {
+        if self.seed != self.last_seed {
+            let found = self.search();
+            if let Some(parameters) = found {
+                self.dynamics.parameters = parameters;
+            }
+            self.search_failed = found.is_none();
+            self.last_seed = self.seed;
+        }
+
+        if self.last_dynamics != Some(self.dynamics) {
+            self.exponent = self
+                .dynamics
+                .lyapunov(self.dynamics.length(EXPONENT_LENGTH));
+            self.last_dynamics = Some(self.dynamics);
+        }
+    }
+
+    // Projects a point of the orbit onto the plane. Flows are turned by the yaw around their z
+    // axis and then tilted by the pitch, maps are already flat.
+    fn project(&self, p: [f64; 3]) -> [f64; 2] {
+        if !self.dynamics.system.is_flow() {
+            return [p[0], p[1]];
+        }
+        let (ys, yc) = (self.yaw as f64).to_radians().sin_cos();
+        let (ps, pc) = (self.pitch as f64).to_radians().sin_cos();
+        let x = p[0] * yc - p[1] * ys;
+        let y = p[0] * ys + p[1] * yc;
+        [x, p[2] * pc + y * ps]
+    }
+
+    // Renders the attractor by counting how often the orbit passes over every pixel. The points
+    // of a flow are joined by lines, so that the curve doesn't fall apart into dots.
+    fn attractor_image(&self, rect: Rect, background: Color32) -> Image {
+        let (width, height) = (rect.width() as usize, rect.height() as usize);
+        let mut image = Image::new(width, height, background);
+        let flow = self.dynamics.system.is_flow();
+        let length = if flow {
+            self.duration
+        } else {
+            self.iterations as f64
+        };
+
+        // the first pass finds the bounds of the attractor on the screen
+        let mut bounds = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+        let bounded = self.dynamics.orbit(length, |p| {
+            let [x, y] = self.project(p);
+            bounds = [
+                bounds[0].min(x),
+                bounds[1].min(y),
+                bounds[2].max(x),
+                bounds[3].max(y),
+            ];
+        });
+        let size = (bounds[2] - bounds[0]).max(bounds[3] - bounds[1]);
+        if !(bounded && size.is_finite() && size > 1e-12) {
+            return image;
+        }
+        let scale = 0.9
+            * self.zoom as f64
+            * (width as f64 / (bounds[2] - bounds[0]).max(size * 1e-3))
+                .min(height as f64 / (bounds[3] - bounds[1]).max(size * 1e-3));
+        let center = [(bounds[0] + bounds[2]) / 2.0, (bounds[1] + bounds[3]) / 2.0];
+        let to_pixel = |p: [f64; 2]| {
+            [
+                width as f64 / 2.0 + (p[0] - center[0]) * scale,
+                height as f64 / 2.0 - (p[1] - center[1]) * scale,
+            ]
+        };
+
+        // the second pass counts the visits
+        let mut counts = vec![0_u32; width * height];
+        let mut visit = |x: f64, y: f64| {
+            if x >= 0.0 && y >= 0.0 && (x as usize) < width && (y as usize) < height {
+                counts[y as usize * width + x as usize] += 1;
+            }
+        };
+        let mut last: Option<[f64; 2]> = None;
+        self.dynamics.orbit(length, |p| {
+            let [x, y] = to_pixel(self.project(p));
+            match last {
+                Some([lx, ly]) if flow => {
+                    let steps = (x - lx).abs().max((y - ly).abs()).ceil().clamp(1.0, 4096.0);
+                    for i in 1..=steps as usize {
+                        let t = i as f64 / steps;
+                        visit(lx + (x - lx) * t, ly + (y - ly) * t);
+                    }
+                }
+                _ => visit(x, y),
+            }
+            last = Some([x, y]);
+        });
+
+        let max = counts.iter().copied().max().unwrap_or(0) as f32;
+        if max == 0.0 {
+            return image;
+        }
+        for (i, &count) in counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let level = ((count as f32).ln_1p() / max.ln_1p()).powf(1.0 / self.brightness);
+            let color = palette::gradient(&COLORS, level);
+            let shade = |channel: u8| (channel as f32 * level).round() as u8;
+            let color = Color32::from_rgba_premultiplied(
+                shade(color.r()),
+                shade(color.g()),
+                shade(color.b()),
+                shade(255),
+            );
+            image.blend((i % width) as i64, (i / width) as i64, color, 1.0);
+        }
+        image
+    }
+
+    fn paint(&mut self, painter: &Painter, frame: &mut epi::Frame<'_>) {
+        self.update_dynamics();
+
+        let rect = painter.clip_rect();
+        let size = rect.size().round();
+        let input = (
+            self.dynamics,
+            self.iterations,
+            self.duration,
+            self.yaw,
+            self.pitch,
+            self.zoom,
+            self.brightness,
+            size,
+        );
+
+        // Need to recalculate due to a change in one of the settings
+        if self.last_input.as_ref() != Some(&input) {
+            let image =
+                self.attractor_image(Rect::from_min_size(rect.min, size), Color32::TRANSPARENT);
+            if let Some((texture, _)) = self.texture.take() {
+                frame.tex_allocator().free(texture);
+            }
+            let texture = frame
+                .tex_allocator()
+                .alloc_srgba_premultiplied((image.width, image.height), &image.pixels);
+            self.texture = Some((texture, size));
+            self.last_input = Some(input);
+        }
+
+        if let Some((texture, size)) = self.texture {
+            let mut mesh = epaint::Mesh::with_texture(texture);
+            let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+            mesh.add_rect_with_uv(Rect::from_min_size(rect.min, size), uv, Color32::WHITE);
+            painter.add(Shape::mesh(mesh));
+        }
+    }
+}
+
+impl Animate for StrangeAttractor {
+    // The system's own parameters can be animated too, which morphs one attractor into another
+    fn parameters(&self) -> Vec<Parameter> {
+        let system = self.dynamics.system;
+        let mut parameters: Vec<Parameter> = system
+            .coefficients()
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _, min, max))| {
+                Parameter::new(
+                    name,
+                    self.dynamics.parameters[i] as f32,
+                    *min as f32..=*max as f32,
+                )
+            })
+            .collect();
+        if system.is_flow() {
+            parameters.push(Parameter::new("Yaw", self.yaw, -180.0..=180.0));
+            parameters.push(Parameter::new("Pitch", self.pitch, -90.0..=90.0));
+        }
+        parameters.push(Parameter::new("Zoom", self.zoom, 0.5..=10.0));
+        parameters.push(Parameter::new("Brightness", self.brightness, 0.2..=4.0));
+        parameters
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        let coefficients = self.dynamics.system.coefficients();
+        if let Some(i) = coefficients.iter().position(|c| c.0 == name) {
+            self.dynamics.parameters[i] = value as f64;
+            return;
+        }
+        match name {
+            "Yaw" => self.yaw = value,
+            "Pitch" => self.pitch = value,
+            "Zoom" => self.zoom = value,
+            "Brightness" => self.brightness = value,
+            _ => {}
+        }
+    }
+}
+
+impl Render for StrangeAttractor {
+    fn render(&mut self, width: usize, height: usize) -> Image {
+        self.update_dynamics();
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(width as f32, height as f32));
+        self.attractor_image(rect, Color32::BLACK)
+    }
+}
+
+impl FractalApp for StrangeAttractor {}
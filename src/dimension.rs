@@ -0,0 +1,571 @@
+use crate::apps::FractalApp;
+use crate::raster::Image;
+use eframe::egui;
+use egui::{widgets::*, *};
+use std::ops::RangeInclusive;
+
+/*
+This file contains a box-counting estimator for the dimension of the fractals. It works on the
+image that an app renders for the exporter, so every fractal can be measured the same way: the
+image is covered with square boxes of 1, 2, 4... pixels, and the boxes that contain part of the
+fractal are counted. A fractal of dimension D needs about (1 / size)^D boxes, so the slope of
+log(count) against log(1 / size) is the dimension. The smallest boxes only see the pixels and
+the thickness of the lines, and the largest only see the outline of the image, so the slope is
+fitted over the boxes in between.
+
+The apps whose dimension is known for their settings report it, e.g. log 8 / log 3 for the
+Sierpinski carpet, so the estimate can be checked against the math. The same measurement can be
+run from the command line, e.g.:
+
+cargo run --release -- dimension sierpinski --size 2048 --set Depth 6
+
+Fractal Explanation: https://en.wikipedia.org/wiki/Minkowski%E2%80%93Bouligand_dimension
+Source: https://en.wikipedia.org/wiki/List_of_fractals_by_Hausdorff_dimension
+*/
+
+// Which pixels of the rendered image are counted as part of the fractal
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Counted {
+    Drawn,    // Every pixel that isn't the black background, for curves and trees
+    Boundary, // The drawn pixels next to the background, for filled shapes and their holes
+}
+
+impl Counted {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Counted::Drawn => "Drawn",
+            Counted::Boundary => "Boundary",
+        }
+    }
+}
+
+// A dimension worked out from the math of a fractal
+pub struct KnownDimension {
+    pub formula: String, // How the dimension is worked out, e.g. "log 8 / log 3"
+    pub value: f64,
+    pub counted: Counted, // The pixels that have this dimension in the rendered image
+}
+
+// Solves the Moran equation r1^s + r2^s + ... = 1 for the similarity dimension s of a fractal made
+// of copies of itself scaled by the ratios. This is the dimension if the copies don't overlap.
+pub fn similarity_dimension(ratios: &[f64]) -> f64 {
+    let sum = |s: f64| ratios.iter().map(|r| r.powf(s)).sum::<f64>();
+    let (mut low, mut high) = (0.0, 1.0);
+    while sum(high) > 1.0 && high < 64.0 {
+        high *= 2.0;
+    }
+    for _ in 0..60 {
+        let s = (low + high) / 2.0;
+        if sum(s) > 1.0 {
+            low = s;
+        } else {
+            high = s;
+        }
+    }
+    (low + high) / 2.0
+}
+
+// Which pixels of the image are part of the fractal. Pixels past the edge of the image count as
+// the same as their neighbor, so the edge isn't mistaken for a boundary.
+pub fn fractal_pixels(image: &Image, counted: Counted, threshold: u8) -> Vec<bool> {
+    let drawn: Vec<bool> = image
+        .pixels
+        .iter()
+        .map(|pixel| pixel.r().max(pixel.g()).max(pixel.b()) > threshold)
+        .collect();
+    if counted == Counted::Drawn {
+        return drawn;
+    }
+
+    let (width, height) = (image.width, image.height);
+    let mut boundary = vec![false; drawn.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * width + x;
+            let next_to_background = (x > 0 && !drawn[index - 1])
+                || (x + 1 < width && !drawn[index + 1])
+                || (y > 0 && !drawn[index - width])
+                || (y + 1 < height && !drawn[index + width]);
+            boundary[index] = drawn[index] && next_to_background;
+        }
+    }
+    boundary
+}
+
+// Counts the boxes of 1, 2, 4... pixels that contain part of the fractal, up to the largest box
+// that fits in the image. Returns the sizes with their counts.
+pub fn box_counts(pixels: &[bool], width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut counts = Vec::new();
+    let mut grid = pixels.to_vec();
+    let (mut columns, mut rows) = (width, height);
+    let mut size = 1;
+    while size <= width.min(height) {
+        counts.push((size, grid.iter().filter(|&&filled| filled).count()));
+
+        // every box of the next size covers 2 x 2 boxes of this one
+        let (next_columns, next_rows) = (columns.div_ceil(2), rows.div_ceil(2));
+        let mut next = vec![false; next_columns * next_rows];
+        for y in 0..rows {
+            for x in 0..columns {
+                if grid[y * columns + x] {
+                    next[(y / 2) * next_columns + x / 2] = true;
+                }
+            }
+        }
+        grid = next;
+        columns = next_columns;
+        rows = next_rows;
+        size *= 2;
+    }
+    counts
+}
+
+// The least squares line through the points of the log-log plot
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fit {
+    pub slope: f64,
+    pub intercept: f64,
+    pub error: f64,     // The standard error of the slope
+    pub r_squared: f64, // How close the points are to a line, 1 is a perfect fit
+}
+
+pub fn fit_line(points: &[(f64, f64)]) -> Option<Fit> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let syy: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    let residuals: f64 = points
+        .iter()
+        .map(|p| (p.1 - intercept - slope * p.0).powi(2))
+        .sum();
+    let error = if points.len() > 2 {
+        (residuals / (n - 2.0) / sxx).sqrt()
+    } else {
+        0.0
+    };
+    let r_squared = if syy > 0.0 {
+        1.0 - residuals / syy
+    } else {
+        1.0
+    };
+    Some(Fit {
+        slope,
+        intercept,
+        error,
+        r_squared,
+    })
+}
+
+// The point of the log-log plot for a box size and its count
+fn log_point((size, count): (usize, usize)) -> (f64, f64) {
+    ((1.0 / size as f64).ln(), (count as f64).ln())
+}
+
+// A measured fractal, kept until the next measurement
+struct Measurement {
+    anchor: String,                // The app that was measured
+    counted: Counted,              // The pixels that were counted
+    counts: Vec<(usize, usize)>,   // The box sizes and how many of them hold the fractal
+    known: Option<KnownDimension>, // The dimension the app reported for its settings
+}
+
+pub struct DimensionPanel {
+    size: usize,               // The width and height of the measured image
+    counted: Option<Counted>,  // The pixels to count, None uses the ones the known value is for
+    threshold: u8,             // The brightness a pixel needs to be part of the fractal
+    fit_range: (usize, usize), // The smallest and largest box in the fit, as powers of 2
+    measurement: Option<Measurement>,
+}
+
+impl Default for DimensionPanel {
+    fn default() -> Self {
+        Self {
+            size: 1024,
+            counted: None,
+            threshold: 16,
+            fit_range: (2, 8),
+            measurement: None,
+        }
+    }
+}
+
+impl DimensionPanel {
+    // Renders the app and counts the boxes that hold its fractal
+    fn measure<A: FractalApp + ?Sized>(&mut self, anchor: &str, app: &mut A) {
+        let known = app.known_dimension();
+        let counted = self
+            .counted
+            .or_else(|| known.as_ref().map(|known| known.counted))
+            .unwrap_or(Counted::Drawn);
+        let image = app.render(self.size, self.size);
+        let pixels = fractal_pixels(&image, counted, self.threshold);
+        let mut counts = box_counts(&pixels, image.width, image.height);
+        // an empty image has no slope
+        if counts.first().is_some_and(|&(_, count)| count == 0) {
+            counts.clear();
+        }
+        self.measurement = Some(Measurement {
+            anchor: anchor.to_owned(),
+            counted,
+            counts,
+            known,
+        });
+    }
+
+    // The boxes in the fit, limited to the ones that were counted, and the line through them
+    fn fit(&self, measurement: &Measurement) -> (RangeInclusive<usize>, Option<Fit>) {
+        let last = measurement.counts.len().saturating_sub(1);
+        let largest = self.fit_range.1.min(last);
+        let smallest = self.fit_range.0.min(largest);
+        let points: Vec<(f64, f64)> = measurement.counts[smallest..=largest]
+            .iter()
+            .map(|&count| log_point(count))
+            .collect();
+        (smallest..=largest, fit_line(&points))
+    }
+
+    pub fn ui<A: FractalApp + ?Sized>(&mut self, ui: &mut Ui, anchor: &str, app: &mut A) {
+        ui.heading("Fractal Dimension");
+        ui.horizontal(|ui| {
+            ui.label("Image size");
+            ui.add(DragValue::new(&mut self.size).clamp_range(64..=4096));
+        });
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.counted, None, "Automatic");
+            ui.radio_value(&mut self.counted, Some(Counted::Drawn), "Drawn");
+            ui.radio_value(&mut self.counted, Some(Counted::Boundary), "Boundary");
+        });
+        ui.add(Slider::new(&mut self.threshold, 0..=254).text("Threshold"));
+
+        if ui.button("Measure").clicked() {
+            self.measure(anchor, app);
+        }
+
+        let measurement = match &self.measurement {
+            Some(measurement) => measurement,
+            None => {
+                ui.label("Measure to count the boxes of the fractal's image");
+                return;
+            }
+        };
+        if measurement.anchor != anchor {
+            ui.label(format!(
+                "Showing the last measurement of {}",
+                measurement.anchor
+            ));
+        }
+        if measurement.counts.is_empty() {
+            ui.label("No pixels were brighter than the threshold");
+            return;
+        }
+        ui.separator();
+
+        let last = measurement.counts.len().saturating_sub(1);
+        ui.add(Slider::new(&mut self.fit_range.0, 0..=last).text("Smallest box (2^n)"));
+        ui.add(Slider::new(&mut self.fit_range.1, 0..=last).text("Largest box (2^n)"));
+        let (fitted, fit) = self.fit(measurement);
+
+        Self::plot(ui, measurement, fit, fitted);
+
+        Grid::new("dimension_results").show(ui, |ui| {
+            ui.label("Counted");
+            ui.label(measurement.counted.name());
+            ui.end_row();
+            if let Some(fit) = fit {
+                ui.label("Box counting");
+                ui.label(format!("{:.4} ± {:.4}", fit.slope, fit.error));
+                ui.end_row();
+                ui.label("R²");
+                ui.label(format!("{:.5}", fit.r_squared));
+                ui.end_row();
+            }
+            match &measurement.known {
+                Some(known) => {
+                    ui.label("Known");
+                    ui.label(format!("{} = {:.4}", known.formula, known.value));
+                    ui.end_row();
+                    if let Some(fit) = fit {
+                        ui.label("Difference");
+                        ui.label(format!("{:+.4}", fit.slope - known.value));
+                        ui.end_row();
+                    }
+                }
+                None => {
+                    ui.label("Known");
+                    ui.label("Not known for these settings");
+                    ui.end_row();
+                }
+            }
+        });
+
+        CollapsingHeader::new("Box Counts").show(ui, |ui| {
+            Grid::new("dimension_counts").striped(true).show(ui, |ui| {
+                ui.label("Box");
+                ui.label("Count");
+                ui.label("Local slope");
+                ui.end_row();
+                for (i, &(size, count)) in measurement.counts.iter().enumerate() {
+                    ui.label(format!("{} px", size));
+                    ui.label(format!("{}", count));
+                    // the slope between this box and the next smaller one
+                    if i > 0 {
+                        let (x0, y0) = log_point(measurement.counts[i - 1]);
+                        let (x1, y1) = log_point((size, count));
+                        ui.label(format!("{:.3}", (y1 - y0) / (x1 - x0)));
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    // Draws log(count) against log(1 / size), with the fitted points filled in, the fitted line
+    // and the slope of the known dimension through the middle of the fitted points
+    fn plot(
+        ui: &mut Ui,
+        measurement: &Measurement,
+        fit: Option<Fit>,
+        fitted: RangeInclusive<usize>,
+    ) {
+        let width = ui.available_width().max(100.0);
+        let (response, painter) = ui.allocate_painter(vec2(width, width * 0.75), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let points: Vec<(f64, f64)> = measurement.counts.iter().map(|&c| log_point(c)).collect();
+        let bounds = points
+            .iter()
+            .fold([f64::MAX, f64::MAX, f64::MIN, f64::MIN], |b, p| {
+                [b[0].min(p.0), b[1].min(p.1), b[2].max(p.0), b[3].max(p.1)]
+            });
+        let plot = rect.shrink(16.0);
+        let to_screen = |(x, y): (f64, f64)| {
+            let tx = (x - bounds[0]) / (bounds[2] - bounds[0]).max(1e-9);
+            let ty = (y - bounds[1]) / (bounds[3] - bounds[1]).max(1e-9);
+            pos2(
+                plot.left() + tx as f32 * plot.width(),
+                plot.bottom() - ty as f32 * plot.height(),
+            )
+        };
+        let line = |slope: f64, intercept: f64| {
+            let y = |x: f64| slope * x + intercept;
+            [
+                to_screen((bounds[0], y(bounds[0]))),
+                to_screen((bounds[2], y(bounds[2]))),
+            ]
+        };
+
+        let text_color = ui.visuals().text_color();
+        painter.text(
+            rect.left_bottom() + vec2(4.0, -2.0),
+            Align2::LEFT_BOTTOM,
+            "log(1 / size)",
+            TextStyle::Small,
+            text_color,
+        );
+        painter.text(
+            rect.left_top() + vec2(4.0, 2.0),
+            Align2::LEFT_TOP,
+            "log(count)",
+            TextStyle::Small,
+            text_color,
+        );
+
+        if let (Some(known), Some(fit)) = (&measurement.known, fit) {
+            // through the center of the fitted points, so only the slopes are compared
+            let fitted_points = &points[fitted.clone()];
+            let n = fitted_points.len() as f64;
+            let mean_x = fitted_points.iter().map(|p| p.0).sum::<f64>() / n;
+            let mean_y = fit.slope * mean_x + fit.intercept;
+            painter.line_segment(
+                line(known.value, mean_y - known.value * mean_x),
+                (1.0, Color32::from_rgb(255, 120, 120)),
+            );
+        }
+        if let Some(fit) = fit {
+            painter.line_segment(line(fit.slope, fit.intercept), (1.5, Color32::GOLD));
+        }
+        for (i, &point) in points.iter().enumerate() {
+            let center = to_screen(point);
+            if fitted.contains(&i) {
+                painter.circle_filled(center, 3.0, Color32::LIGHT_BLUE);
+            } else {
+                painter.circle_stroke(center, 3.0, (1.0, Color32::GRAY));
+            }
+        }
+    }
+}
+
+// Measures the dimension from the command line arguments that follow "dimension"
+pub fn dimension_cli(args: &[String]) -> Result<(), String> {
+    let usage = "usage: dimension <app> [--size PIXELS] [--count drawn|boundary] \
+                 [--threshold N] [--fit SMALLEST LARGEST] [--set PARAMETER VALUE ...]";
+
+    let mut apps = crate::wrap_app::Apps::default();
+    let anchor = args.first().ok_or(usage)?;
+    let app = apps
+        .iter_mut()
+        .find(|(name, _)| name == anchor)
+        .map(|(_, app)| app)
+        .ok_or_else(|| format!("Unknown app '{}'", anchor))?;
+
+    let mut panel = DimensionPanel::default();
+
+    let parse_number = |value: String| {
+        value
+            .parse::<usize>()
+            .map_err(|_| format!("'{}' is not a whole number", value))
+    };
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut next = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--size" => panel.size = parse_number(next()?)?,
+            "--count" => {
+                panel.counted = match next()?.as_str() {
+                    "drawn" => Some(Counted::Drawn),
+                    "boundary" => Some(Counted::Boundary),
+                    value => return Err(format!("Unknown pixels to count '{}'", value)),
+                }
+            }
+            "--threshold" => panel.threshold = parse_number(next()?)?.min(254) as u8,
+            "--fit" => panel.fit_range = (parse_number(next()?)?, parse_number(next()?)?),
+            "--set" => {
+                let parameter = next()?;
+                let value = next()?;
+                let value = value
+                    .parse::<f32>()
+                    .map_err(|_| format!("'{}' is not a number", value))?;
+                if !app.parameters().iter().any(|p| p.name == parameter) {
+                    return Err(format!("Unknown parameter '{}' of {}", parameter, anchor));
+                }
+                app.set_parameter(&parameter, value);
+            }
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, usage)),
+        }
+    }
+    if panel.size < 2 {
+        return Err(String::from("The size must be at least 2 pixels"));
+    }
+
+    panel.measure(anchor, app);
+    let measurement = panel.measurement.as_ref().unwrap();
+    if measurement.counts.is_empty() {
+        return Err(String::from("No pixels were brighter than the threshold"));
+    }
+    println!("{} pixels of {}", measurement.counted.name(), anchor);
+    for &(size, count) in &measurement.counts {
+        println!("{:>6} px {:>10}", size, count);
+    }
+    match panel.fit(measurement) {
+        (fitted, Some(fit)) => println!(
+            "Box counting dimension {:.4} ± {:.4} (R² {:.5}) from {} to {} px",
+            fit.slope,
+            fit.error,
+            fit.r_squared,
+            1 << fitted.start(),
+            1 << fitted.end()
+        ),
+        (_, None) => println!("Not enough box sizes to fit a slope"),
+    }
+    if let Some(known) = &measurement.known {
+        println!("Known dimension {} = {:.4}", known.formula, known.value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    // The fit of log(count) against log(1 / size) over all the boxes
+    fn dimension(pixels: &[bool], size: usize) -> Fit {
+        let counts = box_counts(pixels, size, size);
+        let points: Vec<(f64, f64)> = counts.into_iter().map(log_point).collect();
+        fit_line(&points).unwrap()
+    }
+
+    #[test]
+    fn box_counts_of_a_line_and_a_square() {
+        // the diagonal needs one box for every box along a side
+        let diagonal: Vec<bool> = (0..64 * 64).map(|i| i % 64 == i / 64).collect();
+        assert_eq!(
+            box_counts(&diagonal, 64, 64),
+            vec![(1, 64), (2, 32), (4, 16), (8, 8), (16, 4), (32, 2), (64, 1)]
+        );
+        let fit = dimension(&diagonal, 64);
+        assert_close(fit.slope, 1.0);
+        assert_close(fit.r_squared, 1.0);
+
+        let square = vec![true; 64 * 64];
+        let fit = dimension(&square, 64);
+        assert_close(fit.slope, 2.0);
+        assert_close(fit.error, 0.0);
+
+        // boxes past the edge of the image are only partly filled, but still counted
+        assert_eq!(box_counts(&[true; 15], 5, 3), vec![(1, 15), (2, 6)]);
+        assert_eq!(box_counts(&[false; 16], 4, 4), vec![(1, 0), (2, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn fit_line_statistics() {
+        assert_eq!(fit_line(&[(0.0, 1.0)]), None);
+        assert_eq!(fit_line(&[(1.0, 1.0), (1.0, 2.0)]), None);
+
+        let fit = fit_line(&[(0.0, 0.0), (1.0, 1.0), (2.0, 1.0), (3.0, 2.0)]).unwrap();
+        assert_close(fit.slope, 0.6);
+        assert_close(fit.intercept, 0.1);
+        assert_close(fit.error, 0.02_f64.sqrt());
+        assert_close(fit.r_squared, 0.9);
+    }
+
+    #[test]
+    fn similarity_dimensions() {
+        assert_close(
+            similarity_dimension(&[1.0 / 3.0; 8]),
+            8_f64.ln() / 3_f64.ln(),
+        );
+        assert_close(similarity_dimension(&[0.5; 3]), 3_f64.ln() / 2_f64.ln());
+        assert_close(similarity_dimension(&[0.5; 4]), 2.0);
+    }
+
+    #[test]
+    fn boundary_pixels() {
+        // a 3 x 3 square in the corner of a 4 x 4 image
+        let mut image = Image::new(4, 4, Color32::BLACK);
+        for y in 0..3 {
+            for x in 0..3 {
+                image.set(x, y, Color32::WHITE);
+            }
+        }
+        let drawn = fractal_pixels(&image, Counted::Drawn, 10);
+        assert_eq!(drawn.iter().filter(|&&p| p).count(), 9);
+
+        // the edge of the image isn't a boundary, so only the right and bottom sides are left
+        let boundary = fractal_pixels(&image, Counted::Boundary, 10);
+        let found: Vec<usize> = (0..16).filter(|&i| boundary[i]).collect();
+        assert_eq!(found, vec![2, 6, 8, 9, 10]);
+    }
+}
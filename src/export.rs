@@ -0,0 +1,805 @@
+use crate::apps::FractalApp;
+use crate::raster::Image;
+use crate::solid::{Solid, Vec3};
+use crate::timeline::{Easing, Keyframe, Timeline, Track};
+use eframe::egui;
+use egui::{widgets::*, *};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/*
+This file contains the offline exporter for the fractals. It steps an app through a keyframe
+timeline (or a simple sweep of one parameter), renders every frame with the software rasterizer
+and writes the frames as an animated GIF, an animated PNG or a numbered PNG sequence.
+
+Frame i is always rendered at time i / fps, so the output only depends on the settings and
+never on how fast the frames were rendered. The same code is used by the Export window and by
+the headless command line, e.g.:
+
+cargo run --release -- export mandelbrot --sweep Zoom 1 0.6 --duration 4 --format gif --out zoom.gif
+
+The fractals that have a solid (see solid.rs) can also be saved as STL or OBJ models for 3D
+printing, scaled to a size in millimeters and resting on the printer's bed, e.g.:
+
+cargo run --release -- model solids --format stl --size 80 --out sponge.stl
+
+Sources:
+https://docs.rs/gif/0.11.4/gif/
+https://wiki.mozilla.org/APNG_Specification
+https://en.wikipedia.org/wiki/STL_(file_format)
+https://en.wikipedia.org/wiki/Wavefront_.obj_file
+*/
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Gif,
+    Apng,
+    PngSequence,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Gif,
+        ExportFormat::Apng,
+        ExportFormat::PngSequence,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "GIF",
+            ExportFormat::Apng => "Animated PNG",
+            ExportFormat::PngSequence => "PNG Sequence",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "gif",
+            ExportFormat::Apng | ExportFormat::PngSequence => "png",
+        }
+    }
+
+    // Parses the name used on the command line
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "gif" => Some(ExportFormat::Gif),
+            "apng" => Some(ExportFormat::Apng),
+            "png" | "frames" => Some(ExportFormat::PngSequence),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ExportSettings {
+    pub format: ExportFormat,
+    pub width: usize,
+    pub height: usize,
+    pub fps: u32,
+    pub path: String, // The output file, or the prefix of the numbered files for a sequence
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Gif,
+            width: 512,
+            height: 512,
+            fps: 24,
+            path: String::from("fractal.gif"),
+        }
+    }
+}
+
+impl ExportSettings {
+    // The number of frames needed to cover the duration, including both of its ends
+    pub fn frame_count(&self, duration: f32) -> u32 {
+        (duration * self.fps as f32).round() as u32 + 1
+    }
+
+    // The time at which a frame is rendered
+    pub fn frame_time(&self, frame: u32) -> f32 {
+        frame as f32 / self.fps as f32
+    }
+}
+
+// Writes rendered frames into the chosen format
+pub struct FrameWriter {
+    sink: Sink,
+    fps: u32,
+    frame: u32, // The number of frames written so far
+}
+
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(png::Writer<BufWriter<File>>),
+    PngSequence(PathBuf),
+}
+
+impl FrameWriter {
+    pub fn create(settings: &ExportSettings, frames: u32) -> Result<Self, String> {
+        let path = Path::new(&settings.path);
+        let sink = match settings.format {
+            ExportFormat::Gif => {
+                let file = File::create(path).map_err(|e| e.to_string())?;
+                let mut encoder = gif::Encoder::new(
+                    BufWriter::new(file),
+                    settings.width as u16,
+                    settings.height as u16,
+                    &[],
+                )
+                .map_err(|e| e.to_string())?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|e| e.to_string())?;
+                Sink::Gif(encoder)
+            }
+            ExportFormat::Apng => {
+                let file = File::create(path).map_err(|e| e.to_string())?;
+                let mut encoder = png::Encoder::new(
+                    BufWriter::new(file),
+                    settings.width as u32,
+                    settings.height as u32,
+                );
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(frames, 0).map_err(|e| e.to_string())?;
+                encoder
+                    .set_frame_delay(1, settings.fps as u16)
+                    .map_err(|e| e.to_string())?;
+                Sink::Apng(encoder.write_header().map_err(|e| e.to_string())?)
+            }
+            ExportFormat::PngSequence => Sink::PngSequence(path.with_extension("")),
+        };
+
+        Ok(Self {
+            sink,
+            fps: settings.fps,
+            frame: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, image: &Image) -> Result<(), String> {
+        let mut rgba = image.to_rgba();
+        match &mut self.sink {
+            Sink::Gif(encoder) => {
+                let mut frame = gif::Frame::from_rgba_speed(
+                    image.width as u16,
+                    image.height as u16,
+                    &mut rgba,
+                    10,
+                );
+                // GIF delays are in hundredths of a second, so round the running total instead
+                // of every frame to keep the overall timing exact
+                let start = (100 * self.frame) as f32 / self.fps as f32;
+                let end = (100 * (self.frame + 1)) as f32 / self.fps as f32;
+                frame.delay = (end.round() - start.round()) as u16;
+                encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+            }
+            Sink::Apng(writer) => {
+                writer.write_image_data(&rgba).map_err(|e| e.to_string())?;
+            }
+            Sink::PngSequence(prefix) => {
+                let file_name = format!(
+                    "{}_{:05}.png",
+                    prefix.file_name().unwrap_or_default().to_string_lossy(),
+                    self.frame
+                );
+                write_png(&prefix.with_file_name(file_name), image)?;
+            }
+        }
+
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self.sink {
+            Sink::Gif(_) | Sink::PngSequence(_) => Ok(()),
+            Sink::Apng(writer) => writer.finish().map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// Writes a single image as a png file
+pub fn write_png(path: &Path, image: &Image) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&image.to_rgba())
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ModelFormat {
+    Stl,
+    Obj,
+}
+
+impl ModelFormat {
+    pub const ALL: [ModelFormat; 2] = [ModelFormat::Stl, ModelFormat::Obj];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModelFormat::Stl => "STL",
+            ModelFormat::Obj => "OBJ",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ModelFormat::Stl => "stl",
+            ModelFormat::Obj => "obj",
+        }
+    }
+
+    // Parses the name used on the command line
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "stl" => Some(ModelFormat::Stl),
+            "obj" => Some(ModelFormat::Obj),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ModelSettings {
+    pub format: ModelFormat,
+    pub size: f32,      // The length of the model's longest side in millimeters
+    pub thickness: f32, // How thick flat fractals are, as a fraction of their size
+    pub path: String,
+}
+
+impl Default for ModelSettings {
+    fn default() -> Self {
+        Self {
+            format: ModelFormat::Stl,
+            size: 100.0,
+            thickness: 0.05,
+            path: String::from("fractal.stl"),
+        }
+    }
+}
+
+// Writes a solid as a 3D model, scaled so its longest side is the size and moved so that it
+// rests on z = 0. The solids have y going up, while 3D printers have z going up.
+pub fn write_model(path: &Path, solid: &Solid, settings: &ModelSettings) -> Result<(), String> {
+    if solid.triangles.is_empty() {
+        return Err(String::from("The model is empty"));
+    }
+
+    let z_up = |p: Vec3| Vec3::new(p.x, -p.z, p.y);
+    let mut vertices: Vec<Vec3> = solid.vertices.iter().map(|&p| z_up(p)).collect();
+    let (mut min, mut max) = (vertices[0], vertices[0]);
+    for p in &vertices {
+        min = Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+        max = Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+    }
+    let extent = max - min;
+    let scale = settings.size / extent.x.max(extent.y).max(extent.z);
+    for p in &mut vertices {
+        *p = (*p - min) * scale;
+    }
+
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut out = BufWriter::new(file);
+    match settings.format {
+        ModelFormat::Stl => {
+            // binary STL: a header, the number of triangles and then every triangle's normal
+            // and corners as little endian floats
+            let mut header = [0_u8; 80];
+            let title = b"Fractal from rust_math";
+            header[..title.len()].copy_from_slice(title);
+            out.write_all(&header).map_err(|e| e.to_string())?;
+            out.write_all(&(solid.triangles.len() as u32).to_le_bytes())
+                .map_err(|e| e.to_string())?;
+            for triangle in &solid.triangles {
+                let mut record = Vec::with_capacity(50);
+                let corners = triangle.corners.map(|i| vertices[i]);
+                for p in [z_up(triangle.normal), corners[0], corners[1], corners[2]] {
+                    for value in [p.x, p.y, p.z] {
+                        record.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                record.extend_from_slice(&[0, 0]);
+                out.write_all(&record).map_err(|e| e.to_string())?;
+            }
+        }
+        ModelFormat::Obj => {
+            // the corners of the faces count the vertices from 1
+            writeln!(out, "# Fractal from rust_math, in millimeters").map_err(|e| e.to_string())?;
+            for p in &vertices {
+                writeln!(out, "v {} {} {}", p.x, p.y, p.z).map_err(|e| e.to_string())?;
+            }
+            for triangle in &solid.triangles {
+                let [a, b, c] = triangle.corners;
+                writeln!(out, "f {} {} {}", a + 1, b + 1, c + 1).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+// Creates a timeline that moves each parameter linearly from one value to another
+pub fn sweep_timeline(sweeps: &[(String, f32, f32)], duration: f32) -> Timeline {
+    let mut timeline = Timeline::default();
+    timeline.duration = duration;
+    for (parameter, from, to) in sweeps {
+        let mut track = Track::new(parameter);
+        track.insert(Keyframe {
+            time: 0.0,
+            value: *from,
+            easing: Easing::Linear,
+        });
+        track.insert(Keyframe {
+            time: duration,
+            value: *to,
+            easing: Easing::Linear,
+        });
+        timeline.tracks.push(track);
+    }
+    timeline
+}
+
+// An export in progress, rendering one frame per step so the GUI stays responsive
+pub struct Export {
+    settings: ExportSettings,
+    timeline: Timeline,
+    writer: Option<FrameWriter>,
+    frames: u32,
+    next_frame: u32,
+    saved_parameters: Vec<(&'static str, f32)>, // Restored once the export is done
+}
+
+impl Export {
+    pub fn start<A: FractalApp + ?Sized>(
+        app: &A,
+        timeline: Timeline,
+        settings: ExportSettings,
+    ) -> Result<Self, String> {
+        if settings.width == 0 || settings.height == 0 || settings.fps == 0 {
+            return Err(String::from("The size and frame rate must be positive"));
+        }
+
+        let frames = settings.frame_count(timeline.duration);
+        let writer = FrameWriter::create(&settings, frames)?;
+        // only the keyframed parameters are written by the export, so only they are restored
+        let saved_parameters = app
+            .parameters()
+            .iter()
+            .filter(|p| timeline.tracks.iter().any(|t| t.parameter == p.name))
+            .map(|p| (p.name, p.value))
+            .collect();
+
+        Ok(Self {
+            settings,
+            timeline,
+            writer: Some(writer),
+            frames,
+            next_frame: 0,
+            saved_parameters,
+        })
+    }
+
+    // Renders and writes the next frame, returns true once every frame has been written
+    pub fn step<A: FractalApp + ?Sized>(&mut self, app: &mut A) -> Result<bool, String> {
+        let result = self.write_next_frame(app);
+        if result.is_err() || self.is_done() {
+            for (name, value) in &self.saved_parameters {
+                app.set_parameter(name, *value);
+            }
+        }
+        result?;
+
+        if self.is_done() {
+            if let Some(writer) = self.writer.take() {
+                writer.finish()?;
+            }
+        }
+        Ok(self.is_done())
+    }
+
+    fn write_next_frame<A: FractalApp + ?Sized>(&mut self, app: &mut A) -> Result<(), String> {
+        let time = self
+            .settings
+            .frame_time(self.next_frame)
+            .min(self.timeline.duration);
+        self.timeline.apply_at(time, app);
+
+        let image = app.render(self.settings.width, self.settings.height);
+        if let Some(writer) = &mut self.writer {
+            writer.write_frame(&image)?;
+        }
+        self.next_frame += 1;
+        Ok(())
+    }
+
+    // Stops the export early, leaving the frames written so far
+    pub fn cancel<A: FractalApp + ?Sized>(self, app: &mut A) {
+        for (name, value) in &self.saved_parameters {
+            app.set_parameter(name, *value);
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_frame >= self.frames
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.next_frame as f32 / self.frames as f32
+    }
+
+    // Runs the whole export at once, used by the command line
+    pub fn run<A: FractalApp + ?Sized>(mut self, app: &mut A) -> Result<(), String> {
+        while !self.step(app)? {}
+        Ok(())
+    }
+}
+
+// Where the exported animation gets its parameter values from
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Source {
+    Timeline,
+    Sweep,
+}
+
+// The state of the Export window
+pub struct ExportPanel {
+    settings: ExportSettings,
+    source: Source,
+    sweep_parameter: String,
+    sweep_from: f32,
+    sweep_to: f32,
+    sweep_duration: f32,
+    export: Option<Export>,
+    export_anchor: String, // The app that is being exported
+    status: String,        // The result of the last export
+    model: ModelSettings,
+    model_status: String, // The result of the last saved model
+}
+
+impl Default for ExportPanel {
+    fn default() -> Self {
+        Self {
+            settings: ExportSettings::default(),
+            source: Source::Timeline,
+            sweep_parameter: String::new(),
+            sweep_from: 0.0,
+            sweep_to: 1.0,
+            sweep_duration: 3.0,
+            export: None,
+            export_anchor: String::new(),
+            status: String::new(),
+            model: ModelSettings::default(),
+            model_status: String::new(),
+        }
+    }
+}
+
+impl ExportPanel {
+    // Renders the next frame of a running export, called every frame for every app
+    pub fn update<A: FractalApp + ?Sized>(&mut self, ctx: &CtxRef, anchor: &str, app: &mut A) {
+        if anchor != self.export_anchor {
+            return;
+        }
+
+        if let Some(export) = &mut self.export {
+            match export.step(app) {
+                Ok(false) => ctx.request_repaint(),
+                Ok(true) => {
+                    self.status = format!("Saved {}", self.settings.path);
+                    self.export = None;
+                }
+                Err(error) => {
+                    self.status = format!("Export failed: {}", error);
+                    self.export = None;
+                }
+            }
+        }
+    }
+
+    pub fn ui<A: FractalApp + ?Sized>(
+        &mut self,
+        ui: &mut Ui,
+        anchor: &str,
+        app: &mut A,
+        timeline: &Timeline,
+    ) {
+        if let Some(export) = &self.export {
+            ui.label(format!("Exporting {}", self.settings.path));
+            ui.add(ProgressBar::new(export.progress()).show_percentage());
+            if anchor == self.export_anchor && ui.button("Cancel").clicked() {
+                if let Some(export) = self.export.take() {
+                    export.cancel(app);
+                }
+                self.status = String::from("Export cancelled");
+            }
+            return;
+        }
+
+        ComboBox::from_label("Format")
+            .selected_text(self.settings.format.name())
+            .show_ui(ui, |ui| {
+                for format in ExportFormat::ALL.iter() {
+                    ui.selectable_value(&mut self.settings.format, *format, format.name());
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Size");
+            ui.add(DragValue::new(&mut self.settings.width).clamp_range(16..=4096));
+            ui.label("x");
+            ui.add(DragValue::new(&mut self.settings.height).clamp_range(16..=4096));
+        });
+        ui.add(Slider::new(&mut self.settings.fps, 1..=60).text("FPS"));
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut self.settings.path);
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.source, Source::Timeline, "Timeline");
+            ui.radio_value(&mut self.source, Source::Sweep, "Sweep");
+        });
+
+        let parameters = app.parameters();
+        match self.source {
+            Source::Timeline => {
+                ui.label(format!(
+                    "{} keyframed parameters over {:.1} s",
+                    timeline.tracks.len(),
+                    timeline.duration
+                ));
+            }
+            Source::Sweep => {
+                if !parameters.iter().any(|p| p.name == self.sweep_parameter) {
+                    if let Some(parameter) = parameters.first() {
+                        self.sweep_parameter = parameter.name.to_owned();
+                        self.sweep_from = *parameter.range.start();
+                        self.sweep_to = *parameter.range.end();
+                    }
+                }
+                ComboBox::from_label("Parameter")
+                    .selected_text(self.sweep_parameter.clone())
+                    .show_ui(ui, |ui| {
+                        for parameter in &parameters {
+                            ui.selectable_value(
+                                &mut self.sweep_parameter,
+                                parameter.name.to_owned(),
+                                parameter.name,
+                            );
+                        }
+                    });
+                if let Some(parameter) = parameters.iter().find(|p| p.name == self.sweep_parameter)
+                {
+                    ui.add(Slider::new(&mut self.sweep_from, parameter.range.clone()).text("From"));
+                    ui.add(Slider::new(&mut self.sweep_to, parameter.range.clone()).text("To"));
+                }
+                ui.add(Slider::new(&mut self.sweep_duration, 0.1..=30.0).text("Duration"));
+            }
+        }
+
+        let timeline = match self.source {
+            Source::Timeline => timeline.clone(),
+            Source::Sweep => sweep_timeline(
+                &[(self.sweep_parameter.clone(), self.sweep_from, self.sweep_to)],
+                self.sweep_duration,
+            ),
+        };
+        ui.label(format!(
+            "{} frames",
+            self.settings.frame_count(timeline.duration)
+        ));
+
+        if ui.button("Export").clicked() {
+            match Export::start(app, timeline, self.settings.clone()) {
+                Ok(export) => {
+                    self.export = Some(export);
+                    self.export_anchor = anchor.to_owned();
+                    self.status = String::new();
+                }
+                Err(error) => self.status = format!("Export failed: {}", error),
+            }
+        }
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+
+        ui.separator();
+        CollapsingHeader::new("3D Model").show(ui, |ui| self.model_ui(ui, app));
+    }
+
+    // Saves the fractal as a model for 3D printing
+    fn model_ui<A: FractalApp + ?Sized>(&mut self, ui: &mut Ui, app: &mut A) {
+        ComboBox::from_label("Model format")
+            .selected_text(self.model.format.name())
+            .show_ui(ui, |ui| {
+                for format in ModelFormat::ALL.iter() {
+                    ui.selectable_value(&mut self.model.format, *format, format.name());
+                }
+            });
+        ui.add(Slider::new(&mut self.model.size, 10.0..=300.0).text("Size (mm)"));
+        ui.add(Slider::new(&mut self.model.thickness, 0.005..=0.3).text("Thickness"));
+        ui.label("Flat fractals are as thick as this fraction of their size");
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut self.model.path);
+        });
+
+        if ui.button("Save Model").clicked() {
+            self.model_status = match app.model(self.model.thickness) {
+                None => String::from("This fractal can't be saved as a 3D model"),
+                Some(solid) => {
+                    match write_model(Path::new(&self.model.path), &solid, &self.model) {
+                        Ok(()) => format!(
+                            "Saved {} ({} triangles)",
+                            self.model.path,
+                            solid.triangles.len()
+                        ),
+                        Err(error) => format!("Saving failed: {}", error),
+                    }
+                }
+            };
+        }
+
+        if !self.model_status.is_empty() {
+            ui.label(&self.model_status);
+        }
+    }
+}
+
+// Runs an export from the command line arguments that follow "export"
+pub fn export_cli(args: &[String]) -> Result<(), String> {
+    let usage = "usage: export <app> [--format gif|apng|png] [--out PATH] [--size WIDTHxHEIGHT] \
+                 [--fps N] [--duration SECONDS] --sweep PARAMETER FROM TO [--sweep ...]";
+
+    let mut apps = crate::wrap_app::Apps::default();
+    let anchor = args.first().ok_or(usage)?;
+    let app = apps
+        .iter_mut()
+        .find(|(name, _)| name == anchor)
+        .map(|(_, app)| app)
+        .ok_or_else(|| format!("Unknown app '{}'", anchor))?;
+
+    let mut settings = ExportSettings::default();
+    let mut duration = 3.0;
+    let mut sweeps = Vec::new();
+    let mut out = None;
+
+    let parse_number = |value: String| {
+        value
+            .parse::<f32>()
+            .map_err(|_| format!("'{}' is not a number", value))
+    };
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut next = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--format" => {
+                let value = next()?;
+                settings.format = ExportFormat::from_arg(&value)
+                    .ok_or_else(|| format!("Unknown format '{}'", value))?;
+            }
+            "--out" => out = Some(next()?),
+            "--size" => {
+                let value = next()?;
+                let (width, height) = value
+                    .split_once('x')
+                    .ok_or_else(|| format!("Size '{}' should look like 512x512", value))?;
+                settings.width = parse_number(width.to_owned())? as usize;
+                settings.height = parse_number(height.to_owned())? as usize;
+            }
+            "--fps" => settings.fps = parse_number(next()?)? as u32,
+            "--duration" => duration = parse_number(next()?)?,
+            "--sweep" => {
+                let parameter = next()?;
+                let from = parse_number(next()?)?;
+                let to = parse_number(next()?)?;
+                sweeps.push((parameter, from, to));
+            }
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, usage)),
+        }
+    }
+
+    let parameter_names: Vec<&str> = app.parameters().iter().map(|p| p.name).collect();
+    if sweeps.is_empty() {
+        return Err(format!(
+            "Nothing to animate, the parameters of {} are: {}\n{}",
+            anchor,
+            parameter_names.join(", "),
+            usage
+        ));
+    }
+    for (parameter, _, _) in &sweeps {
+        if !parameter_names.contains(&parameter.as_str()) {
+            return Err(format!(
+                "Unknown parameter '{}', the parameters of {} are: {}",
+                parameter,
+                anchor,
+                parameter_names.join(", ")
+            ));
+        }
+    }
+
+    settings.path = out.unwrap_or_else(|| format!("{}.{}", anchor, settings.format.extension()));
+    let timeline = sweep_timeline(&sweeps, duration);
+    let frames = settings.frame_count(duration);
+    Export::start(app, timeline, settings.clone())?.run(app)?;
+    println!("Wrote {} frames to {}", frames, settings.path);
+    Ok(())
+}
+
+// Saves a model from the command line arguments that follow "model"
+pub fn model_cli(args: &[String]) -> Result<(), String> {
+    let usage = "usage: model <app> [--format stl|obj] [--out PATH] [--size MILLIMETERS] \
+                 [--thickness FRACTION]";
+
+    let mut apps = crate::wrap_app::Apps::default();
+    let anchor = args.first().ok_or(usage)?;
+    let app = apps
+        .iter_mut()
+        .find(|(name, _)| name == anchor)
+        .map(|(_, app)| app)
+        .ok_or_else(|| format!("Unknown app '{}'", anchor))?;
+
+    let mut settings = ModelSettings::default();
+    let mut out = None;
+
+    let parse_number = |value: String| {
+        value
+            .parse::<f32>()
+            .map_err(|_| format!("'{}' is not a number", value))
+    };
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        let mut next = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--format" => {
+                let value = next()?;
+                settings.format = ModelFormat::from_arg(&value)
+                    .ok_or_else(|| format!("Unknown format '{}'", value))?;
+            }
+            "--out" => out = Some(next()?),
+            "--size" => settings.size = parse_number(next()?)?,
+            "--thickness" => settings.thickness = parse_number(next()?)?,
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, usage)),
+        }
+    }
+    if settings.size <= 0.0 || settings.thickness <= 0.0 {
+        return Err(String::from("The size and thickness must be positive"));
+    }
+
+    settings.path = out.unwrap_or_else(|| format!("{}.{}", anchor, settings.format.extension()));
+    let solid = app
+        .model(settings.thickness)
+        .ok_or_else(|| format!("{} can't be saved as a 3D model", anchor))?;
+    write_model(Path::new(&settings.path), &solid, &settings)?;
+    println!(
+        "Wrote {} triangles to {}",
+        solid.triangles.len(),
+        settings.path
+    );
+    Ok(())
+}
@@ -4,9 +4,21 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod apps;
+mod dimension;
+mod export;
+mod lsystem;
+mod palette;
+mod raster;
+mod solid;
+mod timeline;
+mod words;
 mod wrap_app;
+mod zoom_video;
 
+pub use dimension::dimension_cli;
+pub use export::{export_cli, model_cli};
 pub use wrap_app::WrapApp;
+pub use zoom_video::zoom_cli;
 
 #[cfg(target_arch = "wasm32")]
 use eframe::wasm_bindgen::{self, prelude::*};
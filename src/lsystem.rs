@@ -0,0 +1,712 @@
+use eframe::egui::{pos2, Pos2};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/*
+This file contains a general L-system (Lindenmayer system) engine.
+An L-system starts with a word (the axiom) and rewrites every symbol of it in parallel with
+production rules. The final word is then drawn by a turtle that walks forward, turns and saves
+or restores its position on a stack.
+
+The rules are written one per line:
+    F -> F+F--F+F                      a plain rule
+    F -> F[+F]F @ 0.5                  a stochastic rule, chosen with a weight of 0.5
+    A(l, w) : l > 2 -> F(l)[+A(l*0.7, w)]  a parametric rule with a condition
+Lines that are empty or start with # are ignored.
+
+Turtle commands:
+    F G A B   move forward and draw a line (F(l) moves by l instead of the step)
+    f g       move forward without drawing
+    + -       turn left or right by the angle (+(a) turns by a degrees)
+    |         turn around
+    [ ]       save and restore the turtle's position, heading and line width
+    !         make the line thinner by the width factor (!(w) sets the width to w)
+Every other symbol is only used for rewriting.
+
+Fractal Explanation: https://en.wikipedia.org/wiki/L-system
+Parametric and stochastic L-systems: http://algorithmicbotany.org/papers/abop/abop.pdf
+*/
+
+// A symbol of a word together with its parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    pub symbol: char,
+    pub params: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Less,
+    Greater,
+    LessEq,
+    GreaterEq,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+// An arithmetic expression, variables are the parameters of the rule's predecessor
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f32),
+    Variable(usize),
+    Negate(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, vars: &[f32]) -> f32 {
+        let truth = |b: bool| if b { 1.0 } else { 0.0 };
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Variable(i) => vars[*i],
+            Expr::Negate(e) => -e.eval(vars),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(vars), b.eval(vars));
+                match op {
+                    Op::Add => a + b,
+                    Op::Sub => a - b,
+                    Op::Mul => a * b,
+                    Op::Div => a / b,
+                    Op::Pow => a.powf(b),
+                    Op::Less => truth(a < b),
+                    Op::Greater => truth(a > b),
+                    Op::LessEq => truth(a <= b),
+                    Op::GreaterEq => truth(a >= b),
+                    Op::Equal => truth(a == b),
+                    Op::NotEqual => truth(a != b),
+                    Op::And => truth(a != 0.0 && b != 0.0),
+                    Op::Or => truth(a != 0.0 || b != 0.0),
+                }
+            }
+        }
+    }
+}
+
+// A recursive descent parser for expressions
+struct ExprParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    vars: &'a [String],
+}
+
+impl<'a> ExprParser<'a> {
+    fn parse(text: &str, vars: &'a [String]) -> Result<Expr, String> {
+        let mut parser = Self {
+            chars: text.chars().filter(|c| !c.is_whitespace()).collect(),
+            pos: 0,
+            vars,
+        };
+        let expr = parser.or()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!(
+                "Unexpected '{}' in \"{}\"",
+                parser.chars[parser.pos], text
+            ));
+        }
+        Ok(expr)
+    }
+
+    // Consumes the token if it is next
+    fn eat(&mut self, token: &str) -> bool {
+        let len = token.chars().count();
+        if self.chars.len() >= self.pos + len
+            && self.chars[self.pos..self.pos + len]
+                .iter()
+                .copied()
+                .eq(token.chars())
+        {
+            self.pos += len;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn binary(op: Op, a: Expr, b: Expr) -> Expr {
+        Expr::Binary(op, Box::new(a), Box::new(b))
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Self::binary(Op::Or, expr, self.and()?);
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.comparison()?;
+        while self.eat("&&") {
+            expr = Self::binary(Op::And, expr, self.comparison()?);
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let expr = self.sum()?;
+        // the two character operators have to be checked first
+        let ops = [
+            ("<=", Op::LessEq),
+            (">=", Op::GreaterEq),
+            ("==", Op::Equal),
+            ("!=", Op::NotEqual),
+            ("<", Op::Less),
+            (">", Op::Greater),
+        ];
+        for (token, op) in ops {
+            if self.eat(token) {
+                return Ok(Self::binary(op, expr, self.sum()?));
+            }
+        }
+        Ok(expr)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        loop {
+            if self.eat("+") {
+                expr = Self::binary(Op::Add, expr, self.product()?);
+            } else if self.eat("-") {
+                expr = Self::binary(Op::Sub, expr, self.product()?);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            if self.eat("*") {
+                expr = Self::binary(Op::Mul, expr, self.unary()?);
+            } else if self.eat("/") {
+                expr = Self::binary(Op::Div, expr, self.unary()?);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        let base = self.atom()?;
+        if self.eat("^") {
+            return Ok(Self::binary(Op::Pow, base, self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        if self.eat("(") {
+            let expr = self.or()?;
+            if !self.eat(")") {
+                return Err("Missing ')' in an expression".to_owned());
+            }
+            return Ok(expr);
+        }
+
+        let start = self.pos;
+        let is_number = |c: char| c.is_ascii_digit() || c == '.';
+        let is_name = |c: char| c.is_alphanumeric() || c == '_';
+        if self.pos < self.chars.len() && is_number(self.chars[self.pos]) {
+            while self.pos < self.chars.len() && is_number(self.chars[self.pos]) {
+                self.pos += 1;
+            }
+            let text: String = self.chars[start..self.pos].iter().collect();
+            return text
+                .parse()
+                .map(Expr::Number)
+                .map_err(|_| format!("Invalid number \"{}\"", text));
+        }
+        if self.pos < self.chars.len() && is_name(self.chars[self.pos]) {
+            while self.pos < self.chars.len() && is_name(self.chars[self.pos]) {
+                self.pos += 1;
+            }
+            let name: String = self.chars[start..self.pos].iter().collect();
+            return self
+                .vars
+                .iter()
+                .position(|v| *v == name)
+                .map(Expr::Variable)
+                .ok_or_else(|| format!("Unknown variable \"{}\"", name));
+        }
+        Err("Expected a number, a variable or '('".to_owned())
+    }
+}
+
+// Splits "a, (b, c), d" on the commas that aren't inside brackets
+fn split_arguments(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+// Parses a sequence of symbols, each one optionally followed by a list of expressions
+fn parse_symbols(text: &str, vars: &[String]) -> Result<Vec<(char, Vec<Expr>)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut symbols = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let symbol = chars[i];
+        i += 1;
+        if symbol.is_whitespace() {
+            continue;
+        }
+
+        let mut params = Vec::new();
+        if chars.get(i) == Some(&'(') {
+            // find the matching bracket
+            let mut depth = 0;
+            let start = i + 1;
+            loop {
+                match chars.get(i) {
+                    Some('(') => depth += 1,
+                    Some(')') => depth -= 1,
+                    Some(_) => {}
+                    None => return Err(format!("Missing ')' after '{}'", symbol)),
+                }
+                i += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            let inside: String = chars[start..i - 1].iter().collect();
+            for argument in split_arguments(&inside) {
+                params.push(ExprParser::parse(argument, vars)?);
+            }
+        }
+        symbols.push((symbol, params));
+    }
+    Ok(symbols)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    predecessor: char,
+    param_count: usize,
+    condition: Option<Expr>,
+    successor: Vec<(char, Vec<Expr>)>,
+    weight: f32,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Self, String> {
+        let (left, right) = line
+            .split_once("->")
+            .ok_or_else(|| format!("Missing \"->\" in \"{}\"", line))?;
+
+        // the weight of a stochastic rule comes after the successor
+        let (successor, weight) = match right.rsplit_once('@') {
+            Some((successor, weight)) => {
+                let weight: f32 = weight
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid weight \"{}\"", weight.trim()))?;
+                (successor, weight)
+            }
+            None => (right, 1.0),
+        };
+
+        let (head, condition) = match left.split_once(':') {
+            Some((head, condition)) => (head.trim(), Some(condition)),
+            None => (left.trim(), None),
+        };
+        let mut head_chars = head.chars();
+        let predecessor = head_chars
+            .next()
+            .ok_or_else(|| format!("Missing a predecessor in \"{}\"", line))?;
+
+        // the parameter names of the predecessor
+        let rest = head_chars.as_str().trim();
+        let vars: Vec<String> = if rest.is_empty() {
+            Vec::new()
+        } else if rest.starts_with('(') && rest.ends_with(')') {
+            rest[1..rest.len() - 1]
+                .split(',')
+                .map(|v| v.trim().to_owned())
+                .collect()
+        } else {
+            return Err(format!("Invalid predecessor \"{}\"", head));
+        };
+
+        Ok(Self {
+            predecessor,
+            param_count: vars.len(),
+            condition: condition.map(|c| ExprParser::parse(c, &vars)).transpose()?,
+            successor: parse_symbols(successor, &vars)?,
+            weight,
+        })
+    }
+
+    fn matches(&self, module: &Module) -> bool {
+        self.predecessor == module.symbol
+            && self.param_count == module.params.len()
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|c| c.eval(&module.params) != 0.0)
+    }
+
+    fn apply(&self, module: &Module, word: &mut Vec<Module>) {
+        for (symbol, params) in &self.successor {
+            word.push(Module {
+                symbol: *symbol,
+                params: params.iter().map(|p| p.eval(&module.params)).collect(),
+            });
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grammar {
+    axiom: Vec<Module>,
+    rules: Vec<Rule>,
+}
+
+impl Grammar {
+    pub fn parse(axiom: &str, rules: &str) -> Result<Self, String> {
+        let axiom = parse_symbols(axiom, &[])?
+            .into_iter()
+            .map(|(symbol, params)| Module {
+                symbol,
+                params: params.iter().map(|p| p.eval(&[])).collect(),
+            })
+            .collect();
+
+        let rules = rules
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Rule::parse)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { axiom, rules })
+    }
+
+    // Rewrites every module of the word once. Returns None if the new word would be longer
+    // than max_len.
+    fn rewrite(&self, word: &[Module], rng: &mut StdRng, max_len: usize) -> Option<Vec<Module>> {
+        let mut next = Vec::with_capacity(word.len());
+        for module in word {
+            let matching: Vec<&Rule> = self.rules.iter().filter(|r| r.matches(module)).collect();
+
+            // stochastic rules are chosen randomly by their weights
+            let rule = match matching.len() {
+                0 => None,
+                1 => Some(matching[0]),
+                _ => {
+                    let total: f32 = matching.iter().map(|r| r.weight).sum();
+                    let mut choice = rng.gen_range(0.0..total.max(f32::EPSILON));
+                    let mut chosen = matching[matching.len() - 1];
+                    for rule in &matching {
+                        if choice < rule.weight {
+                            chosen = rule;
+                            break;
+                        }
+                        choice -= rule.weight;
+                    }
+                    Some(chosen)
+                }
+            };
+
+            match rule {
+                Some(rule) => rule.apply(module, &mut next),
+                None => next.push(module.clone()),
+            }
+            if next.len() > max_len {
+                return None;
+            }
+        }
+        Some(next)
+    }
+
+    // Rewrites the axiom the given number of times. The seed picks the stochastic rules.
+    // Stops early if the word would get longer than max_len, the returned count is the number
+    // of rewrites that were done.
+    pub fn generate(&self, iterations: usize, seed: u64, max_len: usize) -> (Vec<Module>, usize) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut word = self.axiom.clone();
+        for i in 0..iterations {
+            match self.rewrite(&word, &mut rng, max_len) {
+                Some(next) => word = next,
+                None => return (word, i),
+            }
+        }
+        (word, iterations)
+    }
+}
+
+// A line drawn by the turtle, the width is relative to the starting width of 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub start: Pos2,
+    pub end: Pos2,
+    pub width: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Turtle {
+    pub angle: f32,        // The angle (in degrees) that + and - turn by
+    pub step: f32,         // The distance that F moves forward by
+    pub heading: f32,      // The starting direction (in degrees), 0 is to the right
+    pub width_factor: f32, // How much thinner ! makes the lines
+}
+
+impl Turtle {
+    // Walks over the word and returns the lines that were drawn. The y axis points down like
+    // on the screen, so turning left is counterclockwise.
+    pub fn walk(&self, word: &[Module]) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut position = pos2(0.0, 0.0);
+        let mut heading = self.heading.to_radians();
+        let mut width = 1.0;
+        let mut stack = Vec::new();
+
+        for module in word {
+            let param = module.params.first().copied();
+            match module.symbol {
+                'F' | 'G' | 'A' | 'B' | 'f' | 'g' => {
+                    let length = param.unwrap_or(self.step);
+                    let end = position + length * eframe::egui::vec2(heading.cos(), -heading.sin());
+                    if module.symbol.is_uppercase() {
+                        segments.push(Segment {
+                            start: position,
+                            end,
+                            width,
+                        });
+                    }
+                    position = end;
+                }
+                '+' => heading += param.unwrap_or(self.angle).to_radians(),
+                '-' => heading -= param.unwrap_or(self.angle).to_radians(),
+                '|' => heading += std::f32::consts::PI,
+                '[' => stack.push((position, heading, width)),
+                ']' => {
+                    if let Some(state) = stack.pop() {
+                        (position, heading, width) = state;
+                    }
+                }
+                '!' => width = param.unwrap_or(width * self.width_factor),
+                _ => {}
+            }
+        }
+        segments
+    }
+}
+
+// A grammar with the settings that draw it nicely
+pub struct Preset {
+    pub name: &'static str,
+    pub axiom: &'static str,
+    pub rules: &'static str,
+    pub angle: f32,
+    pub heading: f32,
+    pub iterations: usize,
+}
+
+// Source: https://en.wikipedia.org/wiki/L-system#Examples_of_L-systems
+pub const PRESETS: [Preset; 8] = [
+    Preset {
+        name: "Koch Snowflake",
+        axiom: "F++F++F",
+        rules: "F -> F-F++F-F",
+        angle: 60.0,
+        heading: 0.0,
+        iterations: 4,
+    },
+    Preset {
+        name: "Dragon Curve",
+        axiom: "F",
+        rules: "F -> F+G\nG -> F-G",
+        angle: 90.0,
+        heading: 0.0,
+        iterations: 12,
+    },
+    Preset {
+        name: "Hilbert Curve",
+        axiom: "X",
+        rules: "X -> +YF-XFX-FY+\nY -> -XF+YFY+FX-",
+        angle: 90.0,
+        heading: 0.0,
+        iterations: 6,
+    },
+    Preset {
+        name: "Gosper Curve",
+        axiom: "A",
+        rules: "A -> A-B--B+A++AA+B-\nB -> +A-BB--B-A++A+B",
+        angle: 60.0,
+        heading: 0.0,
+        iterations: 4,
+    },
+    Preset {
+        name: "Sierpinski Arrowhead",
+        axiom: "A",
+        rules: "A -> B-A-B\nB -> A+B+A",
+        angle: 60.0,
+        heading: 0.0,
+        iterations: 7,
+    },
+    Preset {
+        name: "Fractal Plant",
+        axiom: "X",
+        rules: "X -> F+[[X]-X]-F[-FX]+X\nF -> FF",
+        angle: 25.0,
+        heading: 65.0,
+        iterations: 6,
+    },
+    Preset {
+        name: "Stochastic Plant",
+        axiom: "F",
+        rules: "F -> F[+F]F[-F]F @ 1\nF -> F[+F]F @ 1\nF -> F[-F]F @ 1",
+        angle: 25.7,
+        heading: 90.0,
+        iterations: 5,
+    },
+    Preset {
+        name: "Parametric Tree",
+        axiom: "A(100, 6)",
+        rules: "A(l, w) : l > 4 -> !(w)F(l)[+(30)A(l*0.7, w*0.7)][-(40)A(l*0.6, w*0.6)]",
+        angle: 30.0,
+        heading: 90.0,
+        iterations: 12,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The symbols of a word without their parameters
+    fn symbols(word: &[Module]) -> String {
+        word.iter().map(|m| m.symbol).collect()
+    }
+
+    #[test]
+    fn plain_rules() {
+        let grammar = Grammar::parse("F++F++F", "# Koch snowflake\n\n  F -> F-F++F-F  ").unwrap();
+        let (word, iterations) = grammar.generate(1, 0, 1000);
+        assert_eq!(symbols(&word), "F-F++F-F++F-F++F-F++F-F++F-F");
+        assert_eq!(iterations, 1);
+
+        // every F turns into 4, the other symbols stay
+        let (word, _) = grammar.generate(3, 0, 10000);
+        assert_eq!(
+            word.iter().filter(|m| m.symbol == 'F').count(),
+            3 * 4 * 4 * 4
+        );
+    }
+
+    #[test]
+    fn parametric_rules() {
+        let grammar = Grammar::parse(
+            "A(100, 6)",
+            "A(l, w) : l > 4 -> !(w)F(l)[+(30)A(l*0.7, w*0.7)][-(40)A(l*0.6, w*0.6)]",
+        )
+        .unwrap();
+        let (word, _) = grammar.generate(1, 0, 1000);
+        assert_eq!(symbols(&word), "!F[+A][-A]");
+        let params: Vec<f32> = word.iter().flat_map(|m| m.params.clone()).collect();
+        let expected = [6.0, 100.0, 30.0, 70.0, 4.2, 40.0, 60.0, 3.6];
+        assert_eq!(params.len(), expected.len());
+        for (param, expected) in params.iter().zip(expected) {
+            assert!((param - expected).abs() < 1e-4);
+        }
+
+        // the condition stops the rewriting once every branch is 4 or shorter
+        let (finished, _) = grammar.generate(20, 0, 100_000);
+        let (longer, _) = grammar.generate(40, 0, 100_000);
+        assert_eq!(finished, longer);
+        assert!(finished
+            .iter()
+            .filter(|m| m.symbol == 'A')
+            .all(|m| m.params[0] <= 4.0));
+    }
+
+    #[test]
+    fn stochastic_rules() {
+        let grammar = Grammar::parse("F", "F -> F[+F]F @ 1\nF -> F[-F]F @ 1").unwrap();
+        let (word, _) = grammar.generate(4, 7, 10000);
+        assert_eq!(grammar.generate(4, 7, 10000).0, word);
+        assert!(symbols(&word).contains('+') && symbols(&word).contains('-'));
+
+        // a rule with no weight is never chosen
+        let grammar = Grammar::parse("FFFF", "F -> a @ 1\nF -> b @ 0").unwrap();
+        for seed in 0..10 {
+            assert_eq!(symbols(&grammar.generate(1, seed, 100).0), "aaaa");
+        }
+    }
+
+    #[test]
+    fn generate_stops_at_the_longest_word() {
+        let grammar = Grammar::parse("F", "F -> FF").unwrap();
+        let (word, iterations) = grammar.generate(10, 0, 100);
+        assert_eq!(word.len(), 64);
+        assert_eq!(iterations, 6);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |axiom: &str, rules: &str| Grammar::parse(axiom, rules).unwrap_err();
+        assert_eq!(error("F", "F F"), "Missing \"->\" in \"F F\"");
+        assert_eq!(error("F", "F -> F @ x"), "Invalid weight \"x\"");
+        assert_eq!(error("F", "-> F"), "Missing a predecessor in \"-> F\"");
+        assert_eq!(error("F", "FF -> F"), "Invalid predecessor \"FF\"");
+        assert_eq!(error("F", "A(l) -> F(m)"), "Unknown variable \"m\"");
+        assert_eq!(error("F(1", ""), "Missing ')' after 'F'");
+        assert_eq!(
+            error("F", "A(l) -> F(l+)"),
+            "Expected a number, a variable or '('"
+        );
+        assert_eq!(error("F(2(3))", ""), "Unexpected '(' in \"2(3)\"");
+        assert_eq!(error("F((1)", ""), "Missing ')' after 'F'");
+        assert_eq!(error("F(1..2)", ""), "Invalid number \"1..2\"");
+    }
+
+    #[test]
+    fn turtle() {
+        let turtle = Turtle {
+            angle: 90.0,
+            step: 1.0,
+            heading: 0.0,
+            width_factor: 0.5,
+        };
+        let grammar = Grammar::parse("F[+!F]fF(2)", "").unwrap();
+        let segments = turtle.walk(&grammar.generate(0, 0, 100).0);
+        let ends: Vec<(Pos2, f32)> = segments
+            .iter()
+            .map(|s| (pos2(s.end.x.round(), s.end.y.round()), s.width))
+            .collect();
+
+        // turning left goes up the screen, and the stack restores the position and width
+        assert_eq!(
+            ends,
+            vec![
+                (pos2(1.0, 0.0), 1.0),
+                (pos2(1.0, -1.0), 0.5),
+                (pos2(4.0, 0.0), 1.0)
+            ]
+        );
+    }
+}
@@ -6,6 +6,23 @@
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
+    // Renders without opening a window, e.g. `cargo run -- export mandelbrot ...`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("export") => Some(rust_math::export_cli(&args[1..])),
+        Some("model") => Some(rust_math::model_cli(&args[1..])),
+        Some("zoom") => Some(rust_math::zoom_cli(&args[1..])),
+        Some("dimension") => Some(rust_math::dimension_cli(&args[1..])),
+        _ => None,
+    };
+    if let Some(result) = result {
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let app = rust_math::WrapApp::default();
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(Box::new(app), native_options);
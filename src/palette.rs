@@ -0,0 +1,102 @@
+use eframe::egui::Color32;
+
+/*
+This file contains the color palettes shared by the fractals.
+The escape-time palette was first written for the Mandelbrot set and is reused by every
+fractal that colors a pixel by how many iterations it took to escape.
+*/
+
+// Converts a hsv value, where 0 <= h, s, v <= 1
+// Source: https://stackoverflow.com/questions/17242144/javascript-convert-hsb-hsv-color-to-rgb-accurately
+pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
+    let mut r = 0.0;
+    let mut g = 0.0;
+    let mut b = 0.0;
+
+    let i = (h * 6.0).floor();
+    let f = h * 6.0 - i;
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - f * s);
+    let t = v * (1.0 - (1.0 - f) * s);
+
+    match (i as i32).rem_euclid(6) {
+        0 => {
+            r = v;
+            g = t;
+            b = p;
+        }
+        1 => {
+            r = q;
+            g = v;
+            b = p;
+        }
+        2 => {
+            r = p;
+            g = v;
+            b = t;
+        }
+        3 => {
+            r = p;
+            g = q;
+            b = v;
+        }
+        4 => {
+            r = t;
+            g = p;
+            b = v;
+        }
+        5 => {
+            r = v;
+            g = p;
+            b = q;
+        }
+        _ => println!("Something went wrong"),
+    }
+
+    let r_val = (r * 255.0).ceil();
+    let g_val = (g * 255.0).ceil();
+    let b_val = (b * 255.0).ceil();
+
+    (r_val as u8, g_val as u8, b_val as u8)
+}
+
+// Returns a color for a specific pixel given the number of steps it took to escape,
+// points that never escaped are black
+pub fn escape_time_color(i: u32, max_steps: u32) -> Color32 {
+    let hue = (359.0 * (i as f32)) / (max_steps as f32);
+    let saturation = 1.0;
+    let value = if i < max_steps { 1.0 } else { 0.0 };
+
+    let color = hsv_to_rgb(hue, saturation, value);
+    Color32::from_rgb(color.0, color.1, color.2)
+}
+
+// Colors a smooth (fractional) escape count with a rainbow that repeats every `period` steps.
+// Unlike escape_time_color the colors don't depend on the iteration limit, and the phase can be
+// moved over time to cycle the palette.
+pub fn cycling_color(steps: f32, period: f32, phase: f32) -> Color32 {
+    let hue = (steps / period + phase).rem_euclid(1.0);
+    let color = hsv_to_rgb(hue, 0.85, 1.0);
+    Color32::from_rgb(color.0, color.1, color.2)
+}
+
+// A shade of blue for a level of a recursive fractal. The shades go from light cyan at level 0 to
+// dark navy at level 6 and back again, so every level of a deep fractal gets a color.
+pub fn blue_shade(level: usize) -> Color32 {
+    let light = [144.0, 224.0, 239.0];
+    let dark = [3.0, 4.0, 94.0];
+    let t = (level % 12) as f32 / 6.0;
+    let t = if t > 1.0 { 2.0 - t } else { t };
+    let mix = |i: usize| (light[i] + (dark[i] - light[i]) * t).round() as u8;
+    Color32::from_rgb(mix(0), mix(1), mix(2))
+}
+
+// Blends between evenly spaced colors, t goes from 0 at the first color to 1 at the last one
+pub fn gradient(stops: &[Color32], t: f32) -> Color32 {
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (position.floor() as usize).min(stops.len().saturating_sub(2));
+    let f = position - i as f32;
+    let (a, b) = (stops[i], stops[(i + 1).min(stops.len() - 1)]);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f).round() as u8;
+    Color32::from_rgb(mix(a.r(), b.r()), mix(a.g(), b.g()), mix(a.b(), b.b()))
+}
@@ -0,0 +1,306 @@
+use eframe::egui;
+use egui::*;
+
+/*
+This file contains a small software rasterizer that draws egui shapes into a pixel buffer.
+It is used to render the fractals without a window, for example when exporting animations.
+Only the shapes that the fractals use are supported: line segments, rectangles, circles,
+paths and untextured meshes.
+*/
+
+// Implemented by every app that can draw itself into an image without a window
+pub trait Render {
+    fn render(&mut self, width: usize, height: usize) -> Image;
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color32>, // Row-major premultiplied sRGBA pixels
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, background: Color32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    // Creates an image and draws all the shapes into it
+    pub fn from_shapes(width: usize, height: usize, background: Color32, shapes: &[Shape]) -> Self {
+        let mut image = Self::new(width, height, background);
+        for shape in shapes {
+            image.paint_shape(shape);
+        }
+        image
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color32) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    // Blends a premultiplied color over a pixel, coverage is the fraction of the pixel covered
+    pub fn blend(&mut self, x: i64, y: i64, color: Color32, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || coverage <= 0.0 {
+            return;
+        }
+
+        let index = y as usize * self.width + x as usize;
+        let coverage = coverage.min(1.0);
+        let alpha = color.a() as f32 / 255.0 * coverage;
+        let dst = self.pixels[index];
+        let mix =
+            |src: u8, dst: u8| (src as f32 * coverage + dst as f32 * (1.0 - alpha)).round() as u8;
+
+        self.pixels[index] = Color32::from_rgba_premultiplied(
+            mix(color.r(), dst.r()),
+            mix(color.g(), dst.g()),
+            mix(color.b(), dst.b()),
+            mix(color.a(), dst.a()),
+        );
+    }
+
+    // Returns the pixels as tightly packed RGBA bytes, as expected by the image encoders
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in &self.pixels {
+            bytes.extend_from_slice(&pixel.to_array());
+        }
+        bytes
+    }
+
+    pub fn paint_shape(&mut self, shape: &Shape) {
+        match shape {
+            Shape::Noop | Shape::Text(_) => {}
+            Shape::Vec(shapes) => {
+                for shape in shapes {
+                    self.paint_shape(shape);
+                }
+            }
+            Shape::LineSegment { points, stroke } => {
+                self.paint_line(points[0], points[1], *stroke);
+            }
+            Shape::Circle(circle) => {
+                self.paint_circle(circle.center, circle.radius, circle.fill, circle.stroke);
+            }
+            Shape::Rect(rect) => {
+                self.paint_rect(rect.rect, rect.fill, rect.stroke);
+            }
+            Shape::Path(path) => {
+                if path.closed && path.fill != Color32::TRANSPARENT {
+                    self.paint_polygon(&path.points, path.fill);
+                }
+                for pair in path.points.windows(2) {
+                    self.paint_line(pair[0], pair[1], path.stroke);
+                }
+                if path.closed && path.points.len() > 2 {
+                    self.paint_line(
+                        path.points[path.points.len() - 1],
+                        path.points[0],
+                        path.stroke,
+                    );
+                }
+            }
+            Shape::Mesh(mesh) => {
+                for triangle in mesh.indices.chunks(3) {
+                    if triangle.len() == 3 {
+                        self.paint_triangle([
+                            mesh.vertices[triangle[0] as usize],
+                            mesh.vertices[triangle[1] as usize],
+                            mesh.vertices[triangle[2] as usize],
+                        ]);
+                    }
+                }
+            }
+        }
+    }
+
+    // Draws an anti-aliased line by measuring each pixel's distance to the segment
+    pub fn paint_line(&mut self, a: Pos2, b: Pos2, stroke: Stroke) {
+        if stroke.width <= 0.0 || stroke.color == Color32::TRANSPARENT {
+            return;
+        }
+
+        // Thin lines are drawn one pixel wide with reduced coverage
+        let half_width = stroke.width.max(1.0) / 2.0;
+        let thinness = stroke.width.min(1.0);
+
+        let min_x = (a.x.min(b.x) - half_width - 1.0).floor() as i64;
+        let max_x = (a.x.max(b.x) + half_width + 1.0).ceil() as i64;
+        let min_y = (a.y.min(b.y) - half_width - 1.0).floor() as i64;
+        let max_y = (a.y.max(b.y) + half_width + 1.0).ceil() as i64;
+        let (min_x, max_x, min_y, max_y) = self.clamp_bounds(min_x, max_x, min_y, max_y);
+
+        let direction = b - a;
+        let length_sq = direction.length_sq();
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = pos2(x as f32 + 0.5, y as f32 + 0.5);
+                let t = if length_sq > 0.0 {
+                    let to_p = p - a;
+                    ((to_p.x * direction.x + to_p.y * direction.y) / length_sq).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = (p - (a + direction * t)).length();
+                let coverage = (half_width + 0.5 - distance).clamp(0.0, 1.0) * thinness;
+                self.blend(x, y, stroke.color, coverage);
+            }
+        }
+    }
+
+    pub fn paint_rect(&mut self, rect: Rect, fill: Color32, stroke: Stroke) {
+        if fill != Color32::TRANSPARENT {
+            let (min_x, max_x, min_y, max_y) = self.clamp_bounds(
+                rect.left().floor() as i64,
+                rect.right().ceil() as i64,
+                rect.top().floor() as i64,
+                rect.bottom().ceil() as i64,
+            );
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    // The fraction of the pixel that lies inside of the rectangle
+                    let overlap_x =
+                        (rect.right().min(x as f32 + 1.0) - rect.left().max(x as f32)).max(0.0);
+                    let overlap_y =
+                        (rect.bottom().min(y as f32 + 1.0) - rect.top().max(y as f32)).max(0.0);
+                    self.blend(x, y, fill, overlap_x * overlap_y);
+                }
+            }
+        }
+
+        let corners = [
+            rect.left_top(),
+            rect.right_top(),
+            rect.right_bottom(),
+            rect.left_bottom(),
+        ];
+        for i in 0..4 {
+            self.paint_line(corners[i], corners[(i + 1) % 4], stroke);
+        }
+    }
+
+    pub fn paint_circle(&mut self, center: Pos2, radius: f32, fill: Color32, stroke: Stroke) {
+        if fill != Color32::TRANSPARENT {
+            let (min_x, max_x, min_y, max_y) = self.clamp_bounds(
+                (center.x - radius - 1.0).floor() as i64,
+                (center.x + radius + 1.0).ceil() as i64,
+                (center.y - radius - 1.0).floor() as i64,
+                (center.y + radius + 1.0).ceil() as i64,
+            );
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let p = pos2(x as f32 + 0.5, y as f32 + 0.5);
+                    let coverage = (radius + 0.5 - p.distance(center)).clamp(0.0, 1.0);
+                    self.blend(x, y, fill, coverage);
+                }
+            }
+        }
+
+        if stroke.width > 0.0 {
+            let segments = ((radius * 0.5) as usize).clamp(8, 128);
+            for i in 0..segments {
+                let a = i as f32 / segments as f32 * std::f32::consts::TAU;
+                let b = (i + 1) as f32 / segments as f32 * std::f32::consts::TAU;
+                self.paint_line(
+                    center + radius * vec2(a.cos(), a.sin()),
+                    center + radius * vec2(b.cos(), b.sin()),
+                    stroke,
+                );
+            }
+        }
+    }
+
+    // Fills a polygon using the even-odd rule, sampling each pixel at its center
+    pub fn paint_polygon(&mut self, points: &[Pos2], fill: Color32) {
+        if points.len() < 3 {
+            return;
+        }
+
+        let bounds = Rect::from_points(points);
+        let (min_x, max_x, min_y, max_y) = self.clamp_bounds(
+            bounds.left().floor() as i64,
+            bounds.right().ceil() as i64,
+            bounds.top().floor() as i64,
+            bounds.bottom().ceil() as i64,
+        );
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                if point_in_polygon(pos2(x as f32 + 0.5, y as f32 + 0.5), points) {
+                    self.blend(x, y, fill, 1.0);
+                }
+            }
+        }
+    }
+
+    // Fills a triangle, interpolating the colors of its vertices
+    fn paint_triangle(&mut self, vertices: [epaint::Vertex; 3]) {
+        let [a, b, c] = [vertices[0].pos, vertices[1].pos, vertices[2].pos];
+        let area = (b - a).x * (c - a).y - (b - a).y * (c - a).x;
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+
+        let bounds = Rect::from_points(&[a, b, c]);
+        let (min_x, max_x, min_y, max_y) = self.clamp_bounds(
+            bounds.left().floor() as i64,
+            bounds.right().ceil() as i64,
+            bounds.top().floor() as i64,
+            bounds.bottom().ceil() as i64,
+        );
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = pos2(x as f32 + 0.5, y as f32 + 0.5);
+
+                // Barycentric coordinates of the pixel center
+                let w0 = ((b - p).x * (c - p).y - (b - p).y * (c - p).x) / area;
+                let w1 = ((c - p).x * (a - p).y - (c - p).y * (a - p).x) / area;
+                let w2 = 1.0 - w0 - w1;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let channel = |i: usize| {
+                    let value = w0 * vertices[0].color[i] as f32
+                        + w1 * vertices[1].color[i] as f32
+                        + w2 * vertices[2].color[i] as f32;
+                    value.round().clamp(0.0, 255.0) as u8
+                };
+                let color = Color32::from_rgba_premultiplied(
+                    channel(0),
+                    channel(1),
+                    channel(2),
+                    channel(3),
+                );
+                self.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    // Clamps a pixel bounding box to the image, the max bounds are exclusive
+    fn clamp_bounds(&self, min_x: i64, max_x: i64, min_y: i64, max_y: i64) -> (i64, i64, i64, i64) {
+        (
+            min_x.max(0),
+            max_x.min(self.width as i64),
+            min_y.max(0),
+            max_y.min(self.height as i64),
+        )
+    }
+}
+
+// Even-odd test for whether a point lies inside of a polygon
+pub fn point_in_polygon(p: Pos2, points: &[Pos2]) -> bool {
+    let mut inside = false;
+    let mut j = points.len() - 1;
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[j]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/*
+This file contains the 3D fractal solids and the triangle meshes they are drawn with.
+The Menger sponge is the 3D sibling of the Sierpinski carpet: a cube is split into 27 smaller
+cubes and the middle one and the 6 in the middle of each face are removed. A cube of the grid at
+some level is in the sponge when none of its levels has two or more coordinates in the middle.
+Only the faces between a cube and an empty neighbor are kept, so the inside of the sponge costs
+nothing. The sponge can be cut along an axis, which shows carpet-like patterns on the cut.
+The Sierpinski tetrahedron keeps the 4 half-size tetrahedra in the corners of a tetrahedron.
+Flat fractals are raised into slabs made of boxes in the same way, or drawn with tubes.
+The sponge and the tetrahedron fit in the cube from -0.5 to 0.5, and the triangles of every solid
+wind counter-clockwise when seen from outside, which is what 3D printing slicers expect.
+Fractal Explanation: https://en.wikipedia.org/wiki/Menger_sponge
+Source: https://en.wikipedia.org/wiki/Sierpi%C5%84ski_triangle#Analogues_in_higher_dimensions
+*/
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn normalized(self) -> Vec3 {
+        let length = self.dot(self).sqrt();
+        if length > 0.0 {
+            self * (1.0 / length)
+        } else {
+            self
+        }
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, factor: f32) -> Vec3 {
+        Vec3::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+// A triangle of a solid, its corners are indices into the solid's vertices
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Triangle {
+    pub corners: [usize; 3],
+    pub normal: Vec3, // Points out of the solid
+    pub cut: bool,    // If true, the triangle is on the cut through the solid
+}
+
+// A triangle mesh, where triangles share the vertices at the same position
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Solid {
+    pub vertices: Vec<Vec3>,
+    pub triangles: Vec<Triangle>,
+    vertex_index: HashMap<([i64; 3], usize), usize>, // The vertex of every part at a position
+}
+
+impl Solid {
+    // The vertex at a position, which is given exactly by its key on a fine grid. Parts of the
+    // solid that only touch at the position get their own vertex, so that no edge is shared by
+    // more than 2 triangles.
+    fn vertex(&mut self, key: [i64; 3], part: usize, position: Vec3) -> usize {
+        let vertices = &mut self.vertices;
+        *self.vertex_index.entry((key, part)).or_insert_with(|| {
+            vertices.push(position);
+            vertices.len() - 1
+        })
+    }
+
+    // A vertex that isn't shared with the other parts of the solid
+    fn new_vertex(&mut self, position: Vec3) -> usize {
+        self.vertices.push(position);
+        self.vertices.len() - 1
+    }
+
+    // Adds a triangle, turning it so that it winds counter-clockwise around the outward normal
+    fn triangle(&mut self, corners: [usize; 3], outward: Vec3, cut: bool) {
+        let [a, b, c] = corners.map(|i| self.vertices[i]);
+        let normal = (b - a).cross(c - a).normalized();
+        let (corners, normal) = if normal.dot(outward) < 0.0 {
+            ([corners[0], corners[2], corners[1]], -normal)
+        } else {
+            (corners, normal)
+        };
+        self.triangles.push(Triangle {
+            corners,
+            normal,
+            cut,
+        });
+    }
+}
+
+// The axis that the sponge is cut along
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Z => "Z",
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+// Whether a cube of the 3^level grid is in the Menger sponge
+pub fn in_menger_sponge(cell: [usize; 3], level: usize) -> bool {
+    let mut cell = cell;
+    for _ in 0..level {
+        let middles = cell.iter().filter(|&&c| c % 3 == 1).count();
+        if middles >= 2 {
+            return false;
+        }
+        cell = cell.map(|c| c / 3);
+    }
+    true
+}
+
+// The 6 directions out of a cube
+const DIRECTIONS: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+// The part of the solid that a kept box belongs to at one of its corners. The 8 boxes around the
+// corner are numbered by their offset from it, and kept boxes that share a face are in the same
+// part, which is named by its lowest number. Boxes that only touch along an edge or at the corner
+// are in different parts.
+fn corner_part(corner: [i64; 3], cell: [i64; 3], kept: impl Fn([i64; 3]) -> bool) -> usize {
+    let around = |i: usize| [0, 1, 2].map(|axis| corner[axis] - 1 + (i >> axis & 1) as i64);
+    let mut part: [usize; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+    // joining the boxes across the 12 faces around the corner until nothing changes
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..8 {
+            for axis in 0..3 {
+                let j = i ^ (1 << axis);
+                if part[j] < part[i] && kept(around(i)) && kept(around(j)) {
+                    part[i] = part[j];
+                    changed = true;
+                }
+            }
+        }
+    }
+    let index = (0..8).find(|&i| around(i) == cell).unwrap();
+    part[index]
+}
+
+// A solid made of the boxes of a grid, with only the faces between a kept box and an empty
+// neighbor, so no two faces ever lie on top of each other. position gives the place of every
+// corner of the grid, and cut tells if the face towards an empty neighbor lies on a cut.
+fn boxes(
+    size: [i64; 3],
+    kept: impl Fn([i64; 3]) -> bool,
+    cut: impl Fn([i64; 3]) -> bool,
+    position: impl Fn([i64; 3]) -> Vec3,
+) -> Solid {
+    let mut solid = Solid::default();
+    for x in 0..size[0] {
+        for y in 0..size[1] {
+            for z in 0..size[2] {
+                let cell = [x, y, z];
+                if !kept(cell) {
+                    continue;
+                }
+                for direction in DIRECTIONS {
+                    let neighbor = [0, 1, 2].map(|i| cell[i] + direction[i]);
+                    if kept(neighbor) {
+                        continue;
+                    }
+
+                    // the 4 corners of the face towards the neighbor
+                    let axis = direction.iter().position(|&d| d != 0).unwrap();
+                    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                    let mut corners = [0; 4];
+                    for (i, &(du, dv)) in [(0, 0), (1, 0), (1, 1), (0, 1)].iter().enumerate() {
+                        let mut key = cell;
+                        key[axis] += direction[axis].max(0);
+                        key[u] += du;
+                        key[v] += dv;
+                        let part = corner_part(key, cell, &kept);
+                        corners[i] = solid.vertex(key, part, position(key));
+                    }
+
+                    let outward = Vec3::new(
+                        direction[0] as f32,
+                        direction[1] as f32,
+                        direction[2] as f32,
+                    );
+                    let cut = cut(neighbor);
+                    solid.triangle([corners[0], corners[1], corners[2]], outward, cut);
+                    solid.triangle([corners[0], corners[2], corners[3]], outward, cut);
+                }
+            }
+        }
+    }
+    solid
+}
+
+// The Menger sponge at a level. With a cut, the cubes below the given layer along the axis are
+// removed, and the faces on the cut are marked.
+pub fn menger_sponge(level: usize, cut: Option<(Axis, usize)>) -> Solid {
+    let size = 3_usize.pow(level as u32) as i64;
+    let in_sponge = |cell: [i64; 3]| {
+        cell.iter().all(|&c| (0..size).contains(&c))
+            && in_menger_sponge(cell.map(|c| c as usize), level)
+    };
+    let kept = |cell: [i64; 3]| {
+        in_sponge(cell) && cut.is_none_or(|(axis, layer)| cell[axis.index()] >= layer as i64)
+    };
+    let position = |key: [i64; 3]| {
+        let p = key.map(|k| k as f32 / size as f32 - 0.5);
+        Vec3::new(p[0], p[1], p[2])
+    };
+    boxes([size; 3], kept, in_sponge, position)
+}
+
+// A flat grid of cells (row by row) raised into a slab, lying on the x-z plane with rows along z
+pub fn extrude(columns: usize, rows: usize, filled: &[bool], thickness: f32) -> Solid {
+    let size = [columns as i64, 1, rows as i64];
+    let kept = |cell: [i64; 3]| {
+        (0..size[0]).contains(&cell[0])
+            && cell[1] == 0
+            && (0..size[2]).contains(&cell[2])
+            && filled[cell[2] as usize * columns + cell[0] as usize]
+    };
+    let position = |key: [i64; 3]| {
+        Vec3::new(
+            key[0] as f32 / columns as f32 - 0.5,
+            key[1] as f32 * thickness,
+            key[2] as f32 / rows as f32 - 0.5,
+        )
+    };
+    boxes(size, kept, |_| false, position)
+}
+
+// Tubes with 8 sides around line segments on the x-z plane, given by their ends and widths.
+// Every tube has a flat side at the bottom so they all rest on y = 0, and it reaches half its
+// width past both ends to close the gaps where the tubes meet. The tubes aren't joined, every
+// tube is a closed shell of its own that overlaps its neighbors, which slicers merge by printing
+// everything that is inside of any shell.
+pub fn tubes(segments: &[(Vec3, Vec3, f32)]) -> Solid {
+    const SIDES: usize = 8;
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let half_angle = std::f32::consts::PI / SIDES as f32;
+
+    let mut solid = Solid::default();
+    for &(a, b, width) in segments {
+        let along = (b - a).normalized();
+        if along.dot(along) == 0.0 {
+            continue;
+        }
+        let side = along.cross(up).normalized();
+
+        // the corners of the octagon, between the flat sides
+        let apothem = width / 2.0;
+        let radius = apothem / half_angle.cos();
+        let ring: Vec<Vec3> = (0..SIDES)
+            .map(|k| {
+                let angle = (2 * k + 1) as f32 * half_angle;
+                side * (radius * angle.cos()) + up * (radius * angle.sin() + apothem)
+            })
+            .collect();
+        let (start, end) = (a - along * apothem, b + along * apothem);
+        let starts: Vec<usize> = ring.iter().map(|&p| solid.new_vertex(start + p)).collect();
+        let ends: Vec<usize> = ring.iter().map(|&p| solid.new_vertex(end + p)).collect();
+
+        for k in 0..SIDES {
+            let next = (k + 1) % SIDES;
+            let outward = ring[k] + ring[next] - up * (2.0 * apothem);
+            solid.triangle([starts[k], starts[next], ends[next]], outward, false);
+            solid.triangle([starts[k], ends[next], ends[k]], outward, false);
+        }
+        for k in 1..SIDES - 1 {
+            solid.triangle([starts[0], starts[k], starts[k + 1]], -along, false);
+            solid.triangle([ends[0], ends[k], ends[k + 1]], along, false);
+        }
+    }
+    solid
+}
+
+// The Sierpinski tetrahedron at a level
+pub fn sierpinski_tetrahedron(level: usize) -> Solid {
+    // every other corner of the cube, the corners of smaller tetrahedra are on a 2^level grid
+    let corners =
+        [[1, 1, 1], [1, -1, -1], [-1, 1, -1], [-1, -1, 1]].map(|c: [i64; 3]| c.map(|c| c << level));
+    let mut solid = Solid::default();
+    add_tetrahedron(&mut solid, corners, level, 1 << (level + 1));
+    solid
+}
+
+// A recursive function that adds the tetrahedra in the corners of a tetrahedron, whose corners
+// are given on a grid with size points from -0.5 to 0.5
+fn add_tetrahedron(solid: &mut Solid, corners: [[i64; 3]; 4], level: usize, size: i64) {
+    if level > 0 {
+        let middle = |a: [i64; 3], b: [i64; 3]| [0, 1, 2].map(|i| (a[i] + b[i]) / 2);
+        for i in 0..4 {
+            let smaller = [0, 1, 2, 3].map(|j| middle(corners[i], corners[j]));
+            add_tetrahedron(solid, smaller, level - 1, size);
+        }
+        return;
+    }
+
+    let position = |key: [i64; 3]| {
+        let p = key.map(|k| k as f32 / size as f32);
+        Vec3::new(p[0], p[1], p[2])
+    };
+    let indices = corners.map(|key| solid.vertex(key, 0, position(key)));
+    for opposite in 0..4 {
+        // the face across from each corner points away from it
+        let face: Vec<usize> = (0..4).filter(|&i| i != opposite).collect();
+        let center = face
+            .iter()
+            .fold(Vec3::default(), |sum, &i| sum + position(corners[i]))
+            * (1.0 / 3.0);
+        let outward = center - position(corners[opposite]);
+        let face = [indices[face[0]], indices[face[1]], indices[face[2]]];
+        solid.triangle(face, outward, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whether every edge is shared by exactly 2 triangles that go along it in opposite
+    // directions, which makes the solid a closed surface with a consistent winding
+    fn is_closed(solid: &Solid) -> bool {
+        let mut edges = HashMap::new();
+        for triangle in &solid.triangles {
+            let [a, b, c] = triangle.corners;
+            for edge in [(a, b), (b, c), (c, a)] {
+                *edges.entry(edge).or_insert(0) += 1;
+            }
+        }
+        edges
+            .iter()
+            .all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1))
+    }
+
+    #[test]
+    fn menger_sponge_cells() {
+        assert!(in_menger_sponge([0, 0, 0], 1));
+        assert!(in_menger_sponge([1, 0, 2], 1));
+        assert!(!in_menger_sponge([1, 1, 0], 1));
+        assert!(!in_menger_sponge([1, 1, 1], 1));
+
+        // the cube in the middle of a face of a kept cube of level 1
+        assert!(in_menger_sponge([3, 0, 1], 2));
+        assert!(!in_menger_sponge([4, 4, 0], 2));
+        assert!(!in_menger_sponge([3, 3, 0], 2));
+
+        // the sponge keeps 20 of the 27 cubes at every level
+        for level in 0..4 {
+            let size = 3_usize.pow(level as u32);
+            let mut count = 0;
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        count += in_menger_sponge([x, y, z], level) as usize;
+                    }
+                }
+            }
+            assert_eq!(count, 20_usize.pow(level as u32));
+        }
+    }
+
+    #[test]
+    fn menger_sponge_is_closed() {
+        for level in 0..4 {
+            assert!(is_closed(&menger_sponge(level, None)));
+        }
+        for axis in Axis::ALL {
+            assert!(is_closed(&menger_sponge(2, Some((axis, 4)))));
+        }
+
+        // a cube has 12 triangles and 8 corners
+        let cube = menger_sponge(0, None);
+        assert_eq!(cube.triangles.len(), 12);
+        assert_eq!(cube.vertices.len(), 8);
+    }
+
+    #[test]
+    fn sierpinski_tetrahedron_is_closed() {
+        for level in 0..5 {
+            let solid = sierpinski_tetrahedron(level);
+            assert!(is_closed(&solid));
+            assert_eq!(solid.triangles.len(), 4 * 4_usize.pow(level as u32));
+        }
+    }
+
+    #[test]
+    fn extrude_splits_cells_that_touch_at_a_corner() {
+        // a checkerboard, where the filled cells only touch along their vertical edges
+        let filled = [true, false, true, false, true, false, true, false, true];
+        let solid = extrude(3, 3, &filled, 0.1);
+        assert!(is_closed(&solid));
+
+        // 5 separate boxes with 8 corners each
+        assert_eq!(solid.triangles.len(), 5 * 12);
+        assert_eq!(solid.vertices.len(), 5 * 8);
+
+        // cells side by side still share their vertices
+        let solid = extrude(2, 1, &[true, true], 0.1);
+        assert!(is_closed(&solid));
+        assert_eq!(solid.vertices.len(), 12);
+    }
+
+    #[test]
+    fn tubes_are_separate_shells() {
+        let segments = [
+            (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.2),
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 1.0), 0.1),
+            (Vec3::new(1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 1.0), 0.1),
+        ];
+        let solid = tubes(&segments);
+        assert!(is_closed(&solid));
+
+        // the empty segment is skipped, the others are 2 octagons joined by 8 sides
+        assert_eq!(solid.vertices.len(), 2 * 16);
+        assert_eq!(solid.triangles.len(), 2 * (16 + 2 * 6));
+
+        // every tube rests on y = 0
+        let lowest = solid.vertices.iter().map(|v| v.y).fold(f32::MAX, f32::min);
+        assert!(lowest.abs() < 1e-6);
+    }
+}
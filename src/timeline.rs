@@ -0,0 +1,491 @@
+use eframe::egui;
+use egui::{widgets::*, *};
+use std::ops::RangeInclusive;
+
+/*
+This file contains a keyframe timeline for animating the numeric parameters of the fractals.
+Every parameter that an app exposes through the Animate trait can get its own track of keyframes.
+While the timeline is playing (or being scrubbed), the value of each track is interpolated between
+its keyframes using an easing curve and written back into the app before it is drawn.
+
+Source: https://easings.net/
+*/
+
+// A numeric parameter of an app that can be keyframed
+pub struct Parameter {
+    pub name: &'static str, // The label of the parameter, same as its slider
+    pub value: f32,         // The current value of the parameter
+    pub range: RangeInclusive<f32>, // The values the parameter is allowed to take
+}
+
+impl Parameter {
+    pub fn new(name: &'static str, value: f32, range: RangeInclusive<f32>) -> Self {
+        Self { name, value, range }
+    }
+}
+
+// Implemented by every app whose parameters can be driven by the timeline
+pub trait Animate {
+    // Lists the parameters along with their current values
+    fn parameters(&self) -> Vec<Parameter>;
+
+    // Sets a parameter by name, integer parameters round the value
+    fn set_parameter(&mut self, name: &str, value: f32);
+}
+
+// The curve used to interpolate from one keyframe to the next
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Step,
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 5] = [
+        Easing::Step,
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Easing::Step => "Step",
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease In",
+            Easing::EaseOut => "Ease Out",
+            Easing::EaseInOut => "Ease In-Out",
+        }
+    }
+
+    // Maps the progress t (0 <= t <= 1) between two keyframes onto the eased progress
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+// A value at a point in time, the easing is used for the segment towards the next keyframe
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+    pub easing: Easing,
+}
+
+// All the keyframes of a single parameter, kept sorted by time
+#[derive(Clone, PartialEq, Debug)]
+pub struct Track {
+    pub parameter: String,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(parameter: &str) -> Self {
+        Self {
+            parameter: parameter.to_owned(),
+            keyframes: Vec::new(),
+        }
+    }
+
+    // Adds a keyframe, replacing any keyframe that is already at the same time
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        self.keyframes
+            .retain(|k| (k.time - keyframe.time).abs() > f32::EPSILON);
+        self.keyframes.push(keyframe);
+        self.sort();
+    }
+
+    pub fn sort(&mut self) {
+        self.keyframes
+            .sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    }
+
+    // Interpolates the value of the track at a given time
+    pub fn value_at(&self, time: f32) -> Option<f32> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+
+        // Hold the first and last values outside of the keyframes
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+
+        for pair in self.keyframes.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if time >= from.time && time < to.time {
+                let t = (time - from.time) / (to.time - from.time);
+                let eased = from.easing.apply(t);
+                return Some(from.value + (to.value - from.value) * eased);
+            }
+        }
+
+        Some(last.value)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Timeline {
+    pub time: f32,              // The position of the playhead in seconds
+    pub duration: f32,          // The length of the timeline in seconds
+    pub playing: bool,          // If true, the playhead moves forward every frame
+    pub looping: bool,          // If true, the playhead jumps back to 0 at the end
+    pub tracks: Vec<Track>,     // One track for each keyframed parameter
+    selected_parameter: String, // The parameter that "Add Keyframe" will key
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            duration: 5.0,
+            playing: false,
+            looping: true,
+            tracks: Vec::new(),
+            selected_parameter: String::new(),
+        }
+    }
+}
+
+impl Timeline {
+    // Moves the playhead forward by dt seconds
+    pub fn advance(&mut self, dt: f32) {
+        if !self.playing {
+            return;
+        }
+
+        self.time += dt;
+        if self.time > self.duration {
+            if self.looping && self.duration > 0.0 {
+                self.time %= self.duration;
+            } else {
+                self.time = self.duration;
+                self.playing = false;
+            }
+        }
+    }
+
+    // Writes the value of every track at the current time into the app
+    pub fn apply<A: Animate + ?Sized>(&self, app: &mut A) {
+        self.apply_at(self.time, app);
+    }
+
+    // Writes the value of every track at the given time into the app
+    pub fn apply_at<A: Animate + ?Sized>(&self, time: f32, app: &mut A) {
+        let parameters = app.parameters();
+        for track in &self.tracks {
+            let parameter = parameters.iter().find(|p| p.name == track.parameter);
+            if let (Some(parameter), Some(value)) = (parameter, track.value_at(time)) {
+                let value = value.clamp(*parameter.range.start(), *parameter.range.end());
+                app.set_parameter(parameter.name, value);
+            }
+        }
+    }
+
+    // Keys the current value of a parameter at the playhead
+    pub fn add_keyframe(&mut self, parameter: &Parameter) {
+        let keyframe = Keyframe {
+            time: self.time,
+            value: parameter.value,
+            easing: Easing::Linear,
+        };
+
+        match self
+            .tracks
+            .iter_mut()
+            .find(|t| t.parameter == parameter.name)
+        {
+            Some(track) => track.insert(keyframe),
+            None => {
+                let mut track = Track::new(parameter.name);
+                track.insert(keyframe);
+                self.tracks.push(track);
+            }
+        }
+    }
+
+    // Draws the timeline panel, returns true if the app needs the track values applied
+    pub fn ui<A: Animate + ?Sized>(&mut self, ui: &mut Ui, app: &mut A) -> bool {
+        let mut scrubbed = false;
+        let parameters = app.parameters();
+
+        if !parameters.iter().any(|p| p.name == self.selected_parameter) {
+            if let Some(parameter) = parameters.first() {
+                self.selected_parameter = parameter.name.to_owned();
+            }
+        }
+
+        // Transport controls
+        ui.horizontal(|ui| {
+            let play_label = if self.playing {
+                "⏸ Pause"
+            } else {
+                "▶ Play"
+            };
+            if ui.button(play_label).clicked() {
+                if !self.playing && self.time >= self.duration {
+                    self.time = 0.0;
+                }
+                self.playing = !self.playing;
+            }
+            if ui.button("⏮ Rewind").clicked() {
+                self.time = 0.0;
+                scrubbed = true;
+            }
+            ui.checkbox(&mut self.looping, "Loop");
+
+            ui.separator();
+
+            ui.label("Duration");
+            ui.add(
+                DragValue::new(&mut self.duration)
+                    .speed(0.1)
+                    .clamp_range(0.1..=600.0)
+                    .suffix(" s"),
+            );
+        });
+
+        let duration = self.duration;
+        if ui
+            .add(Slider::new(&mut self.time, 0.0..=duration).text("Time"))
+            .changed()
+        {
+            scrubbed = true;
+        }
+
+        // Keyframing the currently selected parameter
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("timeline_parameter")
+                .selected_text(self.selected_parameter.clone())
+                .show_ui(ui, |ui| {
+                    for parameter in &parameters {
+                        ui.selectable_value(
+                            &mut self.selected_parameter,
+                            parameter.name.to_owned(),
+                            parameter.name,
+                        );
+                    }
+                });
+
+            if ui.button("◆ Add Keyframe").clicked() {
+                if let Some(parameter) = parameters
+                    .iter()
+                    .find(|p| p.name == self.selected_parameter)
+                {
+                    self.add_keyframe(parameter);
+                }
+            }
+        });
+
+        // One strip and one editable list of keyframes per track
+        let time = self.time;
+        let mut new_time = None;
+        let mut remove_track = None;
+        for (track_index, track) in self.tracks.iter_mut().enumerate() {
+            let range = parameters
+                .iter()
+                .find(|p| p.name == track.parameter)
+                .map(|p| p.range.clone())
+                .unwrap_or(0.0..=1.0);
+
+            ui.horizontal(|ui| {
+                ui.add_sized([120.0, 16.0], Label::new(&track.parameter));
+                if let Some(scrub) = Self::track_strip(ui, track, time, duration) {
+                    new_time = Some(scrub);
+                }
+            });
+
+            CollapsingHeader::new(format!("{} keyframes", track.parameter))
+                .id_source(("timeline_track", track_index))
+                .show(ui, |ui| {
+                    let mut remove_keyframe = None;
+                    let mut resort = false;
+                    for (index, keyframe) in track.keyframes.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            resort |= ui
+                                .add(
+                                    DragValue::new(&mut keyframe.time)
+                                        .speed(0.01)
+                                        .clamp_range(0.0..=duration)
+                                        .prefix("t: "),
+                                )
+                                .changed();
+                            ui.add(
+                                DragValue::new(&mut keyframe.value)
+                                    .speed(0.01)
+                                    .clamp_range(range.clone())
+                                    .prefix("value: "),
+                            );
+                            ComboBox::from_id_source(("timeline_easing", track_index, index))
+                                .selected_text(keyframe.easing.name())
+                                .show_ui(ui, |ui| {
+                                    for easing in Easing::ALL.iter() {
+                                        ui.selectable_value(
+                                            &mut keyframe.easing,
+                                            *easing,
+                                            easing.name(),
+                                        );
+                                    }
+                                });
+                            if ui.small_button("🗑").clicked() {
+                                remove_keyframe = Some(index);
+                            }
+                        });
+                    }
+                    if let Some(index) = remove_keyframe {
+                        track.keyframes.remove(index);
+                    }
+                    if resort {
+                        track.sort();
+                    }
+                    if ui.button("Remove Track").clicked() {
+                        remove_track = Some(track_index);
+                    }
+                });
+        }
+        if let Some(index) = remove_track {
+            self.tracks.remove(index);
+        }
+        if let Some(time) = new_time {
+            self.time = time;
+            scrubbed = true;
+        }
+
+        scrubbed
+    }
+
+    // Draws the keyframes of a track and the playhead, returns a new time if the strip was clicked
+    fn track_strip(ui: &mut Ui, track: &Track, time: f32, duration: f32) -> Option<f32> {
+        let width = ui.available_width().max(100.0);
+        let (response, painter) = ui.allocate_painter(vec2(width, 16.0), Sense::click_and_drag());
+        let rect = response.rect;
+        let to_x = |t: f32| rect.left() + rect.width() * (t / duration).clamp(0.0, 1.0);
+
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        for keyframe in &track.keyframes {
+            let center = pos2(to_x(keyframe.time), rect.center().y);
+            let size = 5.0;
+            painter.add(Shape::convex_polygon(
+                vec![
+                    center + vec2(0.0, -size),
+                    center + vec2(size, 0.0),
+                    center + vec2(0.0, size),
+                    center + vec2(-size, 0.0),
+                ],
+                Color32::GOLD,
+                Stroke::none(),
+            ));
+        }
+        let x = to_x(time);
+        painter.line_segment(
+            [pos2(x, rect.top()), pos2(x, rect.bottom())],
+            (1.5, Color32::RED),
+        );
+
+        let pointer = response.interact_pointer_pos()?;
+        if response.clicked() || response.dragged() {
+            let t = (pointer.x - rect.left()) / rect.width();
+            Some(t.clamp(0.0, 1.0) * duration)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, value: f32, easing: Easing) -> Keyframe {
+        Keyframe {
+            time,
+            value,
+            easing,
+        }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn easings() {
+        for easing in Easing::ALL {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+
+            // the progress is clamped to the segment
+            assert_eq!(easing.apply(-1.0), 0.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+
+        assert_close(Easing::Step.apply(0.99), 0.0);
+        assert_close(Easing::Linear.apply(0.25), 0.25);
+        assert_close(Easing::EaseIn.apply(0.5), 0.125);
+        assert_close(Easing::EaseOut.apply(0.5), 0.875);
+        assert_close(Easing::EaseInOut.apply(0.25), 0.0625);
+        assert_close(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_close(Easing::EaseInOut.apply(0.75), 0.9375);
+    }
+
+    #[test]
+    fn value_at() {
+        let mut track = Track::new("Depth");
+        assert_eq!(track.value_at(1.0), None);
+
+        track.insert(keyframe(2.0, 10.0, Easing::Linear));
+        track.insert(keyframe(0.0, 0.0, Easing::EaseIn));
+        track.insert(keyframe(3.0, 4.0, Easing::Step));
+
+        // the values before the first and after the last keyframe are held
+        assert_eq!(track.value_at(-1.0), Some(0.0));
+        assert_eq!(track.value_at(5.0), Some(4.0));
+
+        // every segment uses the easing of its first keyframe
+        assert_close(track.value_at(1.0).unwrap(), 1.25);
+        assert_close(track.value_at(2.0).unwrap(), 10.0);
+        assert_close(track.value_at(2.5).unwrap(), 7.0);
+        assert_close(track.value_at(3.0).unwrap(), 4.0);
+    }
+
+    #[test]
+    fn insert_replaces_keyframes_at_the_same_time() {
+        let mut track = Track::new("Depth");
+        track.insert(keyframe(1.0, 1.0, Easing::Linear));
+        track.insert(keyframe(0.0, 0.0, Easing::Linear));
+        track.insert(keyframe(1.0, 5.0, Easing::Step));
+        assert_eq!(
+            track.keyframes,
+            vec![
+                keyframe(0.0, 0.0, Easing::Linear),
+                keyframe(1.0, 5.0, Easing::Step)
+            ]
+        );
+        assert_eq!(track.value_at(1.0), Some(5.0));
+    }
+}
@@ -0,0 +1,532 @@
+use num::complex::Complex;
+use std::collections::{BTreeMap, HashSet};
+
+/*
+This file contains the combinatorics on words used by the word fractals.
+Words are stored as bytes, one digit ('0' or '1') per letter.
+A Sturmian word is an infinite word with exactly n + 1 different factors (subwords) of every
+length n, which is the least a word can have without becoming periodic. The Fibonacci word is the
+best known one, and every other one can be made from a line with an irrational slope by writing
+down which steps of the line cross a whole number.
+Fractal Explanation: https://en.wikipedia.org/wiki/Fibonacci_word
+Sturmian words: https://en.wikipedia.org/wiki/Sturmian_word
+*/
+
+pub const GOLDEN_RATIO: f64 = 1.618_033_988_749_895;
+
+// The letters of the n-th Fibonacci word, where S(0) = 0, S(1) = 01 and S(n) = S(n - 1) S(n - 2).
+// The letters are made one at a time by recursive descent, so the word is never stored and only a
+// stack of at most n + 1 words that still have to be written is kept.
+pub struct FibonacciLetters {
+    stack: Vec<i32>, // The words left to write, last first. -1 stands for the letter 1
+}
+
+pub fn fibonacci_letters(n: u16) -> FibonacciLetters {
+    FibonacciLetters {
+        stack: vec![n as i32],
+    }
+}
+
+impl Iterator for FibonacciLetters {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            match self.stack.pop()? {
+                -1 => return Some(b'1'),
+                0 => return Some(b'0'),
+                1 => self.stack.extend([-1, 0]),
+                k => self.stack.extend([k - 2, k - 1]),
+            }
+        }
+    }
+}
+
+// The number of 0s and 1s in the n-th Fibonacci word, which are both Fibonacci numbers
+pub fn fibonacci_letter_counts(n: u16) -> (u64, u64) {
+    let (mut zeros, mut ones) = ((1, 1), (0, 1));
+    for _ in 1..n {
+        zeros = (zeros.1, zeros.0 + zeros.1);
+        ones = (ones.1, ones.0 + ones.1);
+    }
+    if n == 0 {
+        (zeros.0, ones.0)
+    } else {
+        (zeros.1, ones.1)
+    }
+}
+
+// The first letters of the mechanical word with the given slope and intercept, the k-th letter is
+// floor((k + 1) * slope + intercept) - floor(k * slope + intercept). The slope 2 - φ with the same
+// intercept gives the Fibonacci word.
+pub fn sturmian_word(slope: f64, intercept: f64, len: usize) -> Vec<u8> {
+    let line = |k: usize| (k as f64 * slope + intercept).floor();
+    (0..len)
+        .map(|k| {
+            if line(k + 1) - line(k) >= 1.0 {
+                b'1'
+            } else {
+                b'0'
+            }
+        })
+        .collect()
+}
+
+// The number of different factors of every length from 1 to max_len
+pub fn factor_complexity(word: &[u8], max_len: usize) -> Vec<usize> {
+    (1..=max_len)
+        .map(|n| word.windows(n).collect::<HashSet<_>>().len())
+        .collect()
+}
+
+// The lengths of the prefixes of the word that are palindromes
+pub fn palindromic_prefixes(word: &[u8]) -> Vec<usize> {
+    (1..=word.len())
+        .filter(|&n| word[..n].iter().eq(word[..n].iter().rev()))
+        .collect()
+}
+
+// The longest palindrome centered at every position of the word, as (start, length).
+// Uses Manacher's algorithm on the word with a gap between every two letters.
+// Source: https://en.wikipedia.org/wiki/Longest_palindromic_substring
+pub fn maximal_palindromes(word: &[u8]) -> Vec<(usize, usize)> {
+    let gaps = 2 * word.len() + 1;
+    let letter = |i: usize| if i % 2 == 1 { Some(word[i / 2]) } else { None };
+    let mut radius = vec![0; gaps];
+    let (mut center, mut right) = (0, 0);
+    for i in 0..gaps {
+        let mut r = if i < right {
+            radius[2 * center - i].min(right - i)
+        } else {
+            0
+        };
+        while i > r && i + r + 1 < gaps && letter(i - r - 1) == letter(i + r + 1) {
+            r += 1;
+        }
+        radius[i] = r;
+        if i + r > right {
+            center = i;
+            right = i + r;
+        }
+    }
+
+    radius
+        .iter()
+        .enumerate()
+        .filter(|&(_, &r)| r > 0)
+        .map(|(i, &r)| ((i - r) / 2, r))
+        .collect()
+}
+
+// Every different square ww in the word as (start of the first one, length of w)
+pub fn squares(word: &[u8]) -> Vec<(usize, usize)> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for start in 0..word.len() {
+        for half in 1..=(word.len() - start) / 2 {
+            let (w, next) = (
+                &word[start..start + half],
+                &word[start + half..start + 2 * half],
+            );
+            if w == next && seen.insert(w) {
+                found.push((start, half));
+            }
+        }
+    }
+    found
+}
+
+// A substitution that replaces every letter with a word, like 0 -> 01 and 1 -> 0 for the
+// Fibonacci word. Iterating it from a letter gives a morphic word.
+// Source: https://en.wikipedia.org/wiki/Morphic_word
+#[derive(Clone, Debug, PartialEq)]
+pub struct Morphism {
+    pub alphabet: Vec<char>, // Every letter, in the order they first appear
+    images: BTreeMap<char, Vec<char>>, // The word that every letter is replaced with
+}
+
+impl Morphism {
+    // Parses one rule per line, like "0 -> 01". Lines that are empty or start with # are
+    // ignored, and letters without a rule are left as they are.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut alphabet = Vec::new();
+        let mut images = BTreeMap::new();
+        let add = |letter: char, alphabet: &mut Vec<char>| {
+            if !alphabet.contains(&letter) {
+                alphabet.push(letter);
+            }
+        };
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (letter, image) = line
+                .split_once("->")
+                .ok_or_else(|| format!("Missing \"->\" in \"{}\"", line))?;
+            let mut letters = letter.trim().chars();
+            let letter = match (letters.next(), letters.next()) {
+                (Some(letter), None) => letter,
+                _ => return Err(format!("\"{}\" should be a single letter", letter.trim())),
+            };
+            let image: Vec<char> = image.chars().filter(|c| !c.is_whitespace()).collect();
+            if image.is_empty() {
+                return Err(format!("The image of {} is empty", letter));
+            }
+
+            add(letter, &mut alphabet);
+            for &c in &image {
+                add(c, &mut alphabet);
+            }
+            if images.insert(letter, image).is_some() {
+                return Err(format!("{} has more than one rule", letter));
+            }
+        }
+
+        if alphabet.is_empty() {
+            return Err("There are no rules".to_owned());
+        }
+        Ok(Self { alphabet, images })
+    }
+
+    // Applies the morphism to the start word the given number of times. Stops early if the word
+    // would get longer than max_len, the returned count is the number of times it was applied.
+    pub fn iterate(&self, start: &[char], iterations: usize, max_len: usize) -> (Vec<char>, usize) {
+        let mut word = start.to_vec();
+        for i in 0..iterations {
+            let mut next = Vec::with_capacity(word.len());
+            for c in &word {
+                match self.images.get(c) {
+                    Some(image) => next.extend_from_slice(image),
+                    None => next.push(*c),
+                }
+                if next.len() > max_len {
+                    return (word, i);
+                }
+            }
+            word = next;
+        }
+        (word, iterations)
+    }
+
+    // The incidence matrix, the entry in row i and column j is how often the i-th letter of the
+    // alphabet appears in the image of the j-th one
+    fn incidence_matrix(&self) -> Vec<Vec<f64>> {
+        let k = self.alphabet.len();
+        let mut matrix = vec![vec![0.0; k]; k];
+        for (j, letter) in self.alphabet.iter().enumerate() {
+            let image = self
+                .images
+                .get(letter)
+                .cloned()
+                .unwrap_or_else(|| vec![*letter]);
+            for c in image {
+                let i = self.alphabet.iter().position(|&a| a == c).unwrap();
+                matrix[i][j] += 1.0;
+            }
+        }
+        matrix
+    }
+
+    // The direction every letter moves in when the word is drawn as a broken line and projected
+    // onto the contracting plane of the incidence matrix. For a Pisot substitution like
+    // Tribonacci the projected points stay bounded and fill the Rauzy fractal.
+    // Returns None if the matrix has no eigenvalue smaller than 1.
+    // Source: https://en.wikipedia.org/wiki/Rauzy_fractal
+    pub fn contracting_projection(&self) -> Option<Vec<(f64, f64)>> {
+        let matrix = self.incidence_matrix();
+        let k = matrix.len();
+        let roots = polynomial_roots(&characteristic_polynomial(&matrix));
+
+        // the largest contracting eigenvalue, preferring complex ones which span a plane. Roots
+        // that didn't converge aren't finite and are left out.
+        let contracting = roots
+            .iter()
+            .filter(|r| r.is_finite() && r.norm() < 1.0 - 1e-9 && r.im >= -1e-9)
+            .max_by(|a, b| {
+                (a.im.abs() > 1e-9)
+                    .cmp(&(b.im.abs() > 1e-9))
+                    .then(a.norm().total_cmp(&b.norm()))
+            })?;
+
+        // a left eigenvector of the matrix, which is an eigenvector of its transpose
+        let transposed: Vec<Vec<Complex<f64>>> = (0..k)
+            .map(|i| {
+                (0..k)
+                    .map(|j| {
+                        let diagonal = if i == j { *contracting } else { ZERO };
+                        Complex::new(matrix[j][i], 0.0) - diagonal
+                    })
+                    .collect()
+            })
+            .collect();
+        let vector = null_vector(transposed)?;
+        Some(vector.iter().map(|v| (v.re, v.im)).collect())
+    }
+}
+
+const ZERO: Complex<f64> = Complex::new(0.0, 0.0);
+const ONE: Complex<f64> = Complex::new(1.0, 0.0);
+
+// The coefficients of det(xI - A), from x^k down to the constant, with the Faddeev-LeVerrier
+// algorithm
+// Source: https://en.wikipedia.org/wiki/Faddeev%E2%80%93LeVerrier_algorithm
+fn characteristic_polynomial(matrix: &[Vec<f64>]) -> Vec<f64> {
+    let k = matrix.len();
+    let multiply = |a: &Vec<Vec<f64>>, b: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        (0..k)
+            .map(|i| {
+                (0..k)
+                    .map(|j| (0..k).map(|m| a[i][m] * b[m][j]).sum())
+                    .collect()
+            })
+            .collect()
+    };
+
+    let mut coefficients = vec![1.0];
+    let mut m = vec![vec![0.0; k]; k];
+    for n in 1..=k {
+        // M = A M_prev + c_prev I
+        m = multiply(&m, matrix);
+        let previous = *coefficients.last().unwrap();
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] += previous;
+        }
+        let am = multiply(&m, matrix);
+        let trace: f64 = (0..k).map(|i| am[i][i]).sum();
+        coefficients.push(-trace / n as f64);
+    }
+    coefficients
+}
+
+// Finds every root of a monic polynomial at once with the Durand-Kerner method
+// Source: https://en.wikipedia.org/wiki/Durand%E2%80%93Kerner_method
+fn polynomial_roots(coefficients: &[f64]) -> Vec<Complex<f64>> {
+    let degree = coefficients.len() - 1;
+    let evaluate = |x: Complex<f64>| {
+        coefficients
+            .iter()
+            .fold(ZERO, |sum, &c| sum * x + Complex::new(c, 0.0))
+    };
+
+    // the starting guesses are powers of a number that isn't real or a root of unity
+    let seed = Complex::new(0.4, 0.9);
+    let mut roots: Vec<Complex<f64>> = (0..degree)
+        .scan(ONE, |power, _| {
+            *power *= seed;
+            Some(*power)
+        })
+        .collect();
+    for _ in 0..500 {
+        for i in 0..degree {
+            let mut denominator = ONE;
+            for j in 0..degree {
+                if i != j {
+                    denominator *= roots[i] - roots[j];
+                }
+            }
+            let step = evaluate(roots[i]) / denominator;
+            roots[i] -= step;
+        }
+    }
+    roots
+}
+
+// A vector v with Av = 0 for a singular matrix, found with Gaussian elimination
+fn null_vector(mut a: Vec<Vec<Complex<f64>>>) -> Option<Vec<Complex<f64>>> {
+    let k = a.len();
+    let mut pivots = Vec::new();
+    let mut row = 0;
+    for col in 0..k {
+        // the largest entry makes a stable pivot
+        let best = (row..k).max_by(|&x, &y| a[x][col].norm().total_cmp(&a[y][col].norm()));
+        let best = match best {
+            Some(best) if a[best][col].norm() > 1e-9 => best,
+            _ => continue,
+        };
+        a.swap(row, best);
+        let pivot_row = a[row].clone();
+        for (other, values) in a.iter_mut().enumerate() {
+            if other != row {
+                let factor = values[col] / pivot_row[col];
+                for (value, &pivot) in values.iter_mut().zip(&pivot_row).skip(col) {
+                    *value -= factor * pivot;
+                }
+            }
+        }
+        pivots.push(col);
+        row += 1;
+    }
+
+    // the first column without a pivot is free, the pivot columns follow from it
+    let free = (0..k).find(|c| !pivots.contains(c))?;
+    let mut vector = vec![ZERO; k];
+    vector[free] = ONE;
+    for (r, &col) in pivots.iter().enumerate() {
+        vector[col] = -a[r][free] / a[r][col];
+    }
+    Some(vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The roots sorted by their size, largest first
+    fn roots_by_size(coefficients: &[f64]) -> Vec<Complex<f64>> {
+        let mut roots = polynomial_roots(coefficients);
+        roots.sort_by(|a, b| b.norm().total_cmp(&a.norm()));
+        roots
+    }
+
+    #[test]
+    fn factor_complexity_of_sturmian_words() {
+        // Sturmian words have exactly n + 1 factors of length n
+        let word: Vec<u8> = fibonacci_letters(15).collect();
+        assert_eq!(word.len(), 1597);
+        let expected: Vec<usize> = (2..=21).collect();
+        assert_eq!(factor_complexity(&word, 20), expected);
+
+        let word = sturmian_word(2.0_f64.sqrt() - 1.0, 0.0, 1000);
+        assert_eq!(factor_complexity(&word, 20), expected);
+    }
+
+    #[test]
+    fn factor_complexity_of_thue_morse() {
+        let morphism = Morphism::parse("0 -> 01\n1 -> 10").unwrap();
+        let (word, _) = morphism.iterate(&['0'], 10, 10000);
+        let word: Vec<u8> = word.iter().map(|&c| c as u8).collect();
+        assert_eq!(
+            factor_complexity(&word, 8),
+            vec![2, 4, 6, 10, 12, 16, 20, 22]
+        );
+    }
+
+    #[test]
+    fn palindromes() {
+        assert_eq!(palindromic_prefixes(b"abaaba"), vec![1, 3, 6]);
+
+        // the palindromes around every letter and between every two letters, in order
+        assert_eq!(
+            maximal_palindromes(b"abaab"),
+            vec![(0, 1), (0, 3), (2, 1), (1, 4), (3, 1), (4, 1)]
+        );
+        assert_eq!(
+            maximal_palindromes(b"aaa"),
+            vec![(0, 1), (0, 2), (0, 3), (1, 2), (2, 1)]
+        );
+        assert_eq!(maximal_palindromes(b""), vec![]);
+    }
+
+    #[test]
+    fn squares_are_found_once() {
+        assert_eq!(squares(b"aabab"), vec![(0, 1), (1, 2)]);
+        assert_eq!(squares(b"aaaa"), vec![(0, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn fibonacci_incidence_matrix() {
+        let morphism = Morphism::parse("0 -> 01\n1 -> 0").unwrap();
+        let polynomial = characteristic_polynomial(&morphism.incidence_matrix());
+        assert_eq!(polynomial, vec![1.0, -1.0, -1.0]);
+
+        // x^2 = x + 1 has the roots φ and -1 / φ
+        let roots = roots_by_size(&polynomial);
+        assert!((roots[0] - Complex::new(GOLDEN_RATIO, 0.0)).norm() < 1e-9);
+        assert!((roots[1] - Complex::new(-1.0 / GOLDEN_RATIO, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn tribonacci_incidence_matrix() {
+        let morphism = Morphism::parse("1 -> 12\n2 -> 13\n3 -> 1").unwrap();
+        let polynomial = characteristic_polynomial(&morphism.incidence_matrix());
+        assert_eq!(polynomial, vec![1.0, -1.0, -1.0, -1.0]);
+
+        // the Tribonacci constant, and a complex pair with |λ|^2 = 1 / 1.839...
+        let tribonacci = 1.839_286_755_214_161;
+        let roots = roots_by_size(&polynomial);
+        assert!((roots[0] - Complex::new(tribonacci, 0.0)).norm() < 1e-9);
+        for root in &roots[1..] {
+            assert!((root.norm() - (1.0 / tribonacci).sqrt()).abs() < 1e-9);
+            assert!(root.im.abs() > 0.1);
+        }
+        assert!((roots[1] - roots[2].conj()).norm() < 1e-9);
+    }
+
+    #[test]
+    fn contracting_projection_is_a_left_eigenvector() {
+        let morphism = Morphism::parse("1 -> 12\n2 -> 13\n3 -> 1").unwrap();
+        let matrix = morphism.incidence_matrix();
+        let vector: Vec<Complex<f64>> = morphism
+            .contracting_projection()
+            .unwrap()
+            .iter()
+            .map(|&(re, im)| Complex::new(re, im))
+            .collect();
+
+        // v M = λ v for the contracting eigenvalue λ
+        let product: Vec<Complex<f64>> = (0..3)
+            .map(|j| (0..3).map(|i| vector[i] * matrix[i][j]).sum())
+            .collect();
+        let eigenvalue = product[0] / vector[0];
+        assert!((eigenvalue.norm() - 0.737_352_705_760_328).abs() < 1e-9);
+        for j in 0..3 {
+            assert!((product[j] - eigenvalue * vector[j]).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn expanding_morphisms_have_no_contracting_projection() {
+        // 0 -> 00 and 1 -> 11 only has the eigenvalue 2
+        let morphism = Morphism::parse("0 -> 00\n1 -> 11").unwrap();
+        assert_eq!(morphism.contracting_projection(), None);
+    }
+
+    #[test]
+    fn morphism_parse() {
+        let morphism = Morphism::parse("# Thue-Morse\na -> ab\n\n b -> b a ").unwrap();
+        assert_eq!(morphism.alphabet, vec!['a', 'b']);
+        let (word, iterations) = morphism.iterate(&['a'], 3, 100);
+        assert_eq!(word.iter().collect::<String>(), "abbabaab");
+        assert_eq!(iterations, 3);
+
+        // letters without a rule stay as they are
+        let morphism = Morphism::parse("0 -> 02").unwrap();
+        assert_eq!(morphism.alphabet, vec!['0', '2']);
+        let (word, _) = morphism.iterate(&['0'], 2, 100);
+        assert_eq!(word.iter().collect::<String>(), "022");
+    }
+
+    #[test]
+    fn morphism_parse_errors() {
+        assert_eq!(
+            Morphism::parse("0 01"),
+            Err(String::from("Missing \"->\" in \"0 01\""))
+        );
+        assert_eq!(
+            Morphism::parse("01 -> 0"),
+            Err(String::from("\"01\" should be a single letter"))
+        );
+        assert_eq!(
+            Morphism::parse("0 ->  "),
+            Err(String::from("The image of 0 is empty"))
+        );
+        assert_eq!(
+            Morphism::parse("0 -> 1\n0 -> 2"),
+            Err(String::from("0 has more than one rule"))
+        );
+        assert_eq!(
+            Morphism::parse("# nothing"),
+            Err(String::from("There are no rules"))
+        );
+    }
+
+    #[test]
+    fn iterate_stops_at_the_longest_word() {
+        let morphism = Morphism::parse("0 -> 01\n1 -> 0").unwrap();
+        let (word, iterations) = morphism.iterate(&['0'], 10, 20);
+        // 0, 01, 010, 01001, 01001010, 0100101001001 are all short enough
+        assert_eq!(word.len(), 13);
+        assert_eq!(iterations, 5);
+    }
+}
@@ -1,5 +1,10 @@
 // Imports and Settings
+use crate::apps::FractalApp;
+use crate::dimension::DimensionPanel;
+use crate::export::ExportPanel;
+use crate::timeline::Timeline;
 use eframe::{egui, epi};
+use std::collections::BTreeMap;
 
 #[derive(Default)]
 
@@ -10,20 +15,40 @@ switch between the fractals through a nav bar at the top of the screen.
 Source: https://github.com/emilk/egui/tree/master/egui_demo_lib
 */
 
-pub struct Apps {
+pub(crate) struct Apps {
     fibonacci: crate::apps::FibonacciWord,
+    morphic: crate::apps::MorphicWord,
     htree: crate::apps::HTree,
+    tree: crate::apps::FractalTree,
+    lsystem: crate::apps::LSystem,
     sierpinski: crate::apps::SierpinskiCarpet,
+    triangle: crate::apps::SierpinskiTriangle,
+    ifs: crate::apps::Ifs,
+    flame: crate::apps::FractalFlame,
+    attractors: crate::apps::StrangeAttractor,
+    bifurcation: crate::apps::BifurcationDiagram,
+    lyapunov: crate::apps::LyapunovFractal,
+    solids: crate::apps::FractalSolid,
     mandelbrot: crate::apps::Mandelbrot,
 }
 
 impl Apps {
-    fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut dyn epi::App)> {
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut dyn FractalApp)> {
         vec![
-            ("mandelbrot", &mut self.mandelbrot as &mut dyn epi::App),
-            ("sierpinski", &mut self.sierpinski as &mut dyn epi::App),
-            ("fibonacci", &mut self.fibonacci as &mut dyn epi::App),
-            ("htree", &mut self.htree as &mut dyn epi::App),
+            ("mandelbrot", &mut self.mandelbrot as &mut dyn FractalApp),
+            ("sierpinski", &mut self.sierpinski as &mut dyn FractalApp),
+            ("triangle", &mut self.triangle as &mut dyn FractalApp),
+            ("ifs", &mut self.ifs as &mut dyn FractalApp),
+            ("flame", &mut self.flame as &mut dyn FractalApp),
+            ("attractors", &mut self.attractors as &mut dyn FractalApp),
+            ("bifurcation", &mut self.bifurcation as &mut dyn FractalApp),
+            ("lyapunov", &mut self.lyapunov as &mut dyn FractalApp),
+            ("solids", &mut self.solids as &mut dyn FractalApp),
+            ("fibonacci", &mut self.fibonacci as &mut dyn FractalApp),
+            ("morphic", &mut self.morphic as &mut dyn FractalApp),
+            ("htree", &mut self.htree as &mut dyn FractalApp),
+            ("tree", &mut self.tree as &mut dyn FractalApp),
+            ("lsystem", &mut self.lsystem as &mut dyn FractalApp),
         ]
         .into_iter()
     }
@@ -34,6 +59,12 @@ impl Apps {
 pub struct WrapApp {
     selected_anchor: String,
     apps: Apps,
+    show_timeline: bool,
+    timelines: BTreeMap<String, Timeline>, // One keyframe timeline per fractal
+    show_export: bool,
+    export: ExportPanel,
+    show_dimension: bool,
+    dimension: DimensionPanel,
 }
 
 impl epi::App for WrapApp {
@@ -61,7 +92,43 @@ impl epi::App for WrapApp {
 
         // Handles showing the chosen fractal to display
         for (anchor, app) in self.apps.iter_mut() {
-            if anchor == self.selected_anchor || ctx.memory().everything_is_visible() {
+            // Exports keep running in the background when switching fractals
+            self.export.update(ctx, anchor, app);
+
+            if anchor == self.selected_anchor {
+                let timeline = self.timelines.entry(anchor.to_owned()).or_default();
+                let mut scrubbed = false;
+
+                let export = &mut self.export;
+                egui::Window::new("Export")
+                    .open(&mut self.show_export)
+                    .show(ctx, |ui| export.ui(ui, anchor, app, timeline));
+
+                // The timeline panel has to be added before the fractal's central panel
+                if self.show_timeline {
+                    egui::TopBottomPanel::bottom("wrap_app_timeline").show(ctx, |ui| {
+                        scrubbed = timeline.ui(ui, app);
+                    });
+                }
+
+                // The dimension is measured in a side panel, also added before the central panel
+                if self.show_dimension {
+                    let dimension = &mut self.dimension;
+                    egui::SidePanel::right("wrap_app_dimension").show(ctx, |ui| {
+                        egui::ScrollArea::vertical().show(ui, |ui| dimension.ui(ui, anchor, app));
+                    });
+                }
+
+                // Drives the fractal's parameters from the keyframes
+                let was_playing = timeline.playing;
+                timeline.advance(ctx.input().unstable_dt);
+                if scrubbed || was_playing {
+                    timeline.apply(app);
+                    ctx.request_repaint();
+                }
+
+                app.update(ctx, frame);
+            } else if ctx.memory().everything_is_visible() {
                 app.update(ctx, frame);
             }
         }
@@ -92,6 +159,12 @@ impl WrapApp {
                     }
                 }
             }
+
+            ui.separator();
+
+            ui.checkbox(&mut self.show_timeline, "Timeline");
+            ui.checkbox(&mut self.show_export, "Export");
+            ui.checkbox(&mut self.show_dimension, "Dimension");
         });
     }
 }
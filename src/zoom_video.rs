@@ -0,0 +1,589 @@
+use crate::export::{ExportFormat, ExportSettings, FrameWriter};
+use crate::palette;
+use crate::raster::Image;
+use crate::timeline::Easing;
+use eframe::egui;
+use egui::{widgets::*, *};
+use std::f64::consts::LN_2;
+
+/*
+This file renders smooth zoom videos into the Mandelbrot set. The magnification grows
+exponentially over time, so the zoom looks like it has a constant speed no matter how deep it goes.
+The view is scaled around the target point, which keeps the target at the same place on screen
+while the start view shrinks towards it.
+
+Rendering every frame from scratch is slow, so this uses the usual zoom-movie technique:
+anchor frames are rendered at twice the output resolution, one for every doubling of the
+magnification. Every video frame is then resampled from the two anchors that surround its
+magnification, using the finer anchor wherever it covers the frame. Anchors store smooth
+escape counts instead of colors, so the palette can still be cycled from frame to frame.
+
+Sources:
+https://en.wikipedia.org/wiki/Plotting_algorithms_for_the_Mandelbrot_set
+https://mathr.co.uk/blog/2010-08-31_optimizing_zoom_animations.html
+*/
+
+const OVERSAMPLING: usize = 2; // How much larger the anchors are than the video frames
+const ROWS_PER_STEP: usize = 16; // Anchor rows rendered per step, keeps the GUI responsive
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ZoomPath {
+    pub start_x: f64, // The center of the first frame
+    pub start_y: f64,
+    pub start_width: f64, // The width of the first frame in the complex plane
+    pub target_x: f64,    // The point that is zoomed into
+    pub target_y: f64,
+    pub magnification: f64, // How much the last frame is zoomed in compared to the first
+    pub duration: f32,      // The length of the video in seconds
+    pub easing: Easing,     // Applied to the logarithm of the magnification
+    pub base_steps: u32,    // The iteration limit of the first frame
+    pub steps_per_octave: u32, // Extra iterations for every doubling of the magnification
+    pub palette_period: f32, // The number of iterations per color cycle
+    pub palette_speed: f32, // Color cycles per second
+}
+
+impl Default for ZoomPath {
+    fn default() -> Self {
+        Self {
+            start_x: -0.765,
+            start_y: 0.0,
+            start_width: 3.2,
+            // Seahorse valley
+            target_x: -0.743_643_887_037_151,
+            target_y: 0.131_825_904_205_33,
+            magnification: 1e9,
+            duration: 20.0,
+            easing: Easing::Linear,
+            base_steps: 100,
+            steps_per_octave: 30,
+            palette_period: 48.0,
+            palette_speed: 0.1,
+        }
+    }
+}
+
+impl ZoomPath {
+    // Points that make for good zoom targets
+    pub const PRESETS: [(&'static str, f64, f64); 4] = [
+        (
+            "Seahorse Valley",
+            -0.743_643_887_037_151,
+            0.131_825_904_205_33,
+        ),
+        ("Elephant Valley", 0.285, 0.01),
+        (
+            "Misiurewicz Point",
+            -0.101_096_363_845_622,
+            0.956_286_510_809_142,
+        ),
+        ("Mini Mandelbrot", -1.768_778_833, 0.001_738_996),
+    ];
+
+    // The magnification at a time, interpolated exponentially
+    pub fn magnification_at(&self, time: f32) -> f64 {
+        let t = if self.duration > 0.0 {
+            self.easing.apply(time / self.duration) as f64
+        } else {
+            1.0
+        };
+        self.magnification.max(1.0).powf(t)
+    }
+
+    // The center and width of the view at a magnification. The offset from the target shrinks
+    // with the width, so the target stays at the same place on screen.
+    pub fn view(&self, magnification: f64) -> (f64, f64, f64) {
+        let center_x = self.target_x + (self.start_x - self.target_x) / magnification;
+        let center_y = self.target_y + (self.start_y - self.target_y) / magnification;
+        (center_x, center_y, self.start_width / magnification)
+    }
+
+    // The iteration limit grows with the depth, deeper views need more steps to resolve
+    pub fn max_steps(&self, magnification: f64) -> u32 {
+        let octaves = magnification.max(1.0).log2();
+        self.base_steps + (self.steps_per_octave as f64 * octaves) as u32
+    }
+
+    // The number of doublings from the first to the last frame
+    pub fn octaves(&self) -> f64 {
+        self.magnification.max(1.0).log2()
+    }
+}
+
+// Returns the smooth escape count of a point, or a negative number if it never escaped
+pub fn smooth_escape(cx: f64, cy: f64, max_steps: u32) -> f32 {
+    // Points in the main cardioid and the period-2 bulb never escape
+    let q = (cx - 0.25) * (cx - 0.25) + cy * cy;
+    if q * (q + (cx - 0.25)) <= 0.25 * cy * cy || (cx + 1.0) * (cx + 1.0) + cy * cy <= 0.0625 {
+        return -1.0;
+    }
+
+    let (mut x, mut y) = (0.0_f64, 0.0_f64);
+    for i in 0..max_steps {
+        let x2 = x * x;
+        let y2 = y * y;
+        // A large bailout radius makes the smooth count accurate
+        if x2 + y2 > 256.0 {
+            let log_z = (x2 + y2).ln() / 2.0;
+            let nu = (log_z / LN_2).ln() / LN_2;
+            return (i as f64 + 1.0 - nu) as f32;
+        }
+        y = 2.0 * x * y + cy;
+        x = x2 - y2 + cx;
+    }
+    -1.0
+}
+
+// A pre-rendered frame at a magnification of 2^level
+struct Anchor {
+    level: i32,
+    center_x: f64,
+    center_y: f64,
+    width: f64,
+    height: f64,
+    pixels_x: usize,
+    pixels_y: usize,
+    steps: Vec<f32>, // Smooth escape counts, negative inside the set
+    rows_done: usize,
+}
+
+impl Anchor {
+    fn new(path: &ZoomPath, level: i32, frame_width: usize, frame_height: usize) -> Self {
+        let (center_x, center_y, width) = path.view(2.0_f64.powi(level));
+        let pixels_x = frame_width * OVERSAMPLING;
+        let pixels_y = frame_height * OVERSAMPLING;
+        Self {
+            level,
+            center_x,
+            center_y,
+            width,
+            height: width * pixels_y as f64 / pixels_x as f64,
+            pixels_x,
+            pixels_y,
+            steps: vec![0.0; pixels_x * pixels_y],
+            rows_done: 0,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.rows_done >= self.pixels_y
+    }
+
+    // Renders the next few rows of the anchor
+    fn render_rows(&mut self, max_steps: u32, rows: usize) {
+        let end = (self.rows_done + rows).min(self.pixels_y);
+        for row in self.rows_done..end {
+            let cy = self.center_y + (row as f64 + 0.5) / self.pixels_y as f64 * self.height
+                - self.height / 2.0;
+            for column in 0..self.pixels_x {
+                let cx = self.center_x + (column as f64 + 0.5) / self.pixels_x as f64 * self.width
+                    - self.width / 2.0;
+                self.steps[row * self.pixels_x + column] = smooth_escape(cx, cy, max_steps);
+            }
+        }
+        self.rows_done = end;
+    }
+
+    // Samples the anchor at a point of the complex plane, None if it lies outside of the anchor
+    fn sample(&self, cx: f64, cy: f64) -> Option<f32> {
+        let fx = ((cx - self.center_x) / self.width + 0.5) * self.pixels_x as f64 - 0.5;
+        let fy = ((cy - self.center_y) / self.height + 0.5) * self.pixels_y as f64 - 0.5;
+        if fx < 0.0
+            || fy < 0.0
+            || fx > (self.pixels_x - 1) as f64
+            || fy > (self.pixels_y - 1) as f64
+        {
+            return None;
+        }
+
+        let x0 = (fx.floor() as usize).min(self.pixels_x - 2);
+        let y0 = (fy.floor() as usize).min(self.pixels_y - 2);
+        let tx = (fx - x0 as f64) as f32;
+        let ty = (fy - y0 as f64) as f32;
+        let corners = [
+            self.steps[y0 * self.pixels_x + x0],
+            self.steps[y0 * self.pixels_x + x0 + 1],
+            self.steps[(y0 + 1) * self.pixels_x + x0],
+            self.steps[(y0 + 1) * self.pixels_x + x0 + 1],
+        ];
+
+        // Bilinear filtering only makes sense when all four corners escaped
+        if corners.iter().any(|steps| *steps < 0.0) {
+            let nearest = (tx.round() as usize) + 2 * (ty.round() as usize);
+            return Some(corners[nearest]);
+        }
+        let top = corners[0] + (corners[1] - corners[0]) * tx;
+        let bottom = corners[2] + (corners[3] - corners[2]) * tx;
+        Some(top + (bottom - top) * ty)
+    }
+}
+
+// A zoom video in progress, every step renders part of an anchor or writes a frame
+pub struct ZoomVideo {
+    path: ZoomPath,
+    settings: ExportSettings,
+    writer: Option<FrameWriter>,
+    frames: u32,
+    next_frame: u32,
+    anchors: Vec<Anchor>, // The anchors needed for the next frame, coarsest first
+}
+
+impl ZoomVideo {
+    pub fn start(path: ZoomPath, settings: ExportSettings) -> Result<Self, String> {
+        if settings.width < 2 || settings.height < 2 || settings.fps == 0 {
+            return Err(String::from("The size and frame rate must be positive"));
+        }
+
+        // The view shrinks towards the target, so it has to be visible from the start
+        let start_height = path.start_width * settings.height as f64 / settings.width as f64;
+        if (path.target_x - path.start_x).abs() > path.start_width / 2.0
+            || (path.target_y - path.start_y).abs() > start_height / 2.0
+        {
+            return Err(String::from(
+                "The target has to be inside of the start view",
+            ));
+        }
+
+        let frames = settings.frame_count(path.duration);
+        let writer = FrameWriter::create(&settings, frames)?;
+        Ok(Self {
+            path,
+            settings,
+            writer: Some(writer),
+            frames,
+            next_frame: 0,
+            anchors: Vec::new(),
+        })
+    }
+
+    // Does a bit of work, returns true once every frame has been written
+    pub fn step(&mut self) -> Result<bool, String> {
+        if self.is_done() {
+            return Ok(true);
+        }
+
+        let time = self
+            .settings
+            .frame_time(self.next_frame)
+            .min(self.path.duration);
+        let magnification = self.path.magnification_at(time);
+        let level = (magnification.log2().floor() as i32).clamp(0, self.path.octaves() as i32);
+
+        // Anchors that are no longer needed are dropped, the zoom only ever goes deeper
+        self.anchors.retain(|anchor| anchor.level >= level);
+        for needed in level..=level + 1 {
+            if !self.anchors.iter().any(|anchor| anchor.level == needed) {
+                self.anchors.push(Anchor::new(
+                    &self.path,
+                    needed,
+                    self.settings.width,
+                    self.settings.height,
+                ));
+            }
+        }
+        self.anchors.sort_by_key(|anchor| anchor.level);
+
+        // Keep rendering the anchors before the frame can be written
+        if let Some(anchor) = self.anchors.iter_mut().find(|anchor| !anchor.is_done()) {
+            let max_steps = self.path.max_steps(2.0_f64.powi(anchor.level));
+            anchor.render_rows(max_steps, ROWS_PER_STEP);
+            return Ok(false);
+        }
+
+        let image = self.render_frame(magnification, time);
+        if let Some(writer) = &mut self.writer {
+            writer.write_frame(&image)?;
+        }
+        self.next_frame += 1;
+
+        if self.is_done() {
+            if let Some(writer) = self.writer.take() {
+                writer.finish()?;
+            }
+        }
+        Ok(self.is_done())
+    }
+
+    // Resamples the anchors into a video frame
+    fn render_frame(&self, magnification: f64, time: f32) -> Image {
+        let (width, height) = (self.settings.width, self.settings.height);
+        let (center_x, center_y, view_width) = self.path.view(magnification);
+        let view_height = view_width * height as f64 / width as f64;
+        let phase = time * self.path.palette_speed;
+
+        let mut image = Image::new(width, height, Color32::BLACK);
+        for y in 0..height {
+            let cy = center_y + (y as f64 + 0.5) / height as f64 * view_height - view_height / 2.0;
+            for x in 0..width {
+                let cx = center_x + (x as f64 + 0.5) / width as f64 * view_width - view_width / 2.0;
+
+                // The finest anchor that covers the point has the most detail
+                let steps = self
+                    .anchors
+                    .iter()
+                    .rev()
+                    .find_map(|anchor| anchor.sample(cx, cy));
+                if let Some(steps) = steps {
+                    if steps >= 0.0 {
+                        let color = palette::cycling_color(steps, self.path.palette_period, phase);
+                        image.set(x, y, color);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_frame >= self.frames
+    }
+
+    // Progress counted in frames, anchors are spread out over the video
+    pub fn progress(&self) -> f32 {
+        self.next_frame as f32 / self.frames as f32
+    }
+
+    pub fn run(mut self) -> Result<(), String> {
+        while !self.step()? {}
+        Ok(())
+    }
+}
+
+// The zoom video section in the Mandelbrot settings
+pub struct ZoomVideoPanel {
+    path: ZoomPath,
+    settings: ExportSettings,
+    video: Option<ZoomVideo>,
+    status: String,
+}
+
+// Only the settings are compared, so that the reset button of the Mandelbrot app works
+impl PartialEq for ZoomVideoPanel {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.settings == other.settings
+    }
+}
+
+impl Default for ZoomVideoPanel {
+    fn default() -> Self {
+        Self {
+            path: ZoomPath::default(),
+            settings: ExportSettings {
+                format: ExportFormat::PngSequence,
+                width: 640,
+                height: 360,
+                fps: 30,
+                path: String::from("zoom.png"),
+            },
+            video: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl ZoomVideoPanel {
+    // Works on a running video, called every frame
+    pub fn update(&mut self, ctx: &CtxRef) {
+        if let Some(video) = &mut self.video {
+            match video.step() {
+                Ok(false) => ctx.request_repaint(),
+                Ok(true) => {
+                    self.status = format!("Saved {}", self.settings.path);
+                    self.video = None;
+                }
+                Err(error) => {
+                    self.status = format!("Zoom video failed: {}", error);
+                    self.video = None;
+                }
+            }
+        }
+    }
+
+    // The current view of the Mandelbrot app can be used as the start of the path
+    pub fn ui(&mut self, ui: &mut Ui, current_view: (f64, f64, f64)) {
+        if let Some(video) = &self.video {
+            ui.label(format!("Rendering {}", self.settings.path));
+            ui.add(ProgressBar::new(video.progress()).show_percentage());
+            if ui.button("Cancel").clicked() {
+                self.video = None;
+                self.status = String::from("Zoom video cancelled");
+            }
+            return;
+        }
+
+        let path = &mut self.path;
+        ui.horizontal(|ui| {
+            ui.label("Start");
+            ui.add(
+                DragValue::new(&mut path.start_x)
+                    .speed(0.01)
+                    .max_decimals(15),
+            );
+            ui.add(
+                DragValue::new(&mut path.start_y)
+                    .speed(0.01)
+                    .max_decimals(15),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Start width");
+            ui.add(
+                DragValue::new(&mut path.start_width)
+                    .speed(0.01)
+                    .clamp_range(0.000_001..=8.0),
+            );
+            if ui.button("Use Current View").clicked() {
+                path.start_x = current_view.0;
+                path.start_y = current_view.1;
+                path.start_width = current_view.2;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Target");
+            ui.add(
+                DragValue::new(&mut path.target_x)
+                    .speed(0.0001)
+                    .max_decimals(15),
+            );
+            ui.add(
+                DragValue::new(&mut path.target_y)
+                    .speed(0.0001)
+                    .max_decimals(15),
+            );
+        });
+        ComboBox::from_label("Preset")
+            .selected_text("Choose a target")
+            .show_ui(ui, |ui| {
+                for (name, x, y) in ZoomPath::PRESETS.iter() {
+                    if ui.selectable_label(false, *name).clicked() {
+                        path.target_x = *x;
+                        path.target_y = *y;
+                    }
+                }
+            });
+        ui.add(
+            Slider::new(&mut path.magnification, 2.0..=1e13)
+                .logarithmic(true)
+                .text("Magnification"),
+        );
+        ui.add(Slider::new(&mut path.duration, 1.0..=120.0).text("Duration"));
+        ComboBox::from_label("Easing")
+            .selected_text(path.easing.name())
+            .show_ui(ui, |ui| {
+                for easing in Easing::ALL.iter() {
+                    ui.selectable_value(&mut path.easing, *easing, easing.name());
+                }
+            });
+        ui.add(Slider::new(&mut path.base_steps, 25..=1000).text("Start iterations"));
+        ui.add(Slider::new(&mut path.steps_per_octave, 0..=200).text("Iterations per 2x zoom"));
+        ui.add(Slider::new(&mut path.palette_period, 4.0..=256.0).text("Palette period"));
+        ui.add(Slider::new(&mut path.palette_speed, 0.0..=2.0).text("Palette cycling"));
+
+        ui.separator();
+
+        let settings = &mut self.settings;
+        ComboBox::from_label("Format")
+            .selected_text(settings.format.name())
+            .show_ui(ui, |ui| {
+                for format in ExportFormat::ALL.iter() {
+                    ui.selectable_value(&mut settings.format, *format, format.name());
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Size");
+            ui.add(DragValue::new(&mut settings.width).clamp_range(16..=3840));
+            ui.label("x");
+            ui.add(DragValue::new(&mut settings.height).clamp_range(16..=2160));
+        });
+        ui.add(Slider::new(&mut settings.fps, 1..=60).text("FPS"));
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut settings.path);
+        });
+        ui.label(format!(
+            "{} frames from {} anchors, up to {} iterations",
+            settings.frame_count(path.duration),
+            path.octaves().floor() as u32 + 2,
+            path.max_steps(path.magnification)
+        ));
+
+        if ui.button("Render Zoom Video").clicked() {
+            match ZoomVideo::start(self.path.clone(), self.settings.clone()) {
+                Ok(video) => {
+                    self.video = Some(video);
+                    self.status = String::new();
+                }
+                Err(error) => self.status = format!("Zoom video failed: {}", error),
+            }
+        }
+
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+}
+
+// Renders a zoom video from the command line arguments that follow "zoom"
+pub fn zoom_cli(args: &[String]) -> Result<(), String> {
+    let usage = "usage: zoom [--target X Y] [--start X Y WIDTH] [--magnification M] \
+                 [--duration SECONDS] [--fps N] [--size WIDTHxHEIGHT] [--format gif|apng|png] \
+                 [--out PATH] [--iterations BASE PER_OCTAVE] [--palette PERIOD SPEED]";
+
+    let mut path = ZoomPath::default();
+    let mut settings = ZoomVideoPanel::default().settings;
+    let parse_number = |value: String| {
+        value
+            .parse::<f64>()
+            .map_err(|_| format!("'{}' is not a number", value))
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut next = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--target" => {
+                path.target_x = parse_number(next()?)?;
+                path.target_y = parse_number(next()?)?;
+            }
+            "--start" => {
+                path.start_x = parse_number(next()?)?;
+                path.start_y = parse_number(next()?)?;
+                path.start_width = parse_number(next()?)?;
+            }
+            "--magnification" => path.magnification = parse_number(next()?)?,
+            "--duration" => path.duration = parse_number(next()?)? as f32,
+            "--iterations" => {
+                path.base_steps = parse_number(next()?)? as u32;
+                path.steps_per_octave = parse_number(next()?)? as u32;
+            }
+            "--palette" => {
+                path.palette_period = parse_number(next()?)? as f32;
+                path.palette_speed = parse_number(next()?)? as f32;
+            }
+            "--fps" => settings.fps = parse_number(next()?)? as u32,
+            "--size" => {
+                let value = next()?;
+                let (width, height) = value
+                    .split_once('x')
+                    .ok_or_else(|| format!("Size '{}' should look like 640x360", value))?;
+                settings.width = parse_number(width.to_owned())? as usize;
+                settings.height = parse_number(height.to_owned())? as usize;
+            }
+            "--format" => {
+                let value = next()?;
+                settings.format = ExportFormat::from_arg(&value)
+                    .ok_or_else(|| format!("Unknown format '{}'", value))?;
+            }
+            "--out" => settings.path = next()?,
+            _ => return Err(format!("Unknown argument '{}'\n{}", arg, usage)),
+        }
+    }
+
+    let frames = settings.frame_count(path.duration);
+    ZoomVideo::start(path, settings.clone())?.run()?;
+    println!("Wrote {} frames to {}", frames, settings.path);
+    Ok(())
+}